@@ -18,8 +18,16 @@ impl Debug for Layer<'_> {
                 write!(f, " {i} Free -> {} \n", node.children[0])?;
             } else if node.flag == -3 {
                 write!(f, " {i} Fork:   {:?}  \n", node.children)?;
-            } else {
+            } else if node.flag > 0 {
                 write!(f, " {i} Linked Fork: {:?}  \n", node.children)?;
+            } else {
+                write!(
+                    f,
+                    " {i} Branch (shared: {}, {:#010b}): {:?} \n",
+                    node.is_shared(),
+                    node.shared_children(),
+                    node.children
+                )?;
             }
         }
         write!(f, "\n ----------- \n \n")
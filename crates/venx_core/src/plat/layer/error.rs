@@ -0,0 +1,41 @@
+use core::fmt::Display;
+
+/// Reasons why mutation of [super::layer::Layer] can fail
+///
+/// Returned by `try_*` methods, which never panic on their own
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LayerError {
+    /// No free [crate::plat::node::Node]s left in holder-pool
+    OutOfNodes,
+    /// No free [crate::plat::node_l2::NodeL2]s left in holder-pool
+    OutOfL2Nodes,
+    /// Given position is outside of layer
+    OutOfBounds,
+    /// Node on fork level is not a fork, or voxel_id 0 was given to fork
+    InvalidFork,
+    /// Layer is frozen and cannot be mutated, see [super::layer::Layer::freezed]
+    Frozen,
+    /// Layer cannot be edited, see [super::layer::Persistence::ReadOnly]
+    ReadOnly,
+}
+
+impl Display for LayerError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            LayerError::OutOfNodes => write!(f, "You are out of holder-nodes on type: Node"),
+            LayerError::OutOfL2Nodes => {
+                write!(f, "You are out of holder-nodes on type: NodeLevel2")
+            }
+            LayerError::OutOfBounds => write!(f, "Position is out of layer bounds"),
+            LayerError::InvalidFork => write!(f, "Node is not a valid fork"),
+            LayerError::Frozen => write!(f, "Layer is frozen"),
+            LayerError::ReadOnly => write!(f, "Layer is read-only"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+extern crate std;
+
+#[cfg(feature = "std")]
+impl std::error::Error for LayerError {}
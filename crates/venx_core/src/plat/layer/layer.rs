@@ -5,11 +5,16 @@ use crate::plat::{
     node_l2::NodeL2,
 };
 
+use super::error::LayerError;
+
 /// Alias for layer
 pub type Lr<'a> = Layer<'a>;
 
 #[derive(PartialEq)]
 pub struct Layer<'a> {
+    /// Index of layer in [crate::plat::raw_plat::RawPlat::layers]. Set by [crate::plat::raw_plat::RawPlat::push_layer]
+    pub id: usize,
+
     /// Synced depth with RawPlat
     pub depth: usize,
 
@@ -34,6 +39,36 @@ pub struct Layer<'a> {
     ///
     /// [1] root
     pub nodes: &'a mut [Node],
+
+    /// Frozen layer has no free nodes and its nodes might be shared,
+    /// so any mutation would corrupt it. Writes are rejected with [LayerError::Frozen]
+    pub freezed: bool,
+
+    /// Some nodes might be shared between several parents (e.g. after DAG compression),
+    /// so writes copy those on the path first instead of mutating them in place. See [Layer::copy_path]
+    ///
+    /// Shared nodes are marked themselves (see [Node::is_shared]), this is just a hint to skip the check.
+    /// Set by anything which shares nodes, cleared by [Layer::collect_garbage] once nothing is shared
+    pub shared: bool,
+
+    /// What happens to layer on save and load
+    pub persistence: Persistence,
+}
+
+/// Persistence policy of layer, see [Layer::persistence]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "bitcode_support",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub enum Persistence {
+    /// Saved and loaded
+    #[default]
+    Persistent,
+    /// Never saved, re-created empty on load. Not a part of [crate::plat::raw_plat::RawPlat::content_hash]
+    Transient,
+    /// Saved and loaded, but edits are rejected with [LayerError::ReadOnly]
+    ReadOnly,
 }
 
 pub struct ForkIterProps {
@@ -51,6 +86,11 @@ impl<'a> Layer<'a> {
     pub const SCHEM: usize = 2;
     /// Index to Canvas layer
     pub const CANVAS: usize = 3;
+    /// Voxel id of explicit air (tombstone)
+    ///
+    /// Set it on upper layer to hide voxels of layers below, e.g. to dig into frozen Base through Canvas.
+    /// It is never returned by [crate::plat::raw_plat::RawPlat::get_node] and never loaded into chunks
+    pub const AIR: u32 = u32::MAX;
 
     pub fn new(depth: usize, nodes: &'a mut [Node], l2_nodes: &'a mut [NodeL2]) -> Self {
         {
@@ -167,6 +207,32 @@ impl<'a> Layer<'a> {
         free
     }
 
+    /// Check if there are at least `amount` free nodes of given type
+    ///
+    /// Unlike [Layer::free] it walks only first `amount` links, so it is cheap for small amounts
+    pub fn has_free<N: AllocatableNode + 'a>(&self, amount: usize) -> bool {
+        let mut idx = N::get_first_free_link(self) as usize;
+
+        for _ in 0..amount {
+            if idx == 0 {
+                return false;
+            }
+            idx = N::get_node(self, idx).get_child(0) as usize;
+        }
+
+        true
+    }
+
+    /// Chain nodes in `from..to` range as free and link them to holder-pool
+    ///
+    /// Used after layer was extended, new nodes are not linked by default
+    pub fn link_free<N: AllocatableNode + 'a>(&mut self, from: usize, to: usize) {
+        // Deallocate in reverse order, so head points to `from` and pool stays sorted
+        for idx in (from..to).rev() {
+            self.deallocate_node::<N>(idx);
+        }
+    }
+
     /// Deallocate node with holder-pool
     pub fn deallocate_node<N: AllocatableNode + 'a>(&mut self, node_idx: usize) {
         // Append empty linked list
@@ -185,6 +251,22 @@ impl<'a> Layer<'a> {
     }
     /// Allocate node from pool from given node
     pub fn allocate_node_from<N: AllocatableNode + 'a>(&mut self, node: N) -> usize {
+        match self.try_allocate_node_from(node) {
+            Ok(idx) => idx,
+            Err(_) => panic!("You are out of holder-nodes on type: {}", N::name()),
+        }
+    }
+    /// Fallible version of [Layer::allocate_node]
+    pub fn try_allocate_node<N: AllocatableNode + Default + 'a>(
+        &mut self,
+    ) -> Result<usize, LayerError> {
+        self.try_allocate_node_from(N::default())
+    }
+    /// Fallible version of [Layer::allocate_node_from]
+    pub fn try_allocate_node_from<N: AllocatableNode + 'a>(
+        &mut self,
+        node: N,
+    ) -> Result<usize, LayerError> {
         if N::get_first_free_link(self) != 0 {
             // Taking node linked by head
             let allocated_idx = N::get_first_free_link(self) as usize;
@@ -198,9 +280,9 @@ impl<'a> Layer<'a> {
             // Set
             let used_noded = N::get_node_mut(self, allocated_idx); // = node;
             *used_noded = node;
-            allocated_idx
+            Ok(allocated_idx)
         } else {
-            panic!("You are out of holder-nodes on type: {}", N::name());
+            Err(N::out_of_nodes())
         }
     }
 
@@ -251,85 +333,120 @@ impl<'a> Layer<'a> {
         level: usize,
         fork_level: usize,
     ) -> usize {
+        match self.try_set_child(from_idx, voxel_id, child_idx, level, fork_level) {
+            Ok(idx) => idx,
+            Err(e) => panic!("{}", e),
+        }
+    }
+    /// Error if layer cannot be edited: it is [Persistence::ReadOnly] or frozen (see [Layer::freezed])
+    pub fn check_writable(&self) -> Result<(), LayerError> {
+        if self.persistence == Persistence::ReadOnly {
+            return Err(LayerError::ReadOnly);
+        }
+        if self.freezed {
+            return Err(LayerError::Frozen);
+        }
+        Ok(())
+    }
+
+    /// Fallible version of [Layer::set_child]
+    ///
+    /// On error nothing is allocated
+    pub fn try_set_child(
+        &mut self,
+        from_idx: usize,
+        voxel_id: u32,
+        child_idx: usize,
+        level: usize,
+        fork_level: usize,
+    ) -> Result<usize, LayerError> {
+        self.check_writable()?;
+
         if voxel_id == 0 {
-            panic!();
+            return Err(LayerError::InvalidFork);
         }
 
-        let node = &mut self[from_idx];
+        let node_below_idx = self[from_idx].children[child_idx];
 
         // If children of node on from_idx should point to fork
         if level == fork_level + 1 {
-            let node_below_idx = node.children[child_idx];
-
             if node_below_idx == 0 {
                 // Fork does not exist, lets change that
-                let new_branch_idx = self.allocate_node::<Node>();
+                // We need both, branch and fork
+                if !self.has_free::<Node>(2) {
+                    return Err(LayerError::OutOfNodes);
+                }
+                let new_branch_idx = self.try_allocate_node::<Node>()?;
 
                 let fork = Node {
                     flag: -3,
                     children: [voxel_id, new_branch_idx as u32, 0, 0, 0, 0, 0, 0],
                 };
 
-                let fork_idx = self.allocate_node_from(fork);
+                let fork_idx = self.try_allocate_node_from(fork)?;
 
                 self[from_idx].children[child_idx] = fork_idx as u32;
 
-                return new_branch_idx;
-            } else {
-                let mut fork_idx = node_below_idx;
-
-                loop {
-                    let fork = &mut self[fork_idx as usize];
-                    for voxel_id_idx in 0..4 {
-                        let found_voxel_id = fork[voxel_id_idx * 2];
-                        if found_voxel_id == 0 {
-                            // Nothing here, lets fill it
-                            // So we can allocate our new node to voxel_id
-                            let new_branch_idx = self.allocate_node::<Node>();
-
-                            self[fork_idx as usize][voxel_id_idx * 2] = voxel_id;
-                            self[fork_idx as usize][(voxel_id_idx * 2) + 1] = new_branch_idx as u32;
-
-                            return new_branch_idx;
-                        }
-                        if found_voxel_id == voxel_id {
-                            // Bravo, we found idx to forward!
-                            return fork[(voxel_id_idx * 2) + 1] as usize;
-                        }
+                return Ok(new_branch_idx);
+            }
+
+            let mut fork_idx = node_below_idx as usize;
+
+            loop {
+                let fork = &self[fork_idx];
+
+                if !fork.is_fork() {
+                    return Err(LayerError::InvalidFork);
+                }
+
+                for voxel_id_idx in 0..4 {
+                    let found_voxel_id = fork[voxel_id_idx * 2];
+                    if found_voxel_id == 0 {
+                        // Nothing here, lets fill it
+                        // So we can allocate our new node to voxel_id
+                        let new_branch_idx = self.try_allocate_node::<Node>()?;
+
+                        self[fork_idx][voxel_id_idx * 2] = voxel_id;
+                        self[fork_idx][(voxel_id_idx * 2) + 1] = new_branch_idx as u32;
+
+                        return Ok(new_branch_idx);
                     }
-                    let next_opt = fork.flag;
-                    if next_opt > 0 {
-                        // Switch context until found or run out of links to branches
-                        fork_idx = next_opt as u32;
-                    } else {
-                        // If you are at this point, its only possible if there is not place left
-                        // To fix that, lets extend it with new fork
-                        // Fork does not exist, lets change that
-                        let new_branch_idx = self.allocate_node::<Node>();
-
-                        let fork = Node {
-                            flag: -3,
-                            children: [voxel_id, new_branch_idx as u32, 0, 0, 0, 0, 0, 0],
-                        };
-
-                        let fork_id = self.allocate_node_from(fork);
-
-                        self[fork_idx as usize].flag = fork_id as i32;
-
-                        return new_branch_idx;
+                    if found_voxel_id == voxel_id {
+                        // Bravo, we found idx to forward!
+                        return Ok(fork[(voxel_id_idx * 2) + 1] as usize);
                     }
                 }
-            }
-        } else {
-            let node_below_idx = node.children[child_idx];
-            if node_below_idx == 0 {
-                let new_child_idx = self.allocate_node::<Node>();
+                let next_opt = fork.flag;
+                if next_opt > 0 {
+                    // Switch context until found or run out of links to branches
+                    fork_idx = next_opt as usize;
+                } else {
+                    // If you are at this point, its only possible if there is not place left
+                    // To fix that, lets extend it with new fork
+                    if !self.has_free::<Node>(2) {
+                        return Err(LayerError::OutOfNodes);
+                    }
+                    let new_branch_idx = self.try_allocate_node::<Node>()?;
 
-                self[from_idx].children[child_idx] = new_child_idx as u32;
-                return new_child_idx;
-            } else {
-                return node.children[child_idx] as usize;
+                    let fork = Node {
+                        flag: -3,
+                        children: [voxel_id, new_branch_idx as u32, 0, 0, 0, 0, 0, 0],
+                    };
+
+                    let fork_id = self.try_allocate_node_from(fork)?;
+
+                    self[fork_idx].flag = fork_id as i32;
+
+                    return Ok(new_branch_idx);
+                }
             }
+        } else if node_below_idx == 0 {
+            let new_child_idx = self.try_allocate_node::<Node>()?;
+
+            self[from_idx].children[child_idx] = new_child_idx as u32;
+            Ok(new_child_idx)
+        } else {
+            Ok(node_below_idx as usize)
         }
     }
 }
@@ -338,9 +455,13 @@ impl<'a> Layer<'a> {
 impl<'a> From<(&'a mut [Node], &'a mut [NodeL2], usize)> for Layer<'a> {
     fn from(value: (&'a mut [Node], &'a mut [NodeL2], usize)) -> Self {
         Layer {
+            id: 0,
             depth: value.2,
             level_2: value.1,
             nodes: value.0,
+            freezed: false,
+            shared: false,
+            persistence: Persistence::Persistent,
         }
     }
 }
@@ -365,6 +486,7 @@ mod tests {
 
     use rand::Rng;
 
+    use crate::plat::layer::{error::LayerError, layer::Layer};
     use crate::plat::node_l2::NodeL2;
     use crate::plat::raw_plat::RawPlat;
     use crate::{plat::node::Node, quick_raw_plat, test_utils::set_rand_plat};
@@ -428,6 +550,60 @@ mod tests {
 
         assert_eq!(layer.validate(), Ok(()));
     }
+    #[test]
+    fn has_free() {
+        quick_raw_plat!(plat, depth 10, len 10, len2 5, lenrest 10);
+        let layer = &mut plat.layers[0];
+
+        assert!(layer.has_free::<Node>(8));
+        assert!(!layer.has_free::<Node>(9));
+        assert!(layer.has_free::<NodeL2>(4));
+        assert!(!layer.has_free::<NodeL2>(5));
+
+        layer.allocate_node::<Node>();
+        assert!(!layer.has_free::<Node>(8));
+    }
+
+    #[test]
+    fn link_free_extended() {
+        let mut nodes = alloc::vec![Node::default(); 20];
+        let mut l2 = alloc::vec![NodeL2::default(); 20];
+
+        Layer::new(10, &mut nodes[..10], &mut l2[..10]);
+
+        let mut layer: Layer = (&mut nodes[..], &mut l2[..], 10).into();
+        // Take something before extending
+        layer.allocate_node::<Node>();
+
+        layer.link_free::<Node>(10, 20);
+        layer.link_free::<NodeL2>(10, 20);
+
+        assert_eq!(layer.free(), 7 + 10);
+        assert_eq!(layer.free_l2(), 9 + 10);
+
+        for _ in 0..17 {
+            layer.allocate_node::<Node>();
+        }
+        assert!(!layer.has_free::<Node>(1));
+    }
+
+    #[test]
+    fn try_allocate_empty_pool() {
+        quick_raw_plat!(plat, depth 10, len 3, len2 2, lenrest 10);
+        let layer = &mut plat.layers[0];
+
+        assert_eq!(layer.try_allocate_node::<Node>(), Ok(2));
+        assert_eq!(
+            layer.try_allocate_node::<Node>(),
+            Err(LayerError::OutOfNodes)
+        );
+        assert_eq!(layer.try_allocate_node::<NodeL2>(), Ok(1));
+        assert_eq!(
+            layer.try_allocate_node::<NodeL2>(),
+            Err(LayerError::OutOfL2Nodes)
+        );
+    }
+
     #[test]
     fn deallocate_non_allocated_node() {
         quick_raw_plat!(plat, depth 10, len 1000, len2 1000, lenrest 1000);
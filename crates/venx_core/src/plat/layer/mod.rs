@@ -1,2 +1,3 @@
 pub mod debug;
+pub mod error;
 pub mod layer;
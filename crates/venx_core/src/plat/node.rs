@@ -8,7 +8,10 @@ use spirv_std::glam::UVec3;
 
 use crate::utils::l2s;
 
-use super::layer::layer::Layer;
+use super::{
+    layer::{error::LayerError, layer::Layer},
+    raw_plat::MAX_DEPTH,
+};
 
 #[repr(C)]
 #[derive(Copy, Debug, Clone, Default, PartialEq, PartialOrd, Pod, Zeroable, Hash, Eq)]
@@ -16,6 +19,8 @@ use super::layer::layer::Layer;
 pub struct Node {
     /// ` 0` - branch
     ///
+    /// `i32::MIN | bits` - branch with sharing marks, see [Node::is_shared] and [Node::shared_children]
+    ///
     /// `-1` - free node
     ///
     /// `-3` - fork without link to the next
@@ -32,6 +37,11 @@ pub struct Node {
 }
 
 impl Node {
+    /// Set on flag of branch with any marks, keeps it negative, so it is never taken for a fork
+    const MARKS: i32 = i32::MIN;
+    const SHARED: i32 = 1 << 8;
+    const VISITED: i32 = 1 << 9;
+
     pub fn is_fork(&self) -> bool {
         let flag = self.flag;
         if flag == -3 || flag > 0 {
@@ -40,6 +50,56 @@ impl Node {
             false
         }
     }
+    /// True if node has no children at all
+    pub fn is_empty(&self) -> bool {
+        self.children == [0; 8]
+    }
+    /// Branch might be linked by more than one parent, so it has to be copied before mutation.
+    /// See [Layer::copy_path]
+    ///
+    /// Marks are conservative: node stays marked after other parents are gone, until garbage is collected
+    pub fn is_shared(&self) -> bool {
+        self.marks() & Self::SHARED != 0
+    }
+    pub fn set_shared(&mut self, shared: bool) {
+        self.set_mark(Self::SHARED, shared);
+    }
+    /// Bit mask of children which might be linked by more than one parent
+    ///
+    /// Forks and [super::node_l2::NodeL2] have no room for marks, so they are marked in parent:
+    /// node on level 5 marks its fork chains and node on level 3 nodes on level 2
+    pub fn shared_children(&self) -> u8 {
+        self.marks() as u8
+    }
+    pub fn set_shared_children(&mut self, mask: u8) {
+        self.set_marks((self.marks() & !0xFF) | mask as i32);
+    }
+    /// Temporary mark for walks, which should enter shared node only once. Has to be cleared afterwards
+    pub(crate) fn is_visited(&self) -> bool {
+        self.marks() & Self::VISITED != 0
+    }
+    pub(crate) fn set_visited(&mut self, visited: bool) {
+        self.set_mark(Self::VISITED, visited);
+    }
+    /// Marks of branch. Forks and free nodes have none
+    fn marks(&self) -> i32 {
+        if self.flag < -3 {
+            self.flag & !Self::MARKS
+        } else {
+            0
+        }
+    }
+    fn set_marks(&mut self, marks: i32) {
+        debug_assert!(!self.is_fork() && self.flag != -1);
+        self.flag = if marks == 0 { 0 } else { Self::MARKS | marks };
+    }
+    fn set_mark(&mut self, mark: i32, value: bool) {
+        if value {
+            self.set_marks(self.marks() | mark);
+        } else {
+            self.set_marks(self.marks() & !mark);
+        }
+    }
     /// Internal index of node converted to normalized vector
     pub fn get_child_position(i: u32) -> UVec3 {
         UVec3::new(i & 1, (i >> 1) & 1, (i >> 2) & 1)
@@ -79,6 +139,10 @@ impl AllocatableNode for Node {
         layer[0][0] = new_link
     }
 
+    fn get_node<'a>(layer: &'a Layer<'_>, index: usize) -> &'a Self {
+        &layer[index]
+    }
+
     fn get_node_mut<'a>(layer: &'a mut Layer<'_>, index: usize) -> &'a mut Self {
         &mut layer[index]
     }
@@ -87,6 +151,10 @@ impl AllocatableNode for Node {
     fn name() -> &'static str {
         "Node"
     }
+
+    fn out_of_nodes() -> LayerError {
+        LayerError::OutOfNodes
+    }
 }
 
 pub trait AllocatableNode {
@@ -102,9 +170,14 @@ pub trait AllocatableNode {
 
     fn set_first_free_link(layer: &mut Layer, new_link: u32);
 
+    fn get_node<'a>(layer: &'a Layer<'_>, index: usize) -> &'a Self;
+
     fn get_node_mut<'a>(layer: &'a mut Layer<'_>, index: usize) -> &'a mut Self;
 
     fn name() -> &'static str;
+
+    /// Error returned if holder-pool of this type is empty
+    fn out_of_nodes() -> LayerError;
 }
 
 impl Index<usize> for Node {
@@ -121,12 +194,20 @@ impl IndexMut<usize> for Node {
     }
 }
 
-// TODO: use u64
-pub struct NodeAddr(u32);
+/// Path from root to node, 3 bits (child index) per level
+///
+/// Single u64 can hold only 21 indices, so levels are spread over two of them.
+/// That covers levels 0..=[NodeAddr::MAX_LEVEL], which is every level of the deepest plat
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct NodeAddr([u64; 2]);
 
 impl NodeAddr {
+    pub const MAX_LEVEL: usize = MAX_DEPTH;
+    /// Amount of levels in single u64
+    const LEVELS_PER_WORD: usize = 21;
+
     pub fn from_position(mut position: UVec3, depth: usize, until_level: usize) -> Self {
-        assert!(depth <= 21);
+        assert!(depth <= Self::MAX_LEVEL);
         let mut addr = Self::new();
         let mut level = depth;
         let mut size = l2s(depth);
@@ -147,20 +228,45 @@ impl NodeAddr {
 
         addr
     }
+    /// Inverse of [NodeAddr::from_position]
+    ///
+    /// Returns position of node on `until_level`, so everything below it is zeroed
+    pub fn to_position(&self, depth: usize, until_level: usize) -> UVec3 {
+        assert!(depth <= Self::MAX_LEVEL);
+        let mut position = UVec3::ZERO;
+
+        for level in (until_level + 1)..=depth {
+            position += Node::get_child_position(self.get_idx(level) as u32) * l2s(level - 1);
+        }
+
+        position
+    }
     pub(crate) fn new() -> Self {
-        NodeAddr(0)
+        NodeAddr([0; 2])
     }
     /// Get child idx on given level
     pub fn get_idx(&self, level: usize) -> usize {
-        assert!(level <= 21);
-        ((&self.0 >> (level * 3)) & 0o7) as usize
+        assert!(level <= Self::MAX_LEVEL);
+        let (word, shift) = Self::locate(level);
+        ((self.0[word] >> shift) & 0o7) as usize
     }
     /// Set child idx on given level
     /// Idx is 0-7
     pub fn set_idx(&mut self, level: usize, idx: usize) {
-        assert!(level <= 21);
+        assert!(level <= Self::MAX_LEVEL);
         assert!(idx < 8);
-        self.0 |= (idx as u32) << level * 3;
+        let (word, shift) = Self::locate(level);
+        // Clear previous one
+        self.0[word] &= !(0o7 << shift);
+        self.0[word] |= (idx as u64) << shift;
+    }
+    /// Word and bit offset of given level
+    #[inline]
+    fn locate(level: usize) -> (usize, usize) {
+        (
+            level / Self::LEVELS_PER_WORD,
+            (level % Self::LEVELS_PER_WORD) * 3,
+        )
     }
 }
 
@@ -168,6 +274,8 @@ impl NodeAddr {
 mod tests {
     use spirv_std::glam::uvec3;
 
+    use crate::utils::l2s;
+
     use super::NodeAddr;
 
     #[test]
@@ -199,4 +307,49 @@ mod tests {
         assert_eq!(addr.get_idx(1), 5);
         assert_eq!(addr.get_idx(0), 0);
     }
+
+    #[test]
+    fn test_node_addr_deep() {
+        let mut addr = NodeAddr::new();
+
+        for level in 0..=NodeAddr::MAX_LEVEL {
+            addr.set_idx(level, level % 8);
+        }
+        // Overwrite, on both sides of the word boundary
+        addr.set_idx(20, 3);
+        addr.set_idx(21, 4);
+
+        for level in (0..=NodeAddr::MAX_LEVEL).filter(|level| ![20, 21].contains(level)) {
+            assert_eq!(addr.get_idx(level), level % 8);
+        }
+        assert_eq!(addr.get_idx(20), 3);
+        assert_eq!(addr.get_idx(21), 4);
+    }
+
+    #[test]
+    fn test_node_addr_round_trip() {
+        let positions = [
+            uvec3(0, 0, 0),
+            uvec3(7, 20, 5),
+            uvec3((1 << 20) - 1, (1 << 20) - 1, (1 << 20) - 1),
+            uvec3(123_456, 654_321, 1 << 19),
+            uvec3(u32::MAX >> 1, 1 << 30, 1_234_567_890),
+        ];
+
+        // Deeper than single word can hold
+        for (depth, position) in [20, 24, NodeAddr::MAX_LEVEL]
+            .into_iter()
+            .flat_map(|depth| positions.map(|position| (depth, position % l2s(depth))))
+        {
+            for until_level in 0..depth {
+                let addr = NodeAddr::from_position(position, depth, until_level);
+                let mask = !((1 << until_level) - 1);
+
+                assert_eq!(
+                    addr.to_position(depth, until_level),
+                    uvec3(position.x & mask, position.y & mask, position.z & mask)
+                );
+            }
+        }
+    }
 }
@@ -51,6 +51,10 @@ impl NodeL2 {
         let (outer_offset, inner_offset, idx) = Self::get_offsets(position);
         self.packed_children[idx] &= !(1 << inner_offset << (outer_offset * 8));
     }
+    /// True if there is no voxel within `4x4x4` grid
+    pub fn is_empty(&self) -> bool {
+        self.packed_children == [0, 0]
+    }
     #[inline]
     fn get_offsets(mut position: UVec3) -> (usize, usize, usize) {
         if position.x > 3 || position.y > 3 || position.z > 3 {
@@ -102,6 +106,10 @@ impl AllocatableNode for NodeL2 {
         layer.level_2[0].packed_children[0] = new_link
     }
 
+    fn get_node<'a>(layer: &'a super::layer::layer::Layer<'_>, index: usize) -> &'a Self {
+        &layer.level_2[index]
+    }
+
     fn get_node_mut<'a>(
         layer: &'a mut super::layer::layer::Layer<'_>,
         index: usize,
@@ -112,6 +120,10 @@ impl AllocatableNode for NodeL2 {
     fn name() -> &'static str {
         "NodeLevel2"
     }
+
+    fn out_of_nodes() -> super::layer::error::LayerError {
+        super::layer::error::LayerError::OutOfL2Nodes
+    }
 }
 
 impl Debug for NodeL2 {
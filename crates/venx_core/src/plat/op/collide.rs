@@ -0,0 +1,379 @@
+use spirv_std::glam::{IVec3, UVec3, Vec3};
+
+use crate::{
+    plat::{
+        node::Node,
+        raw_plat::{RawPlat, MAX_DEPTH},
+    },
+    utils::l2s,
+};
+
+/// Result of [RawPlat::sweep_aabb]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SweepHit {
+    /// Position of voxel box runs into, in octree space
+    pub position: UVec3,
+    /// Normal of the face box touches
+    pub normal: IVec3,
+    /// If 0 its none
+    pub voxel_id: u32,
+    /// Time of impact. Part of velocity box can move before contact, from 0 to 1
+    pub time: f32,
+}
+
+impl SweepHit {
+    #[inline]
+    pub fn is_some(&self) -> bool {
+        self.voxel_id != 0
+    }
+    #[inline]
+    pub fn is_none(&self) -> bool {
+        !self.is_some()
+    }
+
+    #[allow(non_snake_case)]
+    pub fn None() -> Self {
+        Self {
+            position: UVec3::ZERO,
+            normal: IVec3::ZERO,
+            voxel_id: 0,
+            time: 0.,
+        }
+    }
+}
+
+impl RawPlat<'_> {
+    /// Call `callback` on every solid voxel in layers of `layers_mask`, which intersects box from `min` to `max`
+    ///
+    /// Box is given in octree space. Touching faces are not considered as intersection.
+    /// Voxel is solid if `is_solid(voxel_id)` returns true, see [RawPlat::get_node_in].
+    /// Subtrees which are empty in all masked layers are skipped at once
+    pub fn collide_aabb<F, C>(
+        &self,
+        min: Vec3,
+        max: Vec3,
+        layers_mask: u32,
+        is_solid: F,
+        mut callback: C,
+    ) where
+        F: Fn(u32) -> bool,
+        C: FnMut(UVec3, u32),
+    {
+        // Size is a power of two, so it is exact even at depth 31, where it does not fit into i32
+        let size = self.size() as f32;
+
+        // Voxels box overlaps
+        let from = min.floor().max(Vec3::ZERO);
+        let to = max.ceil().min(Vec3::splat(size));
+
+        if from.cmpge(to).any() {
+            return;
+        }
+        // Inclusive
+        let (from, to) = (from.as_uvec3(), to.as_uvec3() - 1);
+
+        // (position, level)
+        let mut stack = [(UVec3::ZERO, 0usize); 8 * MAX_DEPTH];
+        stack[0] = (UVec3::ZERO, self.depth());
+        let mut stack_len = 1;
+
+        while stack_len > 0 {
+            stack_len -= 1;
+            let (position, level) = stack[stack_len];
+            let cube_size = l2s(level);
+
+            if (position + cube_size - 1).cmplt(from).any() || position.cmpgt(to).any() {
+                continue;
+            }
+
+            if level == 0 {
+                let res = self.get_node_in(position, 0, layers_mask);
+
+                if res.is_some() && is_solid(res.voxel_id as u32) {
+                    callback(position, res.voxel_id as u32);
+                }
+                continue;
+            }
+
+            if self.empty_level_in(position, layers_mask) >= level {
+                continue;
+            }
+
+            for child_index in 0..8 {
+                stack[stack_len] = (
+                    position + Node::get_child_position(child_index) * (cube_size / 2),
+                    level - 1,
+                );
+                stack_len += 1;
+            }
+        }
+    }
+
+    /// Move box from `min` to `max` by `velocity` and find the first solid voxel it runs into, see [RawPlat::collide_aabb]
+    ///
+    /// Voxels box already intersects are ignored, so it can always move out of them
+    pub fn sweep_aabb<F>(
+        &self,
+        min: Vec3,
+        max: Vec3,
+        velocity: Vec3,
+        layers_mask: u32,
+        is_solid: F,
+    ) -> SweepHit
+    where
+        F: Fn(u32) -> bool,
+    {
+        let mut hit = SweepHit::None();
+
+        self.collide_aabb(
+            min.min(min + velocity),
+            max.max(max + velocity),
+            layers_mask,
+            is_solid,
+            |position, voxel_id| {
+                let voxel_min = position.as_vec3();
+                let voxel_max = voxel_min + 1.;
+
+                let (mut t_entry, mut t_exit) = (f32::NEG_INFINITY, f32::INFINITY);
+                let mut entry_axis = 0;
+
+                for axis in 0..3 {
+                    let v = velocity[axis];
+
+                    if v == 0. {
+                        if max[axis] <= voxel_min[axis] || min[axis] >= voxel_max[axis] {
+                            return;
+                        }
+                        continue;
+                    }
+
+                    let (entry, exit) = if v > 0. {
+                        (
+                            (voxel_min[axis] - max[axis]) / v,
+                            (voxel_max[axis] - min[axis]) / v,
+                        )
+                    } else {
+                        (
+                            (voxel_max[axis] - min[axis]) / v,
+                            (voxel_min[axis] - max[axis]) / v,
+                        )
+                    };
+
+                    if entry > t_entry {
+                        (t_entry, entry_axis) = (entry, axis);
+                    }
+                    if exit < t_exit {
+                        t_exit = exit;
+                    }
+                }
+
+                if !(0. ..=1.).contains(&t_entry) || t_entry >= t_exit {
+                    return;
+                }
+
+                if hit.is_none() || t_entry < hit.time {
+                    let mut normal = IVec3::ZERO;
+                    normal[entry_axis] = if velocity[entry_axis] > 0. { -1 } else { 1 };
+
+                    hit = SweepHit {
+                        position,
+                        normal,
+                        voxel_id,
+                        time: t_entry,
+                    };
+                }
+            },
+        );
+
+        hit
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+    extern crate std;
+
+    use alloc::vec::Vec;
+    use rand::Rng;
+    use spirv_std::glam::{ivec3, uvec3, vec3, UVec3};
+
+    use crate::{
+        plat::{layer::layer::Lr, node::Node, raw_plat::RawPlat},
+        quick_raw_plat,
+    };
+
+    const STONE: u32 = 1;
+    const WATER: u32 = 2;
+
+    fn is_solid(voxel_id: u32) -> bool {
+        voxel_id != WATER
+    }
+
+    fn build_floor(plat: &mut RawPlat) {
+        for x in 0..10 {
+            for z in 0..10 {
+                plat[Lr::BASE].set(uvec3(x, 0, z), STONE);
+            }
+        }
+        plat[Lr::BASE].set(uvec3(5, 1, 5), WATER);
+        plat[Lr::BASE].set(uvec3(6, 1, 2), STONE);
+    }
+
+    #[test]
+    fn collide_aabb() {
+        quick_raw_plat!(plat, depth 6, len 1_000, len2 1_000, lenrest 1_000);
+        build_floor(&mut plat);
+
+        let mut found = Vec::new();
+        plat.collide_aabb(
+            vec3(4.5, 0.5, 4.5),
+            vec3(5.5, 1.5, 5.5),
+            u32::MAX,
+            is_solid,
+            |position, _| found.push(position),
+        );
+        found.sort_by_key(|p| p.to_array());
+
+        // Water does not collide
+        assert_eq!(
+            found,
+            [
+                uvec3(4, 0, 4),
+                uvec3(4, 0, 5),
+                uvec3(5, 0, 4),
+                uvec3(5, 0, 5)
+            ]
+        );
+
+        let mut count = 0;
+        plat.collide_aabb(
+            vec3(4.5, 0.5, 4.5),
+            vec3(5.5, 1.5, 5.5),
+            u32::MAX,
+            |_| true,
+            |_, _| count += 1,
+        );
+        assert_eq!(count, 5);
+
+        // Standing on the floor is not a collision
+        plat.collide_aabb(
+            vec3(1., 1., 1.),
+            vec3(2., 3., 2.),
+            u32::MAX,
+            is_solid,
+            |_, _| panic!(),
+        );
+    }
+
+    #[test]
+    fn sweep_aabb() {
+        quick_raw_plat!(plat, depth 6, len 1_000, len2 1_000, lenrest 1_000);
+        build_floor(&mut plat);
+
+        // Falling
+        let hit = plat.sweep_aabb(
+            vec3(2.2, 3., 2.2),
+            vec3(2.8, 4.8, 2.8),
+            vec3(0., -5., 0.),
+            u32::MAX,
+            is_solid,
+        );
+        assert!(hit.is_some());
+        assert!((hit.time - 0.4).abs() < 0.0001);
+        assert_eq!(hit.normal, ivec3(0, 1, 0));
+        assert_eq!(hit.position.y, 0);
+
+        // Walking along the floor
+        let (min, max) = (vec3(2.2, 1., 2.2), vec3(2.8, 2.8, 2.8));
+
+        assert!(plat
+            .sweep_aabb(min, max, vec3(3., 0., 0.), u32::MAX, is_solid)
+            .is_none());
+
+        let hit = plat.sweep_aabb(min, max, vec3(5., 0., 0.), u32::MAX, is_solid);
+        assert_eq!(hit.position, uvec3(6, 1, 2));
+        assert_eq!(hit.normal, ivec3(-1, 0, 0));
+        assert!((hit.time - 0.64).abs() < 0.0001);
+
+        // Through the water
+        let (min, max) = (vec3(5.2, 1., 3.2), vec3(5.8, 2.8, 3.8));
+        assert!(plat
+            .sweep_aabb(min, max, vec3(0., 0., 4.), u32::MAX, is_solid)
+            .is_none());
+    }
+
+    #[test]
+    fn collide_aabb_max_depth() {
+        quick_raw_plat!(plat, depth 31, len 100, len2 100, lenrest 10);
+        let size = plat.size();
+
+        plat[Lr::BASE].set(uvec3(size - 256, 0, 0), STONE);
+
+        let mut found = Vec::new();
+        plat.collide_aabb(
+            vec3(size as f32 - 256., -1., -1.),
+            vec3(size as f32 * 2., 1., 1.),
+            u32::MAX,
+            is_solid,
+            |position, _| found.push(position),
+        );
+        assert_eq!(found, [uvec3(size - 256, 0, 0)]);
+    }
+
+    #[test]
+    fn collide_aabb_matches_get_voxel() {
+        quick_raw_plat!(plat, depth 6, len 10_000, len2 10_000, lenrest 10);
+        let mut rng = rand::thread_rng();
+
+        for _ in 0..300 {
+            let position = uvec3(
+                rng.gen_range(0..64),
+                rng.gen_range(0..64),
+                rng.gen_range(0..64),
+            );
+            plat[Lr::BASE].set(position, rng.gen_range(1..4));
+        }
+
+        for _ in 0..50 {
+            let min = vec3(
+                rng.gen_range(-10.0..64.0),
+                rng.gen_range(-10.0..64.0),
+                rng.gen_range(-10.0..64.0),
+            );
+            let max = min
+                + vec3(
+                    rng.gen_range(0.0..30.0),
+                    rng.gen_range(0.0..30.0),
+                    rng.gen_range(0.0..30.0),
+                );
+
+            let mut found = Vec::new();
+            plat.collide_aabb(min, max, u32::MAX, is_solid, |position, _| {
+                found.push(position)
+            });
+            found.sort_by_key(|p| p.to_array());
+
+            let mut expected = Vec::new();
+            for x in 0..64 {
+                for y in 0..64 {
+                    for z in 0..64 {
+                        let position = UVec3::new(x, y, z);
+                        let (voxel_min, voxel_max) = (position.as_vec3(), position.as_vec3() + 1.);
+
+                        if voxel_min.cmplt(max).all()
+                            && voxel_max.cmpgt(min).all()
+                            && is_solid(plat.get_voxel(position).voxel_id as u32)
+                            && plat.get_voxel(position).is_some()
+                        {
+                            expected.push(position);
+                        }
+                    }
+                }
+            }
+
+            assert_eq!(found, expected);
+        }
+    }
+}
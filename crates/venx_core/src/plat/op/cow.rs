@@ -0,0 +1,344 @@
+use spirv_std::glam::UVec3;
+
+use crate::{
+    plat::{layer::layer::Layer, node::Node},
+    utils::l2s,
+};
+
+impl Layer<'_> {
+    /// Copy every shared node which [Layer::set] or [Layer::unset] on given position could touch
+    ///
+    /// Path is the same as theirs: nodes from the root down to level 5, its fork chain
+    /// and in every branch of the chain nodes down to level 2 containing position.
+    /// Only nodes marked as shared are copied (see [Node::is_shared] and [Node::shared_children]).
+    /// Copy is linked in place of original and belongs to this path only,
+    /// while children of original get one more parent and are marked as shared.
+    /// So once path reaches first shared node, everything below it is copied as well
+    ///
+    /// Originals stay untouched for other parents. If there are none anymore, they are leaked until garbage is collected
+    ///
+    /// Used when [Layer::shared] is set
+    pub fn copy_path(&mut self, mut position: UVec3) {
+        let mut size = l2s(self.depth);
+        let mut level = self.depth;
+
+        // If given position is out of bound
+        if position.y >= size || position.x >= size || position.z >= size {
+            return;
+        }
+
+        // Root is always on 1 idx and never shared;
+        let mut idx = 1;
+
+        while level > 5 {
+            let child_index = Node::get_child_index(position, level - 1);
+
+            if self[idx][child_index] == 0 {
+                return;
+            }
+
+            idx = self.own_node(idx, child_index, level - 1);
+
+            {
+                size /= 2;
+                level -= 1;
+                position %= size;
+            }
+        }
+
+        let child_index = Node::get_child_index(position, 4);
+        position %= size / 2;
+
+        let l3_child_index = Node::get_child_index(position, 3);
+        let l2_child_index = Node::get_child_index(position % 8, 2);
+
+        self.own_chain(idx, child_index);
+
+        let mut fork_idx = self[idx][child_index] as usize;
+
+        while fork_idx != 0 {
+            for slot in 0..4 {
+                // Entries are packed
+                if self[fork_idx][slot * 2] == 0 {
+                    break;
+                }
+
+                let branch_idx = self.own_node(fork_idx, slot * 2 + 1, 4);
+
+                if self[branch_idx][l3_child_index] == 0 {
+                    continue;
+                }
+                let l3_idx = self.own_node(branch_idx, l3_child_index, 3);
+
+                if self[l3_idx][l2_child_index] == 0 {
+                    continue;
+                }
+                self.own_l2(l3_idx, l2_child_index);
+            }
+
+            let flag = self[fork_idx].flag;
+            fork_idx = if flag > 0 { flag as usize } else { 0 };
+        }
+    }
+
+    /// Amount of nodes on upper levels and level 2 which [Layer::copy_path] would allocate
+    pub fn copy_path_cost(&self, mut position: UVec3) -> (usize, usize) {
+        let mut size = l2s(self.depth);
+        let mut level = self.depth;
+
+        if position.y >= size || position.x >= size || position.z >= size {
+            return (0, 0);
+        }
+
+        let mut idx = 1;
+        let (mut upper, mut l2) = (0, 0);
+        // Everything below the first shared node is copied
+        let mut copying = false;
+
+        while level > 5 {
+            let child_index = Node::get_child_index(position, level - 1);
+            idx = self[idx][child_index] as usize;
+
+            if idx == 0 {
+                return (upper, l2);
+            }
+
+            copying |= self[idx].is_shared();
+            upper += copying as usize;
+
+            {
+                size /= 2;
+                level -= 1;
+                position %= size;
+            }
+        }
+
+        let child_index = Node::get_child_index(position, 4);
+        position %= size / 2;
+
+        let l3_child_index = Node::get_child_index(position, 3);
+        let l2_child_index = Node::get_child_index(position % 8, 2);
+
+        let mut fork_idx = self[idx][child_index] as usize;
+        copying |= self[idx].shared_children() & (1 << child_index) != 0;
+
+        while fork_idx != 0 {
+            let fork = &self[fork_idx];
+            upper += copying as usize;
+
+            for slot in 0..4 {
+                if fork[slot * 2] == 0 {
+                    break;
+                }
+
+                let branch_idx = fork[slot * 2 + 1] as usize;
+                let copying = copying || self[branch_idx].is_shared();
+                upper += copying as usize;
+
+                let l3_idx = self[branch_idx][l3_child_index] as usize;
+                if l3_idx == 0 {
+                    continue;
+                }
+                let copying = copying || self[l3_idx].is_shared();
+                upper += copying as usize;
+
+                if self[l3_idx][l2_child_index] != 0
+                    && (copying || self[l3_idx].shared_children() & (1 << l2_child_index) != 0)
+                {
+                    l2 += 1;
+                }
+            }
+
+            fork_idx = if fork.flag > 0 { fork.flag as usize } else { 0 };
+        }
+
+        (upper, l2)
+    }
+
+    /// Make regular child of node owned by it, copying the child if it is shared. Returns idx of owned child
+    ///
+    /// `level` is level of child. Node on level 4 is a branch linked by fork, so `child_index` is its slot in fork children
+    pub(crate) fn own_node(&mut self, idx: usize, child_index: usize, level: usize) -> usize {
+        let child_idx = self[idx][child_index] as usize;
+
+        if !self[child_idx].is_shared() {
+            return child_idx;
+        }
+
+        // Children are linked by original and copy now
+        self.share_children(child_idx, level);
+
+        let mut copy = self[child_idx];
+        copy.set_shared(false);
+
+        let copy_idx = self.allocate_node_from(copy);
+        self[idx][child_index] = copy_idx as u32;
+        copy_idx
+    }
+
+    /// Make fork chain linked by node on level 5 owned by it, copying the whole chain if it is shared
+    pub(crate) fn own_chain(&mut self, idx: usize, child_index: usize) {
+        let mut shared_children = self[idx].shared_children();
+
+        if shared_children & (1 << child_index) == 0 {
+            return;
+        }
+
+        let mut fork_idx = self[idx][child_index] as usize;
+        // Fork, which links to currently copied one. 0 means it is linked by parent
+        let mut prev_idx = 0;
+
+        while fork_idx != 0 {
+            self.share_children(fork_idx, 4);
+
+            let copy_idx = self.allocate_node_from(self[fork_idx]);
+
+            if prev_idx == 0 {
+                self[idx][child_index] = copy_idx as u32;
+            } else {
+                self[prev_idx].flag = copy_idx as i32;
+            }
+
+            let flag = self[copy_idx].flag;
+            prev_idx = copy_idx;
+            fork_idx = if flag > 0 { flag as usize } else { 0 };
+        }
+
+        shared_children &= !(1 << child_index);
+        self[idx].set_shared_children(shared_children);
+    }
+
+    /// Make node on level 2 linked by node on level 3 owned by it, copying it if it is shared
+    fn own_l2(&mut self, idx: usize, child_index: usize) {
+        let mut shared_children = self[idx].shared_children();
+
+        if shared_children & (1 << child_index) == 0 {
+            return;
+        }
+
+        let l2_idx = self[idx][child_index] as usize;
+        let copy_idx = self.allocate_node_from(self.level_2[l2_idx]);
+        self[idx][child_index] = copy_idx as u32;
+
+        shared_children &= !(1 << child_index);
+        self[idx].set_shared_children(shared_children);
+    }
+
+    /// Mark every child of node on `level` as shared. Fork on level 4 marks its branches
+    fn share_children(&mut self, idx: usize, level: usize) {
+        if self[idx].is_fork() {
+            for slot in 0..4 {
+                // Entries are packed
+                if self[idx][slot * 2] == 0 {
+                    break;
+                }
+                let branch_idx = self[idx][slot * 2 + 1] as usize;
+                self[branch_idx].set_shared(true);
+            }
+            return;
+        }
+
+        let mut mask = 0;
+
+        for child_index in 0..8 {
+            let child_idx = self[idx][child_index] as usize;
+
+            if child_idx == 0 {
+                continue;
+            }
+
+            // Forks and NodeL2 are marked in parent
+            if level == 5 || level == 3 {
+                mask |= 1 << child_index;
+            } else {
+                self[child_idx].set_shared(true);
+            }
+        }
+
+        if mask != 0 {
+            let shared_children = self[idx].shared_children();
+            self[idx].set_shared_children(shared_children | mask);
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+    extern crate std;
+
+    use alloc::vec;
+    use spirv_std::glam::uvec3;
+
+    use crate::{
+        plat::{node::Node, raw_plat::RawPlat},
+        quick_raw_plat,
+    };
+
+    #[test]
+    fn copy_path_shared() {
+        quick_raw_plat!(plat, depth 6, len 1_000, len2 1_000, lenrest 10);
+
+        for i in 0..8 {
+            plat[0].set(uvec3(i, i, 0), i % 3 + 1);
+        }
+        // Region on x 32..64 shares everything with region on 0..32
+        let idx = plat[0][1][0] as usize;
+        plat[0][1][1] = idx as u32;
+        plat[0][idx].set_shared(true);
+        plat[0].shared = true;
+
+        assert_eq!(plat.get_voxel(uvec3(32 + 2, 2, 0)).voxel_id, 3);
+
+        plat[0].set(uvec3(32 + 2, 2, 0), 7);
+        plat[0].set(uvec3(32 + 3, 3, 0), 0);
+        plat[0].set(uvec3(32 + 20, 0, 0), 1);
+
+        for i in 0..8 {
+            // Original region is unchanged
+            assert_eq!(plat.get_voxel(uvec3(i, i, 0)).voxel_id as u32, i % 3 + 1);
+        }
+        assert!(plat.get_voxel(uvec3(20, 0, 0)).is_none());
+
+        assert_eq!(plat.get_voxel(uvec3(32 + 2, 2, 0)).voxel_id, 7);
+        assert!(plat.get_voxel(uvec3(32 + 3, 3, 0)).is_none());
+        assert_eq!(plat.get_voxel(uvec3(32 + 20, 0, 0)).voxel_id, 1);
+        assert_eq!(plat.get_voxel(uvec3(32 + 1, 1, 0)).voxel_id, 2);
+
+        // Copies are owned by their path, so they are written in place
+        assert_eq!(plat[0].copy_path_cost(uvec3(32 + 2, 2, 0)), (0, 0));
+        assert!(plat
+            .validate(&mut vec![0; 1_000], &mut vec![false; 1_000], None)
+            .is_correct());
+    }
+
+    #[test]
+    fn copy_path_cost_matches() {
+        quick_raw_plat!(plat, depth 7, len 1_000, len2 1_000, lenrest 10);
+
+        for i in 0..6 {
+            plat[0].set(uvec3(i, 0, 0), i + 1);
+        }
+
+        // Nothing is shared
+        assert_eq!(plat[0].copy_path_cost(uvec3(1, 0, 0)), (0, 0));
+
+        // Everything below node on level 6 gets copied
+        let idx = plat[0][1][0] as usize;
+        plat[0][idx].set_shared(true);
+
+        let cost = plat[0].copy_path_cost(uvec3(1, 0, 0));
+        // Node on level 6 and 5, two forks and branch with node on level 3 for each voxel.
+        // Voxels on x 4 and 5 are in another NodeL2
+        assert_eq!(cost, (1 + 1 + 2 + 6 * 2, 4));
+        let (free, free_l2) = (plat[0].free(), plat[0].free_l2());
+
+        plat[0].copy_path(uvec3(1, 0, 0));
+
+        assert_eq!((free - plat[0].free(), free_l2 - plat[0].free_l2()), cost);
+        for i in 0..6 {
+            assert_eq!(plat.get_voxel(uvec3(i, 0, 0)).voxel_id as u32, i + 1);
+        }
+    }
+}
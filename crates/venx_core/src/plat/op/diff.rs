@@ -0,0 +1,378 @@
+use spirv_std::glam::UVec3;
+
+use crate::{
+    plat::{layer::layer::Layer, node::Node, raw_plat::MAX_DEPTH},
+    utils::l2s,
+};
+
+/// Voxel which differs between two layers, see [Layer::diff]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VoxelDiff {
+    pub position: UVec3,
+    /// Voxel id in compared layer. If 0 its none
+    pub before: u32,
+    /// Voxel id in other layer. If 0 its none
+    pub after: u32,
+}
+
+impl VoxelDiff {
+    #[inline]
+    pub fn is_added(&self) -> bool {
+        self.before == 0
+    }
+    #[inline]
+    pub fn is_removed(&self) -> bool {
+        self.after == 0
+    }
+    #[inline]
+    pub fn is_changed(&self) -> bool {
+        self.before != 0 && self.after != 0
+    }
+}
+
+impl Layer<'_> {
+    /// Walk region in both layers in lockstep and call `callback` on every voxel, which is different in `other`
+    ///
+    /// Region is node on `region_level` (at least 5) and its position is given in units of that level, like in [Layer::traverse].
+    /// Subtrees which are empty in both layers are skipped. If `other` is the very same layer (same storage),
+    /// subtrees with the same index are identical. Otherwise index tells nothing, so nodes on level 2 are compared by content
+    ///
+    /// Each voxel is reported once, in no particular order
+    pub fn diff<F>(
+        &self,
+        other: &Layer,
+        region_position: UVec3,
+        region_level: usize,
+        mut callback: F,
+    ) where
+        F: FnMut(VoxelDiff),
+    {
+        assert_eq!(self.depth, other.depth);
+        assert!(region_level >= 5);
+
+        let same_idx_is_same = self.same_storage(other);
+        let region_level = region_level.min(self.depth);
+        let region_origin = region_position * l2s(region_level);
+
+        // Find region in both layers
+        let (mut a_idx, mut b_idx) = (1, 1);
+        let mut level = self.depth;
+
+        while level > region_level {
+            let child_index = Node::get_child_index(region_origin % l2s(level), level - 1);
+
+            a_idx = self.child_or_none(a_idx, child_index);
+            b_idx = other.child_or_none(b_idx, child_index);
+            level -= 1;
+        }
+
+        // (idx in self, idx in other, level, position)
+        let mut stack = [(0usize, 0usize, 0usize, UVec3::ZERO); 8 * MAX_DEPTH];
+        let mut stack_len = 0;
+
+        if a_idx != 0 || b_idx != 0 {
+            stack[0] = (a_idx, b_idx, region_level, region_origin);
+            stack_len = 1;
+        }
+
+        while stack_len > 0 {
+            stack_len -= 1;
+            let (a_idx, b_idx, level, position) = stack[stack_len];
+
+            for child_index in 0..8 {
+                let a_child = self.child_or_none(a_idx, child_index);
+                let b_child = other.child_or_none(b_idx, child_index);
+
+                if (a_child == 0 && b_child == 0) || (same_idx_is_same && a_child == b_child) {
+                    continue;
+                }
+
+                let child_position =
+                    position + Node::get_child_position(child_index as u32) * l2s(level - 1);
+
+                if level > 5 {
+                    stack[stack_len] = (a_child, b_child, level - 1, child_position);
+                    stack_len += 1;
+                    continue;
+                }
+
+                // Children of level 5 are forks
+                // Voxels which are in `self`, but not in `other` with the same voxel_id
+                self.diff_forks(
+                    other,
+                    a_child,
+                    b_child,
+                    child_position,
+                    |position, voxel_id| {
+                        callback(VoxelDiff {
+                            position,
+                            before: voxel_id,
+                            after: other.get_node(position, 0, None).voxel_id as u32,
+                        });
+                    },
+                );
+
+                // And the other way around. Voxels which exist in both are already reported
+                other.diff_forks(
+                    self,
+                    b_child,
+                    a_child,
+                    child_position,
+                    |position, voxel_id| {
+                        if self.get_node(position, 0, None).is_none() {
+                            callback(VoxelDiff {
+                                position,
+                                before: 0,
+                                after: voxel_id,
+                            });
+                        }
+                    },
+                );
+            }
+        }
+    }
+
+    /// True if both layers are backed by the same nodes, so the same index is the same subtree
+    fn same_storage(&self, other: &Layer) -> bool {
+        core::ptr::eq(self.nodes.as_ptr(), other.nodes.as_ptr())
+            && core::ptr::eq(self.level_2.as_ptr(), other.level_2.as_ptr())
+    }
+
+    #[inline]
+    fn child_or_none(&self, idx: usize, child_index: usize) -> usize {
+        if idx == 0 {
+            0
+        } else {
+            self[idx].children[child_index] as usize
+        }
+    }
+
+    /// Branch of given voxel_id in fork chain. 0 if there is none
+    fn find_branch(&self, fork_idx: usize, voxel_id: usize) -> usize {
+        let mut branch_idx = 0;
+
+        if fork_idx != 0 {
+            self.iter_fork(fork_idx, &mut |props| {
+                if props.voxel_id == voxel_id {
+                    branch_idx = props.node_idx;
+                    props.drop = true;
+                }
+            });
+        }
+        branch_idx
+    }
+
+    /// Call `callback` on every voxel in region of given fork, which is not in `other_fork` with the same voxel_id
+    fn diff_forks<F>(
+        &self,
+        other: &Layer,
+        fork_idx: usize,
+        other_fork_idx: usize,
+        position: UVec3,
+        mut callback: F,
+    ) where
+        F: FnMut(UVec3, u32),
+    {
+        if fork_idx == 0 {
+            return;
+        }
+
+        let same_idx_is_same = self.same_storage(other);
+
+        self.iter_fork(fork_idx, &mut |props| {
+            let branch_idx = props.node_idx;
+            let other_branch_idx = other.find_branch(other_fork_idx, props.voxel_id);
+
+            if same_idx_is_same && branch_idx == other_branch_idx {
+                return;
+            }
+
+            // Level 3
+            for l3_child in 0..8 {
+                let l3_idx = self.child_or_none(branch_idx, l3_child);
+                let other_l3_idx = other.child_or_none(other_branch_idx, l3_child);
+
+                if l3_idx == 0 || (same_idx_is_same && l3_idx == other_l3_idx) {
+                    continue;
+                }
+
+                let l3_position = position + Node::get_child_position(l3_child as u32) * 8;
+
+                // Level 2
+                for l2_child in 0..8 {
+                    let l2_idx = self.child_or_none(l3_idx, l2_child);
+                    let other_l2_idx = other.child_or_none(other_l3_idx, l2_child);
+
+                    if l2_idx == 0 || (same_idx_is_same && l2_idx == other_l2_idx) {
+                        continue;
+                    }
+
+                    let node_l2 = self.level_2[l2_idx];
+                    let other_node_l2 = other.level_2[other_l2_idx];
+
+                    if other_l2_idx != 0 && node_l2 == other_node_l2 {
+                        continue;
+                    }
+                    let l2_position = l3_position + Node::get_child_position(l2_child as u32) * 4;
+
+                    for i in 0..64 {
+                        let local = UVec3::new(i % 4, (i / 4) % 4, i / 16);
+
+                        if node_l2.is_at(local)
+                            && (other_l2_idx == 0 || !other_node_l2.is_at(local))
+                        {
+                            callback(l2_position + local, props.voxel_id as u32);
+                        }
+                    }
+                }
+            }
+        });
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+    extern crate std;
+
+    use alloc::vec::Vec;
+    use rand::Rng;
+    use spirv_std::glam::{uvec3, UVec3};
+
+    use crate::{
+        plat::{
+            layer::layer::{Layer, Lr},
+            node::Node,
+            raw_plat::RawPlat,
+        },
+        quick_raw_plat,
+    };
+
+    use super::VoxelDiff;
+
+    fn collect(a: &Layer, b: &Layer, position: UVec3, level: usize) -> Vec<VoxelDiff> {
+        let mut diffs = Vec::new();
+        a.diff(b, position, level, |diff| diffs.push(diff));
+        diffs.sort_by_key(|diff| diff.position.to_array());
+        diffs
+    }
+
+    #[test]
+    fn diff_layers() {
+        quick_raw_plat!(plat, depth 7, len 1_000, len2 1_000, lenrest 1_000);
+
+        plat[Lr::BASE].set(uvec3(1, 1, 1), 1);
+        plat[Lr::BASE].set(uvec3(2, 2, 2), 1);
+        plat[Lr::BASE].set(uvec3(3, 3, 3), 1);
+        plat[Lr::BASE].set(uvec3(100, 3, 3), 5);
+
+        plat[Lr::CANVAS].set(uvec3(1, 1, 1), 1);
+        plat[Lr::CANVAS].set(uvec3(2, 2, 2), 2);
+        plat[Lr::CANVAS].set(uvec3(40, 0, 0), 3);
+        plat[Lr::CANVAS].set(uvec3(100, 3, 3), 5);
+
+        let diffs = collect(&plat[Lr::BASE], &plat[Lr::CANVAS], UVec3::ZERO, 7);
+
+        assert_eq!(
+            diffs,
+            [
+                VoxelDiff {
+                    position: uvec3(2, 2, 2),
+                    before: 1,
+                    after: 2
+                },
+                VoxelDiff {
+                    position: uvec3(3, 3, 3),
+                    before: 1,
+                    after: 0
+                },
+                VoxelDiff {
+                    position: uvec3(40, 0, 0),
+                    before: 0,
+                    after: 3
+                },
+            ]
+        );
+        assert!(diffs[0].is_changed() && diffs[1].is_removed() && diffs[2].is_added());
+
+        // Only region with (40, 0, 0)
+        let diffs = collect(&plat[Lr::BASE], &plat[Lr::CANVAS], uvec3(1, 0, 0), 5);
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].position, uvec3(40, 0, 0));
+
+        assert!(collect(&plat[Lr::CANVAS], &plat[Lr::CANVAS], UVec3::ZERO, 7).is_empty());
+    }
+
+    #[test]
+    fn diff_shared_layers() {
+        quick_raw_plat!(plat, depth 6, len 1_000, len2 1_000, lenrest 10);
+
+        // Both layers allocate the same indices
+        plat[Lr::BASE].set(uvec3(1, 1, 1), 1);
+        plat[Lr::CANVAS].set(uvec3(1, 1, 1), 2);
+        plat[Lr::BASE].shared = true;
+        plat[Lr::CANVAS].shared = true;
+
+        assert_eq!(
+            collect(&plat[Lr::BASE], &plat[Lr::CANVAS], UVec3::ZERO, 6),
+            [VoxelDiff {
+                position: uvec3(1, 1, 1),
+                before: 1,
+                after: 2
+            }]
+        );
+    }
+
+    #[test]
+    fn diff_with_snapshot() {
+        quick_raw_plat!(plat, depth 7, len 10_000, len2 10_000, lenrest 10);
+        let mut rng = rand::thread_rng();
+
+        for _ in 0..500 {
+            let position = uvec3(rng.gen_range(0..128), rng.gen_range(0..32), 5);
+            plat[Lr::BASE].set(position, rng.gen_range(1..4));
+        }
+
+        // Save
+        let (mut nodes, mut level_2) = (
+            plat[Lr::BASE].nodes.to_vec(),
+            plat[Lr::BASE].level_2.to_vec(),
+        );
+        let snapshot: Layer = (&mut nodes[..], &mut level_2[..], 7).into();
+
+        let mut expected = Vec::new();
+        for _ in 0..50 {
+            let position = uvec3(rng.gen_range(0..128), rng.gen_range(0..32), 5);
+            let voxel_id = rng.gen_range(0..4);
+            plat[Lr::BASE].set(position, voxel_id);
+            expected.push(position);
+        }
+
+        let diffs = collect(&snapshot, &plat[Lr::BASE], UVec3::ZERO, 7);
+
+        for diff in diffs.iter() {
+            assert_eq!(
+                snapshot.get_node(diff.position, 0, None).voxel_id as u32,
+                diff.before
+            );
+            assert_eq!(
+                plat[Lr::BASE].get_node(diff.position, 0, None).voxel_id as u32,
+                diff.after
+            );
+            assert_ne!(diff.before, diff.after);
+            assert!(expected.contains(&diff.position));
+        }
+        // Everything else is untouched
+        for position in expected {
+            let (before, after) = (
+                snapshot.get_node(position, 0, None).voxel_id,
+                plat[Lr::BASE].get_node(position, 0, None).voxel_id,
+            );
+            assert_eq!(
+                before != after,
+                diffs.iter().any(|diff| diff.position == position)
+            );
+        }
+    }
+}
@@ -0,0 +1,789 @@
+use spirv_std::glam::{UVec3, Vec3};
+
+use crate::{
+    plat::{
+        layer::{error::LayerError, layer::Layer},
+        node::Node,
+        node_l2::NodeL2,
+    },
+    utils::l2s,
+};
+
+/// Voxels in single node on level 4
+const BLOCK_VOLUME: usize = 16 * 16 * 16;
+
+/// One bit per voxel of a block
+struct BlockMask([u32; BLOCK_VOLUME / 32]);
+
+impl Default for BlockMask {
+    fn default() -> Self {
+        Self([0; BLOCK_VOLUME / 32])
+    }
+}
+
+impl BlockMask {
+    fn get(&self, i: usize) -> bool {
+        self.0[i / 32] & (1 << (i % 32)) != 0
+    }
+
+    fn set(&mut self, i: usize) {
+        self.0[i / 32] |= 1 << (i % 32);
+    }
+}
+
+/// Indices of fully filled nodes, allocated once per fill and shared by all homogeneous interiors
+#[derive(Default)]
+struct FullNodes {
+    l2: usize,
+    l3: usize,
+    branch: usize,
+}
+
+impl Layer<'_> {
+    /// Set every voxel in box from `from` to `to` (exclusive) to `voxel_id`, 0 clears it. See [Layer::fill_with]
+    pub fn fill_box(&mut self, from: UVec3, to: UVec3, voxel_id: u32) -> Result<(), LayerError> {
+        self.fill_with(from, to, |_| Some(voxel_id))
+    }
+
+    /// Set every voxel, which center is within `radius` from `center`, to `voxel_id`, 0 carves it out.
+    /// See [Layer::fill_with]
+    pub fn fill_sphere(
+        &mut self,
+        center: Vec3,
+        radius: f32,
+        voxel_id: u32,
+    ) -> Result<(), LayerError> {
+        let (from, to) = Self::bounds(center - radius, center + radius);
+
+        self.fill_with(from, to, |position| {
+            if (position.as_vec3() + 0.5 - center).length_squared() <= radius * radius {
+                Some(voxel_id)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Set every voxel of vertical cylinder to `voxel_id`, 0 carves it out. See [Layer::fill_with]
+    ///
+    /// Cylinder stands on `base` (center of its bottom face) and goes `height` voxels up
+    pub fn fill_cylinder(
+        &mut self,
+        base: Vec3,
+        radius: f32,
+        height: f32,
+        voxel_id: u32,
+    ) -> Result<(), LayerError> {
+        let (from, to) = Self::bounds(
+            base - Vec3::new(radius, 0., radius),
+            base + Vec3::new(radius, height, radius),
+        );
+
+        self.fill_with(from, to, |position| {
+            let center = position.as_vec3() + 0.5;
+            let offset = Vec3::new(center.x - base.x, 0., center.z - base.z);
+
+            if offset.length_squared() <= radius * radius
+                && center.y >= base.y
+                && center.y <= base.y + height
+            {
+                Some(voxel_id)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Set every voxel in box from `from` to `to` (exclusive) to whatever `f` returns for its position.
+    /// If `f` returns `None`, voxel is left untouched, `Some(0)` removes it
+    ///
+    /// Instead of walking from the root for each voxel, region is processed by nodes on level 4.
+    /// Each one is built from bottom to top, writing whole [NodeL2] at once.
+    /// Voxels it already has are kept and built in as well, old nodes are returned to holder-pools unless shared.
+    /// Fully filled nodes on levels 2 to 4 are allocated once and shared by all homogeneous interiors,
+    /// so they are marked as shared (see [Node::is_shared]) and layer becomes [Layer::shared]
+    ///
+    /// On error layer might be filled partially, filling the same region again continues where it stopped
+    pub fn fill_with<F>(&mut self, from: UVec3, to: UVec3, mut f: F) -> Result<(), LayerError>
+    where
+        F: FnMut(UVec3) -> Option<u32>,
+    {
+        self.check_writable()?;
+
+        let to = to.min(UVec3::splat(l2s(self.depth)));
+
+        if from.cmpge(to).any() {
+            return Ok(());
+        }
+
+        let mut full = FullNodes::default();
+        let result = self.fill_region(from, to, &mut f, &mut full);
+
+        // Layer is not marked earlier, since nothing shared by this fill is on the way of the next block
+        if full.l2 != 0 {
+            self.shared = true;
+        }
+
+        result
+    }
+
+    fn fill_region<F>(
+        &mut self,
+        from: UVec3,
+        to: UVec3,
+        f: &mut F,
+        full: &mut FullNodes,
+    ) -> Result<(), LayerError>
+    where
+        F: FnMut(UVec3) -> Option<u32>,
+    {
+        let mut ids = [0u32; BLOCK_VOLUME];
+
+        let (block_from, block_to) = (from / 16, (to - 1) / 16);
+
+        for bz in block_from.z..=block_to.z {
+            for by in block_from.y..=block_to.y {
+                for bx in block_from.x..=block_to.x {
+                    let block_position = UVec3::new(bx, by, bz) * 16;
+
+                    // Evaluate the whole block first
+                    let mut written = BlockMask::default();
+                    let mut is_untouched = true;
+
+                    for (i, id) in ids.iter_mut().enumerate() {
+                        let position = block_position + Self::block_local(i);
+                        *id = 0;
+
+                        if position.cmplt(from).any() || position.cmpge(to).any() {
+                            continue;
+                        }
+
+                        if let Some(voxel_id) = f(position) {
+                            *id = voxel_id;
+                            written.set(i);
+                            is_untouched = false;
+                        }
+                    }
+
+                    if !is_untouched {
+                        self.fill_block(block_position, &mut ids, &written, full)?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write voxels of single node on level 4. Voxels, which are not `written`, are taken from old nodes into `ids`.
+    /// Written 0 removes voxel
+    ///
+    /// Amount of free nodes is checked before anything is written, so on error block stays untouched
+    fn fill_block(
+        &mut self,
+        block_position: UVec3,
+        ids: &mut [u32; BLOCK_VOLUME],
+        written: &BlockMask,
+        full: &mut FullNodes,
+    ) -> Result<(), LayerError> {
+        let fork_child_index = Node::get_child_index(block_position % 32, 4);
+        let old_fork = {
+            let idx = self.node_at_level_5(block_position);
+
+            if idx == 0 {
+                0
+            } else {
+                self[idx][fork_child_index] as usize
+            }
+        };
+
+        if old_fork != 0 {
+            // Voxels which are not overwritten are kept, so chain is rebuilt with them
+            self.iter_fork(old_fork, &mut |props| {
+                self.read_branch(props.node_idx, props.voxel_id as u32, ids, written)
+            });
+        } else if ids.iter().all(|&id| id == 0) {
+            // Only removals and nothing to remove
+            return Ok(());
+        }
+
+        // Copy or missing node on each level down to level 5 and new chain
+        let (upper, l2) = Self::block_cost(ids, full);
+
+        if !self.has_free::<Node>(self.depth - 5 + upper) {
+            return Err(LayerError::OutOfNodes);
+        }
+        if !self.has_free::<NodeL2>(l2) {
+            return Err(LayerError::OutOfL2Nodes);
+        }
+
+        // Down to node on level 5
+        let mut idx = 1;
+        let mut level = self.depth;
+
+        while level > 5 {
+            let child_index = Node::get_child_index(block_position % l2s(level), level - 1);
+
+            idx = if self[idx][child_index] == 0 {
+                let new_idx = self.allocate_node::<Node>();
+                self[idx][child_index] = new_idx as u32;
+                new_idx
+            } else {
+                self.own_node(idx, child_index, level - 1)
+            };
+            level -= 1;
+        }
+
+        // Fork chain is built separately and linked once it is complete
+        let (mut first_fork, mut last_fork, mut slot) = (0, 0, 4);
+        let mut taken = BlockMask::default();
+
+        for start in 0..BLOCK_VOLUME {
+            let voxel_id = ids[start];
+
+            if voxel_id == 0 || taken.get(start) {
+                continue;
+            }
+
+            let masks = Self::take_masks(ids, &mut taken, start);
+            let branch_idx = self.build_branch(&masks, full);
+
+            if slot == 4 {
+                let fork_idx = self.allocate_node_from(Node {
+                    flag: -3,
+                    children: [0; 8],
+                });
+
+                if last_fork == 0 {
+                    first_fork = fork_idx;
+                } else {
+                    self[last_fork].flag = fork_idx as i32;
+                }
+                (last_fork, slot) = (fork_idx, 0);
+            }
+
+            self[last_fork][slot * 2] = voxel_id;
+            self[last_fork][slot * 2 + 1] = branch_idx as u32;
+            slot += 1;
+        }
+
+        self[idx][fork_child_index] = first_fork as u32;
+
+        // New chain is linked only here
+        let shared_chains = self[idx].shared_children();
+        self[idx].set_shared_children(shared_chains & !(1 << fork_child_index));
+
+        if old_fork != 0 && shared_chains & (1 << fork_child_index) == 0 {
+            self.deallocate_chain(old_fork);
+        }
+
+        Ok(())
+    }
+
+    /// Node on level 5 containing given position. 0 if there is none
+    fn node_at_level_5(&self, position: UVec3) -> usize {
+        let mut idx = 1;
+        let mut level = self.depth;
+
+        while level > 5 && idx != 0 {
+            let child_index = Node::get_child_index(position % l2s(level), level - 1);
+
+            idx = self[idx][child_index] as usize;
+            level -= 1;
+        }
+        idx
+    }
+
+    /// Occupancy of every node on level 2 by voxel_id at `start`. Voxels are marked in `taken`
+    fn take_masks(
+        ids: &[u32; BLOCK_VOLUME],
+        taken: &mut BlockMask,
+        start: usize,
+    ) -> [[NodeL2; 8]; 8] {
+        let voxel_id = ids[start];
+        let mut masks = [[NodeL2::default(); 8]; 8];
+
+        for (i, &id) in ids.iter().enumerate().skip(start) {
+            if id == voxel_id && !taken.get(i) {
+                let local = Self::block_local(i);
+
+                masks[Node::get_child_index(local, 3)][Node::get_child_index(local % 8, 2)]
+                    .set(local % 4);
+                taken.set(i);
+            }
+        }
+        masks
+    }
+
+    /// Amount of nodes on upper levels and level 2, which fork chain of block needs. Mirrors [Layer::build_branch]
+    fn block_cost(ids: &[u32; BLOCK_VOLUME], full: &FullNodes) -> (usize, usize) {
+        let full_l2 = NodeL2 {
+            packed_children: [u32::MAX; 2],
+        };
+
+        let mut taken = BlockMask::default();
+        let (mut upper, mut l2, mut entries) = (0, 0, 0usize);
+        // Which fully filled nodes are needed, from level 2 to 4
+        let mut needs_full = [false; 3];
+
+        for start in 0..BLOCK_VOLUME {
+            if ids[start] == 0 || taken.get(start) {
+                continue;
+            }
+
+            let masks = Self::take_masks(ids, &mut taken, start);
+            entries += 1;
+
+            if masks.iter().flatten().all(|mask| *mask == full_l2) {
+                needs_full = [true; 3];
+                continue;
+            }
+            upper += 1;
+
+            for l3_masks in masks.iter() {
+                if l3_masks.iter().all(|mask| mask.is_empty()) {
+                    continue;
+                }
+                if l3_masks.iter().all(|mask| *mask == full_l2) {
+                    needs_full[0] = true;
+                    needs_full[1] = true;
+                    continue;
+                }
+                upper += 1;
+
+                for mask in l3_masks.iter().filter(|mask| !mask.is_empty()) {
+                    if *mask == full_l2 {
+                        needs_full[0] = true;
+                    } else {
+                        l2 += 1;
+                    }
+                }
+            }
+        }
+
+        // Forks
+        upper += entries.div_ceil(4);
+
+        l2 += (needs_full[0] && full.l2 == 0) as usize;
+        upper += (needs_full[1] && full.l3 == 0) as usize;
+        upper += (needs_full[2] && full.branch == 0) as usize;
+
+        (upper, l2)
+    }
+
+    /// Put voxels of branch into block buffer, where nothing is written
+    fn read_branch(
+        &self,
+        branch_idx: usize,
+        voxel_id: u32,
+        ids: &mut [u32; BLOCK_VOLUME],
+        written: &BlockMask,
+    ) {
+        for (l3_child, &l3_idx) in self[branch_idx].children.iter().enumerate() {
+            if l3_idx == 0 {
+                continue;
+            }
+
+            for (l2_child, &l2_idx) in self[l3_idx as usize].children.iter().enumerate() {
+                if l2_idx == 0 {
+                    continue;
+                }
+
+                let node_l2 = self.level_2[l2_idx as usize];
+                let position = Node::get_child_position(l3_child as u32) * 8
+                    + Node::get_child_position(l2_child as u32) * 4;
+
+                for i in 0..64 {
+                    let local = UVec3::new(i % 4, (i / 4) % 4, i / 16);
+
+                    if node_l2.is_at(local) {
+                        let local = position + local;
+                        let i = (local.x + 16 * (local.y + 16 * local.z)) as usize;
+
+                        if !written.get(i) {
+                            ids[i] = voxel_id;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Return fork chain, which is not linked anymore, to holder-pools. Shared nodes are left to other parents
+    fn deallocate_chain(&mut self, mut fork_idx: usize) {
+        while fork_idx != 0 {
+            for slot in 0..4 {
+                // Entries are packed
+                if self[fork_idx][slot * 2] == 0 {
+                    break;
+                }
+
+                let branch_idx = self[fork_idx][slot * 2 + 1] as usize;
+
+                if self[branch_idx].is_shared() {
+                    continue;
+                }
+
+                for l3_child in 0..8 {
+                    let l3_idx = self[branch_idx][l3_child] as usize;
+
+                    if l3_idx == 0 || self[l3_idx].is_shared() {
+                        continue;
+                    }
+
+                    let shared_l2 = self[l3_idx].shared_children();
+
+                    for l2_child in 0..8 {
+                        let l2_idx = self[l3_idx][l2_child] as usize;
+
+                        if l2_idx != 0 && shared_l2 & (1 << l2_child) == 0 {
+                            self.deallocate_node::<NodeL2>(l2_idx);
+                        }
+                    }
+                    self.deallocate_node::<Node>(l3_idx);
+                }
+                self.deallocate_node::<Node>(branch_idx);
+            }
+
+            let flag = self[fork_idx].flag;
+            self.deallocate_node::<Node>(fork_idx);
+            fork_idx = if flag > 0 { flag as usize } else { 0 };
+        }
+    }
+
+    /// Allocate branch on level 4 with given occupancy, reusing fully filled nodes
+    ///
+    /// Expects enough free nodes, see [Layer::block_cost]
+    fn build_branch(&mut self, masks: &[[NodeL2; 8]; 8], full: &mut FullNodes) -> usize {
+        let full_l2 = NodeL2 {
+            packed_children: [u32::MAX; 2],
+        };
+
+        if masks.iter().flatten().all(|mask| *mask == full_l2) {
+            return self.full_branch(full);
+        }
+
+        let branch_idx = self.allocate_node::<Node>();
+
+        for (l3_child, l3_masks) in masks.iter().enumerate() {
+            if l3_masks.iter().all(|mask| mask.is_empty()) {
+                continue;
+            }
+
+            let l3_idx = if l3_masks.iter().all(|mask| *mask == full_l2) {
+                self.full_l3(full)
+            } else {
+                let l3_idx = self.allocate_node::<Node>();
+                let mut shared_l2 = 0;
+
+                for (l2_child, mask) in l3_masks.iter().enumerate() {
+                    if mask.is_empty() {
+                        continue;
+                    }
+
+                    let l2_idx = if *mask == full_l2 {
+                        shared_l2 |= 1 << l2_child;
+                        self.full_l2(full)
+                    } else {
+                        self.allocate_node_from(*mask)
+                    };
+                    self[l3_idx][l2_child] = l2_idx as u32;
+                }
+                self[l3_idx].set_shared_children(shared_l2);
+                l3_idx
+            };
+            self[branch_idx][l3_child] = l3_idx as u32;
+        }
+
+        branch_idx
+    }
+
+    fn full_l2(&mut self, full: &mut FullNodes) -> usize {
+        if full.l2 == 0 {
+            full.l2 = self.allocate_node_from(NodeL2 {
+                packed_children: [u32::MAX; 2],
+            });
+        }
+        full.l2
+    }
+
+    fn full_l3(&mut self, full: &mut FullNodes) -> usize {
+        if full.l3 == 0 {
+            let l2 = self.full_l2(full) as u32;
+            let mut node = Node {
+                flag: 0,
+                children: [l2; 8],
+            };
+            node.set_shared(true);
+            node.set_shared_children(u8::MAX);
+            full.l3 = self.allocate_node_from(node);
+        }
+        full.l3
+    }
+
+    fn full_branch(&mut self, full: &mut FullNodes) -> usize {
+        if full.branch == 0 {
+            let l3 = self.full_l3(full) as u32;
+            let mut node = Node {
+                flag: 0,
+                children: [l3; 8],
+            };
+            node.set_shared(true);
+            full.branch = self.allocate_node_from(node);
+        }
+        full.branch
+    }
+
+    /// Position within node on level 4 by index in block buffer
+    #[inline]
+    fn block_local(i: usize) -> UVec3 {
+        let i = i as u32;
+        UVec3::new(i % 16, (i / 16) % 16, i / 256)
+    }
+
+    /// Voxels touched by box from `min` to `max`, clamped to zero
+    fn bounds(min: Vec3, max: Vec3) -> (UVec3, UVec3) {
+        (
+            min.floor().max(Vec3::ZERO).as_uvec3(),
+            max.ceil().max(Vec3::ZERO).as_uvec3(),
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+    extern crate std;
+
+    use alloc::vec;
+    use spirv_std::glam::{uvec3, vec3, UVec3};
+
+    use crate::{
+        plat::{
+            layer::{error::LayerError, layer::Lr},
+            node::Node,
+            raw_plat::RawPlat,
+        },
+        quick_raw_plat,
+    };
+
+    #[test]
+    fn fill_box() {
+        quick_raw_plat!(plat, depth 7, len 10_000, len2 10_000, lenrest 10);
+
+        plat[Lr::BASE]
+            .fill_box(uvec3(3, 5, 7), uvec3(70, 40, 21), 2)
+            .unwrap();
+
+        for x in 0..80 {
+            for y in 0..50 {
+                for z in 0..30 {
+                    let inside =
+                        (3..70).contains(&x) && (5..40).contains(&y) && (7..21).contains(&z);
+                    assert_eq!(
+                        plat.get_voxel(uvec3(x, y, z)).voxel_id,
+                        if inside { 2 } else { 0 },
+                        "{x} {y} {z}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn fill_box_shares_interior() {
+        quick_raw_plat!(plat, depth 8, len 1_000, len2 1_000, lenrest 10);
+
+        // 128^3 voxels would take way more nodes without sharing
+        plat[Lr::BASE]
+            .fill_box(UVec3::ZERO, UVec3::splat(128), 1)
+            .unwrap();
+
+        assert!(plat[Lr::BASE].shared);
+        assert!(plat[Lr::BASE].free_l2() > 1_000 - 3);
+        assert_eq!(plat.get_voxel(uvec3(127, 0, 64)).voxel_id, 1);
+        assert!(plat.get_voxel(uvec3(128, 0, 64)).is_none());
+
+        // Editing shared interior does not leak into the rest
+        plat[Lr::BASE].set(uvec3(5, 5, 5), 3);
+        plat[Lr::BASE].set(uvec3(6, 5, 5), 0);
+
+        assert_eq!(plat.get_voxel(uvec3(5, 5, 5)).voxel_id, 3);
+        assert!(plat.get_voxel(uvec3(6, 5, 5)).is_none());
+        assert_eq!(plat.get_voxel(uvec3(5, 5, 21)).voxel_id, 1);
+        assert_eq!(plat.get_voxel(uvec3(6, 5, 21)).voxel_id, 1);
+
+        assert!(plat
+            .validate(&mut vec![0; 1_000], &mut vec![false; 1_000], None)
+            .is_correct());
+
+        // Shared nodes are reachable, so only leaked copies are collected
+        plat[Lr::BASE].collect_garbage(&mut vec![false; 1_000], &mut vec![false; 1_000]);
+        assert_eq!(plat.get_voxel(uvec3(5, 5, 5)).voxel_id, 3);
+        assert_eq!(plat.get_voxel(uvec3(100, 100, 100)).voxel_id, 1);
+    }
+
+    #[test]
+    fn fill_over_existing() {
+        quick_raw_plat!(plat, depth 6, len 10_000, len2 10_000, lenrest 10);
+
+        plat[Lr::BASE].set(uvec3(1, 1, 1), 5);
+        plat[Lr::BASE].set(uvec3(20, 1, 1), 5);
+
+        plat[Lr::BASE]
+            .fill_with(UVec3::ZERO, uvec3(32, 2, 2), |p| {
+                if p.x % 2 == 0 {
+                    Some(1)
+                } else {
+                    Some(2)
+                }
+            })
+            .unwrap();
+
+        assert_eq!(plat.get_voxel(uvec3(1, 1, 1)).voxel_id, 2);
+        assert_eq!(plat.get_voxel(uvec3(20, 1, 1)).voxel_id, 1);
+        assert_eq!(plat.get_voxel(uvec3(31, 0, 0)).voxel_id, 2);
+    }
+
+    #[test]
+    fn fill_refill() {
+        quick_raw_plat!(plat, depth 7, len 20_000, len2 20_000, lenrest 10);
+
+        plat[Lr::BASE]
+            .fill_box(UVec3::ZERO, UVec3::splat(16), 1)
+            .unwrap();
+        plat[Lr::BASE]
+            .fill_box(UVec3::ZERO, UVec3::splat(16), 2)
+            .unwrap();
+        // Partially over the filled region
+        plat[Lr::BASE]
+            .fill_box(uvec3(8, 0, 0), uvec3(40, 16, 16), 3)
+            .unwrap();
+        plat[Lr::BASE]
+            .fill_box(uvec3(8, 0, 0), uvec3(40, 16, 16), 3)
+            .unwrap();
+
+        for x in 0..48 {
+            for y in 0..20 {
+                for z in 0..20 {
+                    let expected = match (x, y, z) {
+                        (_, 16.., _) | (_, _, 16..) | (40.., _, _) => 0,
+                        (0..=7, _, _) => 2,
+                        _ => 3,
+                    };
+                    assert_eq!(
+                        plat.get_voxel(uvec3(x, y, z)).voxel_id,
+                        expected,
+                        "{x} {y} {z}"
+                    );
+                }
+            }
+        }
+
+        // Replaced nodes are freed, only shared full ones are left for garbage collection
+        assert!(plat[Lr::BASE].free() > 20_000 - 50);
+        assert!(plat
+            .validate(&mut vec![0; 20_000], &mut vec![false; 20_000], None)
+            .is_correct());
+
+        let report =
+            plat[Lr::BASE].collect_garbage(&mut vec![false; 20_000], &mut vec![false; 20_000]);
+        assert!(report.nodes <= 2 * 3);
+        assert_eq!(plat.get_voxel(uvec3(7, 15, 15)).voxel_id, 2);
+    }
+
+    #[test]
+    fn fill_sphere_and_cylinder() {
+        quick_raw_plat!(plat, depth 6, len 10_000, len2 10_000, lenrest 10_000);
+
+        let center = vec3(20., 20., 20.);
+        plat[Lr::BASE].fill_sphere(center, 10., 1).unwrap();
+
+        let base = vec3(50., 2., 50.);
+        plat[Lr::CANVAS].fill_cylinder(base, 5., 30., 2).unwrap();
+
+        for x in 0..64 {
+            for y in 0..64 {
+                for z in 0..64 {
+                    let p = uvec3(x, y, z).as_vec3() + 0.5;
+
+                    assert_eq!(
+                        plat[Lr::BASE].get_node(uvec3(x, y, z), 0, None).voxel_id,
+                        if (p - center).length() <= 10. { 1 } else { 0 }
+                    );
+
+                    let in_cylinder = vec3(p.x - base.x, 0., p.z - base.z).length() <= 5.
+                        && p.y >= 2.
+                        && p.y <= 32.;
+                    assert_eq!(
+                        plat[Lr::CANVAS].get_node(uvec3(x, y, z), 0, None).voxel_id,
+                        if in_cylinder { 2 } else { 0 }
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn fill_carve() {
+        quick_raw_plat!(plat, depth 6, len 10_000, len2 10_000, lenrest 10);
+        let free = plat[Lr::BASE].free();
+
+        plat[Lr::BASE]
+            .fill_box(UVec3::ZERO, uvec3(64, 32, 64), 1)
+            .unwrap();
+
+        let center = vec3(20., 20., 20.);
+        plat[Lr::BASE].fill_sphere(center, 10., 0).unwrap();
+        plat[Lr::BASE]
+            .fill_cylinder(vec3(50., 0., 50.), 5., 40., 0)
+            .unwrap();
+
+        for x in 0..64 {
+            for y in 0..40 {
+                for z in 0..64 {
+                    let p = uvec3(x, y, z).as_vec3() + 0.5;
+                    let carved = (p - center).length() <= 10.
+                        || vec3(p.x - 50., 0., p.z - 50.).length() <= 5.;
+
+                    assert_eq!(
+                        plat.get_voxel(uvec3(x, y, z)).voxel_id,
+                        if y < 32 && !carved { 1 } else { 0 },
+                        "{x} {y} {z}"
+                    );
+                }
+            }
+        }
+
+        // Emptied blocks lose their fork chains
+        plat[Lr::BASE]
+            .fill_box(UVec3::ZERO, UVec3::splat(64), 0)
+            .unwrap();
+
+        assert!(plat.get_voxel(uvec3(1, 1, 1)).is_none());
+        assert!(plat[Lr::BASE].free() > free - 10);
+        assert!(plat
+            .validate(&mut vec![0; 10_000], &mut vec![false; 10_000], None)
+            .is_correct());
+    }
+
+    #[test]
+    fn fill_errors() {
+        quick_raw_plat!(plat, depth 6, len 6, len2 1_000, lenrest 10);
+        let free = plat[Lr::BASE].free();
+
+        assert_eq!(
+            plat[Lr::BASE].fill_with(UVec3::ZERO, UVec3::splat(64), |p| Some(p.x + 1)),
+            Err(LayerError::OutOfNodes)
+        );
+        // Block which does not fit is not started
+        assert_eq!(plat[Lr::BASE].free(), free);
+
+        plat[Lr::CANVAS].freezed = true;
+        assert_eq!(
+            plat[Lr::CANVAS].fill_box(UVec3::ZERO, UVec3::ONE, 1),
+            Err(LayerError::Frozen)
+        );
+    }
+}
@@ -0,0 +1,422 @@
+use crate::plat::{layer::layer::Layer, node::Node, node_l2::NodeL2, raw_plat::MAX_DEPTH};
+
+/// Amount of nodes returned to holder-pools by [Layer::collect_garbage]
+///
+/// Unreachable nodes have no parents, so their level is unknown and they are counted by kind
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct GarbageReport {
+    /// Regular nodes on upper levels (3..depth)
+    pub nodes: usize,
+    pub forks: usize,
+    pub level_2: usize,
+}
+
+impl Layer<'_> {
+    /// Mark-and-sweep. Returns every node, which is not reachable from root, to holder-pools
+    ///
+    /// Sharing marks of reachable nodes are recounted (see [Node::is_shared]),
+    /// so nodes which lost their other parents are not copied on write anymore.
+    /// If nothing is shared, [Layer::shared] is cleared
+    ///
+    /// `marks` and `marks_l2` are helpers with the same length as `nodes` and `level_2`.
+    /// Their content is overwritten
+    pub fn collect_garbage(&mut self, marks: &mut [bool], marks_l2: &mut [bool]) -> GarbageReport {
+        assert_eq!(marks.len(), self.nodes.len());
+        assert_eq!(marks_l2.len(), self.level_2.len());
+
+        marks.fill(false);
+        marks_l2.fill(false);
+
+        // Free nodes are not garbage
+        {
+            let mut idx = 0;
+            loop {
+                marks[idx] = true;
+                idx = self.nodes[idx].children[0] as usize;
+                if idx == 0 {
+                    break;
+                }
+            }
+
+            let mut idx = 0;
+            loop {
+                marks_l2[idx] = true;
+                idx = self.level_2[idx].packed_children[0] as usize;
+                if idx == 0 {
+                    break;
+                }
+            }
+        }
+
+        let shared = self.mark_reachable(marks, marks_l2);
+
+        let mut report = GarbageReport::default();
+
+        for idx in 2..self.nodes.len() {
+            if marks[idx] {
+                continue;
+            }
+            if self[idx].is_fork() {
+                report.forks += 1;
+            } else {
+                report.nodes += 1;
+            }
+            self.deallocate_node::<Node>(idx);
+        }
+
+        for (idx, &marked) in marks_l2.iter().enumerate().skip(1) {
+            if marked {
+                continue;
+            }
+            report.level_2 += 1;
+            self.deallocate_node::<NodeL2>(idx);
+        }
+
+        if shared {
+            self.mark_first_parents(marks, marks_l2);
+        }
+        self.shared = shared;
+
+        report
+    }
+
+    /// Mark every node reachable from root. Returns true if any of them is shared
+    ///
+    /// Sharing marks are recounted on the way: node linked again is marked as shared, the rest is unmarked.
+    /// Forks and [NodeL2] are marked in parent (see [Node::shared_children]), so here only parents which link them again are marked.
+    /// The first one is found by [Layer::mark_first_parents]
+    fn mark_reachable(&mut self, marks: &mut [bool], marks_l2: &mut [bool]) -> bool {
+        let mut shared = false;
+        // (node_idx, level). Each level pushes at most 8 children
+        let mut stack = [(0usize, 0usize); 8 * MAX_DEPTH];
+        let mut stack_len = 1;
+        // Root is always on 1 idx;
+        stack[0] = (1, self.depth);
+        marks[1] = true;
+
+        while stack_len > 0 {
+            stack_len -= 1;
+            let (idx, level) = stack[stack_len];
+
+            if level > 5 {
+                for child_index in 0..8 {
+                    let child = self[idx][child_index] as usize;
+
+                    if child == 0 {
+                        continue;
+                    }
+                    // Shared nodes are visited only once
+                    if marks[child] {
+                        self[child].set_shared(true);
+                        shared = true;
+                        continue;
+                    }
+                    marks[child] = true;
+                    self[child].set_shared(false);
+                    stack[stack_len] = (child, level - 1);
+                    stack_len += 1;
+                }
+                continue;
+            }
+
+            // Level 5. Children are forks
+            let mut shared_chains = 0;
+
+            for child_index in 0..8 {
+                let mut fork_idx = self[idx][child_index] as usize;
+
+                if fork_idx != 0 && marks[fork_idx] {
+                    shared_chains |= 1 << child_index;
+                    continue;
+                }
+
+                while fork_idx != 0 && !marks[fork_idx] {
+                    marks[fork_idx] = true;
+
+                    for slot in 0..4 {
+                        // Entries are packed
+                        if self[fork_idx][slot * 2] == 0 {
+                            break;
+                        }
+
+                        let branch_idx = self[fork_idx][slot * 2 + 1] as usize;
+                        if marks[branch_idx] {
+                            self[branch_idx].set_shared(true);
+                            shared = true;
+                            continue;
+                        }
+                        marks[branch_idx] = true;
+                        self[branch_idx].set_shared(false);
+
+                        // Level 4 and 3 are handled right here, so stack stays small
+                        for l3_child in 0..8 {
+                            let l3_idx = self[branch_idx][l3_child] as usize;
+
+                            if l3_idx == 0 {
+                                continue;
+                            }
+                            if marks[l3_idx] {
+                                self[l3_idx].set_shared(true);
+                                shared = true;
+                                continue;
+                            }
+                            marks[l3_idx] = true;
+
+                            let mut shared_l2 = 0;
+
+                            for l2_child in 0..8 {
+                                let l2_idx = self[l3_idx][l2_child] as usize;
+
+                                if l2_idx == 0 {
+                                    continue;
+                                }
+                                if marks_l2[l2_idx] {
+                                    shared_l2 |= 1 << l2_child;
+                                }
+                                marks_l2[l2_idx] = true;
+                            }
+
+                            self[l3_idx].set_shared(false);
+                            self[l3_idx].set_shared_children(shared_l2);
+                            shared |= shared_l2 != 0;
+                        }
+                    }
+
+                    let flag = self[fork_idx].flag;
+                    fork_idx = if flag > 0 { flag as usize } else { 0 };
+                }
+            }
+
+            self[idx].set_shared_children(shared_chains);
+            shared |= shared_chains != 0;
+        }
+
+        shared
+    }
+
+    /// Mark forks and [NodeL2] as shared in the parent, which linked them first, see [Layer::mark_reachable]
+    ///
+    /// Takes two walks over reachable nodes. First one clears marks of children which are marked as shared in any parent,
+    /// second one marks such children in every parent. Nodes on upper levels are visited once per walk by flipping their marks
+    fn mark_first_parents(&mut self, marks: &mut [bool], marks_l2: &mut [bool]) {
+        for visited in [false, true] {
+            let mut stack = [(0usize, 0usize); 8 * MAX_DEPTH];
+            let mut stack_len = 1;
+            stack[0] = (1, self.depth);
+            marks[1] = visited;
+
+            while stack_len > 0 {
+                stack_len -= 1;
+                let (idx, level) = stack[stack_len];
+
+                if level > 5 {
+                    for &child in self[idx].children.iter() {
+                        if child != 0 && marks[child as usize] != visited {
+                            marks[child as usize] = visited;
+                            stack[stack_len] = (child as usize, level - 1);
+                            stack_len += 1;
+                        }
+                    }
+                    continue;
+                }
+
+                // Level 5. Shared chains are walked once per parent, which is bounded by their size
+                let mut shared_chains = self[idx].shared_children();
+
+                for child_index in 0..8 {
+                    let mut fork_idx = self[idx][child_index] as usize;
+
+                    if fork_idx == 0 {
+                        continue;
+                    }
+                    if !visited && shared_chains & (1 << child_index) != 0 {
+                        marks[fork_idx] = false;
+                    }
+                    if visited && !marks[fork_idx] {
+                        shared_chains |= 1 << child_index;
+                    }
+
+                    while fork_idx != 0 {
+                        for slot in 0..4 {
+                            if self[fork_idx][slot * 2] == 0 {
+                                break;
+                            }
+
+                            let branch_idx = self[fork_idx][slot * 2 + 1] as usize;
+
+                            for l3_child in 0..8 {
+                                let l3_idx = self[branch_idx][l3_child] as usize;
+
+                                if l3_idx != 0 {
+                                    self.mark_first_parents_l2(marks_l2, l3_idx, visited);
+                                }
+                            }
+                        }
+
+                        let flag = self[fork_idx].flag;
+                        fork_idx = if flag > 0 { flag as usize } else { 0 };
+                    }
+                }
+
+                self[idx].set_shared_children(shared_chains);
+            }
+        }
+    }
+
+    fn mark_first_parents_l2(&mut self, marks_l2: &mut [bool], idx: usize, visited: bool) {
+        let mut shared_l2 = self[idx].shared_children();
+
+        for l2_child in 0..8 {
+            let l2_idx = self[idx][l2_child] as usize;
+
+            if l2_idx == 0 {
+                continue;
+            }
+            if !visited && shared_l2 & (1 << l2_child) != 0 {
+                marks_l2[l2_idx] = false;
+            }
+            if visited && !marks_l2[l2_idx] {
+                shared_l2 |= 1 << l2_child;
+            }
+        }
+
+        self[idx].set_shared_children(shared_l2);
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+    extern crate std;
+
+    use alloc::vec;
+    use spirv_std::glam::uvec3;
+
+    use crate::{
+        plat::{node::Node, raw_plat::RawPlat},
+        quick_raw_plat,
+        test_utils::set_rand_plat,
+    };
+
+    use super::GarbageReport;
+
+    #[test]
+    fn collect_garbage_recounts_shared() {
+        quick_raw_plat!(plat, depth 7, len 1_000, len2 1_000, lenrest 10);
+
+        plat[0].set(uvec3(0, 0, 0), 1);
+        plat[0].set(uvec3(4, 0, 0), 1);
+
+        let level_6 = plat[0][1][0] as usize;
+        let level_5 = plat[0][level_6][0] as usize;
+        let fork = plat[0][level_5][0] as usize;
+        let l3 = plat[0][plat[0][fork][1] as usize][0] as usize;
+
+        // Link the same nodes again, without marks
+        plat[0][1][1] = level_6 as u32;
+        plat[0][level_5][1] = fork as u32;
+        plat[0][l3][2] = plat[0][l3][0];
+
+        plat[0].collect_garbage(&mut vec![false; 1_000], &mut vec![false; 1_000]);
+
+        assert!(plat[0].shared);
+        assert!(plat[0][level_6].is_shared());
+        assert!(!plat[0][level_5].is_shared());
+        // Both parents are marked
+        assert_eq!(plat[0][level_5].shared_children(), 0b11);
+        assert_eq!(plat[0][l3].shared_children(), 0b101);
+
+        assert!(plat
+            .validate(&mut vec![0; 1_000], &mut vec![false; 1_000], None)
+            .is_correct());
+
+        // Nothing is shared anymore
+        plat[0][1][1] = 0;
+        plat[0][level_5][1] = 0;
+        plat[0][l3][2] = 0;
+
+        plat[0].collect_garbage(&mut vec![false; 1_000], &mut vec![false; 1_000]);
+
+        assert!(!plat[0].shared);
+        assert!(!plat[0][level_6].is_shared());
+        assert_eq!(plat[0][level_5].shared_children(), 0);
+        assert_eq!(plat[0][l3].shared_children(), 0);
+    }
+
+    #[test]
+    fn collect_garbage_clean() {
+        quick_raw_plat!(plat, depth 7, len 100_000, len2 100_000, lenrest 10);
+
+        let mtx = set_rand_plat::<32>(&mut plat, 50);
+        let (free, free_l2) = (plat[0].free(), plat[0].free_l2());
+
+        let report = plat[0].collect_garbage(&mut vec![false; 100_000], &mut vec![false; 100_000]);
+
+        assert_eq!(report, GarbageReport::default());
+        assert_eq!((plat[0].free(), plat[0].free_l2()), (free, free_l2));
+
+        for x in 0..32 {
+            for y in 0..32 {
+                for z in 0..32 {
+                    assert_eq!(
+                        plat.get_voxel(uvec3(x, y, z)).voxel_id as u32,
+                        mtx[x as usize][y as usize][z as usize]
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn collect_garbage_detached() {
+        quick_raw_plat!(plat, depth 6, len 1_000, len2 1_000, lenrest 10);
+
+        // Single voxel takes: node on level 5, fork, branch, node on level 3 and NodeL2
+        plat[0].set(uvec3(0, 0, 0), 1);
+        plat[0].set(uvec3(40, 0, 0), 2);
+
+        // Detach first region
+        plat[0][1][0] = 0;
+
+        let report = plat[0].collect_garbage(&mut vec![false; 1_000], &mut vec![false; 1_000]);
+
+        assert_eq!(
+            report,
+            GarbageReport {
+                nodes: 3,
+                forks: 1,
+                level_2: 1
+            }
+        );
+        assert!(plat.get_voxel(uvec3(0, 0, 0)).is_none());
+        assert_eq!(plat.get_voxel(uvec3(40, 0, 0)).voxel_id, 2);
+        // Free-head, root and the second region are left
+        assert_eq!(plat[0].free(), 1_000 - 2 - 4);
+        assert_eq!(plat[0].free_l2(), 1_000 - 1 - 1);
+    }
+
+    #[test]
+    fn collect_garbage_after_copy_path() {
+        quick_raw_plat!(plat, depth 6, len 1_000, len2 1_000, lenrest 10);
+
+        for i in 0..6 {
+            plat[0].set(uvec3(i, 0, 0), i + 1);
+        }
+        let (free, free_l2) = (plat[0].free(), plat[0].free_l2());
+
+        // Originals are leaked, since they are not actually shared
+        let idx = plat[0][1][0] as usize;
+        plat[0][idx].set_shared(true);
+        plat[0].shared = true;
+
+        plat[0].copy_path(uvec3(0, 0, 0));
+        plat[0].collect_garbage(&mut vec![false; 1_000], &mut vec![false; 1_000]);
+
+        assert_eq!((plat[0].free(), plat[0].free_l2()), (free, free_l2));
+        assert!(!plat[0].shared);
+        for i in 0..6 {
+            assert_eq!(plat.get_voxel(uvec3(i, 0, 0)).voxel_id as u32, i + 1);
+        }
+    }
+}
@@ -22,6 +22,11 @@ impl GetNodeResult {
     pub fn is_none(&self) -> bool {
         !self.is_some()
     }
+    /// Explicit air, see [Lr::AIR]
+    #[inline]
+    pub fn is_air(&self) -> bool {
+        self.voxel_id == Lr::AIR as usize
+    }
     #[allow(non_snake_case)]
     pub fn Some(voxel_id: usize, layer_id: usize, node_idx: usize) -> Self {
         Self {
@@ -44,9 +49,27 @@ impl GetNodeResult {
 impl RawPlat<'_> {
     #[inline]
     pub fn get_node(&self, position: UVec3, level: usize) -> GetNodeResult {
-        for layer_idx in (0..4).rev() {
+        self.get_node_in(position, level, u32::MAX)
+    }
+
+    /// Same as [RawPlat::get_node], but only layers in `layers_mask` are taken into account
+    ///
+    /// Layer with id `i` is included if `i`-th bit is set, e.g. `1 << Lr::CANVAS` or `!(1 << Lr::CANVAS)`
+    ///
+    /// Explicit air ([Lr::AIR]) on upper layer hides everything below and results in none
+    #[inline]
+    pub fn get_node_in(&self, position: UVec3, level: usize, layers_mask: u32) -> GetNodeResult {
+        // From top to bottom
+        for &layer_idx in self.order().iter().rev() {
+            if layers_mask & (1 << layer_idx) == 0 {
+                continue;
+            }
+
             let res = self[layer_idx].get_node(position, level, None);
 
+            if res.is_air() {
+                return GetNodeResult::None();
+            }
             if res.is_some() {
                 return res;
             }
@@ -59,6 +82,12 @@ impl RawPlat<'_> {
     pub fn get_voxel(&self, position: UVec3) -> GetNodeResult {
         self.get_node(position, 0)
     }
+
+    /// Same as [RawPlat::get_voxel], but only layers in `layers_mask` are taken into account, see [RawPlat::get_node_in]
+    #[inline]
+    pub fn get_voxel_in(&self, position: UVec3, layers_mask: u32) -> GetNodeResult {
+        self.get_node_in(position, 0, layers_mask)
+    }
 }
 
 impl Lr<'_> {
@@ -77,7 +106,7 @@ impl Lr<'_> {
         let mut idx = 1;
 
         if level == self.depth {
-            return GetNodeResult::Some(0, 0, 1);
+            return GetNodeResult::Some(0, self.id, 1);
         }
 
         while current_level > fork_level {
@@ -89,12 +118,7 @@ impl Lr<'_> {
                 idx = below_node_idx as usize;
 
                 if current_level == level + 1 {
-                    let res = GetNodeResult::Some(
-                        0,
-                        // TODO: Let layer store its id
-                        0,
-                        below_node_idx as usize,
-                    );
+                    let res = GetNodeResult::Some(0, self.id, below_node_idx as usize);
                     return res;
                 }
             } else {
@@ -139,8 +163,7 @@ impl Lr<'_> {
                         if node_l2.is_at(position) {
                             found_idx = GetNodeResult::Some(
                                 props.voxel_id as usize,
-                                // TODO: Let layer store its id
-                                0,
+                                self.id,
                                 below_node_idx as usize,
                             );
                         }
@@ -149,8 +172,7 @@ impl Lr<'_> {
                     if current_level == level + 1 {
                         found_idx = GetNodeResult::Some(
                             props.voxel_id as usize,
-                            // TODO: Let layer store its id
-                            0,
+                            self.id,
                             below_node_idx as usize,
                         );
                         //found_idx = Some(below_node_idx as usize);
@@ -362,4 +384,86 @@ mod tests {
         assert_eq!(plat.get_voxel((0, 1, 0).into()).voxel_id, 2);
         assert_eq!(plat.get_voxel((0, 2, 0).into()).voxel_id, 2);
     }
+
+    #[test]
+    fn get_voxel_layer_id() {
+        quick_raw_plat!(plat, depth 6, len 100, len2 100, lenrest 10);
+
+        plat[Lr::BASE].set(uvec3(1, 1, 1), 1);
+        plat[Lr::CANVAS].set(uvec3(1, 1, 1), 2);
+        plat[Lr::SCHEM].set(uvec3(2, 2, 2), 3);
+
+        let res = plat.get_voxel(uvec3(1, 1, 1));
+        assert_eq!((res.voxel_id, res.layer_id), (2, Lr::CANVAS));
+        let res = plat.get_voxel(uvec3(2, 2, 2));
+        assert_eq!((res.voxel_id, res.layer_id), (3, Lr::SCHEM));
+        // Node on level 3 covers both voxels, top layer wins
+        assert_eq!(plat.get_node(uvec3(2, 2, 2), 3).layer_id, Lr::CANVAS);
+
+        // Canvas only
+        let res = plat.get_voxel_in(uvec3(1, 1, 1), 1 << Lr::CANVAS);
+        assert_eq!((res.voxel_id, res.layer_id), (2, Lr::CANVAS));
+        assert!(plat.get_voxel_in(uvec3(2, 2, 2), 1 << Lr::CANVAS).is_none());
+
+        // Without player edits
+        let res = plat.get_voxel_in(uvec3(1, 1, 1), !(1 << Lr::CANVAS));
+        assert_eq!((res.voxel_id, res.layer_id), (1, Lr::BASE));
+
+        assert!(plat.get_voxel_in(uvec3(1, 1, 1), 0).is_none());
+    }
+
+    #[test]
+    fn get_voxel_air_override() {
+        quick_raw_plat!(plat, depth 6, len 100, len2 100, lenrest 10);
+
+        plat[Lr::BASE].set(uvec3(1, 1, 1), 1);
+        plat[Lr::BASE].set(uvec3(1, 2, 1), 1);
+        plat[Lr::CANVAS].set(uvec3(1, 1, 1), Lr::AIR);
+
+        assert!(plat.get_voxel(uvec3(1, 1, 1)).is_none());
+        assert_eq!(plat.get_voxel(uvec3(1, 2, 1)).voxel_id, 1);
+        // Base itself is untouched
+        assert_eq!(plat.get_voxel_in(uvec3(1, 1, 1), 1 << Lr::BASE).voxel_id, 1);
+        assert!(plat[Lr::CANVAS].get_node(uvec3(1, 1, 1), 0, None).is_air());
+        assert!(plat.get_voxel_in(uvec3(1, 1, 1), 1 << Lr::CANVAS).is_none());
+
+        // Voxel above the tombstone is visible again
+        plat[Lr::TMP].set(uvec3(1, 1, 1), 2);
+        plat.set_order(&[Lr::BASE, Lr::SCHEM, Lr::CANVAS, Lr::TMP]);
+        assert_eq!(plat.get_voxel(uvec3(1, 1, 1)).voxel_id, 2);
+    }
+
+    #[test]
+    fn get_voxel_custom_layers() {
+        quick_raw_plat!(plat, depth 6, len 100, len2 100, lenrest 10);
+        let (mut decals, mut decals_l2) = (
+            alloc::vec![Node::default(); 100],
+            alloc::vec![crate::plat::node_l2::NodeL2::default(); 100],
+        );
+
+        let decals_id = plat.push_layer(Lr::new(6, &mut decals, &mut decals_l2));
+        assert_eq!(decals_id, 4);
+        assert_eq!(plat.layers().len(), 5);
+
+        plat[Lr::CANVAS].set(uvec3(1, 1, 1), 1);
+        plat[decals_id].set(uvec3(1, 1, 1), 2);
+        plat[decals_id].set(uvec3(2, 2, 2), 3);
+
+        // Added layer is on top
+        assert_eq!(plat.get_voxel(uvec3(1, 1, 1)).voxel_id, 2);
+        assert_eq!(plat.get_voxel(uvec3(2, 2, 2)).voxel_id, 3);
+
+        // Put decals right above base
+        plat.set_order(&[Lr::BASE, decals_id, Lr::TMP, Lr::SCHEM, Lr::CANVAS]);
+        assert_eq!(plat.get_voxel(uvec3(1, 1, 1)).voxel_id, 1);
+        assert_eq!(plat.get_voxel(uvec3(2, 2, 2)).voxel_id, 3);
+    }
+
+    #[test]
+    #[should_panic]
+    fn set_order_duplicate() {
+        quick_raw_plat!(plat, depth 6, len 100, len2 100, lenrest 10);
+
+        plat.set_order(&[0, 1, 1, 3]);
+    }
 }
@@ -1,13 +1,46 @@
 use spirv_std::glam::UVec3;
 
-use crate::plat::{chunk::chunk::Chunk, layer::layer::Layer};
+use crate::plat::{chunk::chunk::Chunk, layer::layer::Layer, raw_plat::RawPlat};
+
+impl RawPlat<'_> {
+    /// Compose all layers into chunk. Result is the same as calling [RawPlat::get_voxel] for each voxel
+    #[inline(always)]
+    pub fn load_chunk_gpu(&self, chunk: &mut Chunk) {
+        self.load_chunk_gpu_in(chunk, u32::MAX);
+    }
+
+    /// Same as [RawPlat::load_chunk_gpu], but only layers in `layers_mask` are taken into account, see [RawPlat::get_node_in]
+    #[inline(always)]
+    pub fn load_chunk_gpu_in(&self, chunk: &mut Chunk, layers_mask: u32) {
+        // From bottom to top, so upper layers override lower ones
+        for &layer_idx in self.order() {
+            if layers_mask & (1 << layer_idx) == 0 {
+                continue;
+            }
+            self[layer_idx].load_chunk_gpu(chunk);
+        }
+    }
+
+    pub fn load_chunk(&self, position: UVec3, lod_level: usize, chunk_level: usize) -> Chunk {
+        let mut chunk = Chunk::new(position, lod_level, chunk_level);
+        self.load_chunk_gpu(&mut chunk);
+        chunk
+    }
+}
 
 impl Layer<'_> {
+    /// Write voxels of this layer into chunk on top of its current content
+    ///
+    /// Explicit air ([Layer::AIR]) clears voxel in chunk, so chunk never contains it
     #[inline(always)]
     pub fn load_chunk_gpu(&self, chunk: &mut Chunk) {
         self.traverse(chunk.position(), 0..=(chunk.chunk_level()), |p| {
             if p.level == 0 {
-                chunk.set_global(*p.position, p.voxel_id as u32);
+                let voxel_id = p.voxel_id;
+                chunk.set_global(
+                    *p.position,
+                    if voxel_id == Layer::AIR { 0 } else { voxel_id },
+                );
             }
         });
     }
@@ -25,7 +58,10 @@ mod tests {
 
     use spirv_std::glam::{uvec3, UVec3};
 
-    use crate::{plat::layer::layer::Layer, quick_raw_plat};
+    use crate::{
+        plat::{chunk::chunk::Chunk, layer::layer::Layer},
+        quick_raw_plat,
+    };
 
     extern crate alloc;
     extern crate std;
@@ -56,4 +92,61 @@ mod tests {
         assert!(chunk.get_global(uvec3(61, 61, 61)).is_none());
         assert!(chunk.get_global(uvec3(60, 60, 60)).is_some());
     }
+
+    #[test]
+    fn load_chunk_air() {
+        quick_raw_plat!(plat, depth 12, len 1_000);
+
+        plat[Layer::BASE].set(uvec3(1, 1, 1), 1);
+        plat[Layer::BASE].set(uvec3(2, 2, 2), 1);
+        plat[Layer::CANVAS].set(uvec3(1, 1, 1), Layer::AIR);
+
+        let mut chunk = plat[Layer::BASE].load_chunk(uvec3(0, 0, 0), 0, 5);
+        assert_eq!(chunk.get(uvec3(1, 1, 1)), Some(1));
+
+        // Tombstone is written as empty voxel
+        plat[Layer::CANVAS].load_chunk_gpu(&mut chunk);
+        assert!(chunk.get(uvec3(1, 1, 1)).is_none());
+        assert_eq!(chunk.get(uvec3(2, 2, 2)), Some(1));
+
+        let chunk = plat[Layer::CANVAS].load_chunk(uvec3(0, 0, 0), 0, 5);
+        assert!(chunk.get(uvec3(1, 1, 1)).is_none());
+    }
+
+    #[test]
+    fn load_chunk_all_layers() {
+        quick_raw_plat!(plat, depth 12, len 1_000);
+
+        plat[Layer::BASE].set(uvec3(1, 1, 1), 1);
+        plat[Layer::BASE].set(uvec3(2, 2, 2), 1);
+        plat[Layer::BASE].set(uvec3(3, 3, 3), 1);
+        plat[Layer::TMP].set(uvec3(4, 4, 4), 2);
+        plat[Layer::SCHEM].set(uvec3(2, 2, 2), 3);
+        plat[Layer::CANVAS].set(uvec3(1, 1, 1), Layer::AIR);
+        plat[Layer::CANVAS].set(uvec3(2, 2, 2), 4);
+
+        let chunk = plat.load_chunk(uvec3(0, 0, 0), 0, 5);
+
+        for x in 0..8 {
+            let position = uvec3(x, x, x);
+            assert_eq!(
+                chunk.get(position).unwrap_or_default() as usize,
+                plat.get_voxel(position).voxel_id
+            );
+        }
+        assert!(chunk.get(uvec3(1, 1, 1)).is_none());
+        assert_eq!(chunk.get(uvec3(2, 2, 2)), Some(4));
+        assert_eq!(chunk.get(uvec3(4, 4, 4)), Some(2));
+
+        // Canvas below Schem
+        plat.set_order(&[Layer::BASE, Layer::TMP, Layer::CANVAS, Layer::SCHEM]);
+        let chunk = plat.load_chunk(uvec3(0, 0, 0), 0, 5);
+        assert_eq!(chunk.get(uvec3(2, 2, 2)), Some(3));
+
+        // Base only
+        let mut chunk = Chunk::new(uvec3(0, 0, 0), 0, 5);
+        plat.load_chunk_gpu_in(&mut chunk, 1 << Layer::BASE);
+        assert_eq!(chunk.get(uvec3(1, 1, 1)), Some(1));
+        assert!(chunk.get(uvec3(4, 4, 4)).is_none());
+    }
 }
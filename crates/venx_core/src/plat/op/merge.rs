@@ -0,0 +1,169 @@
+use spirv_std::glam::UVec3;
+
+use crate::plat::{
+    layer::{error::LayerError, layer::Layer},
+    raw_plat::RawPlat,
+};
+
+impl RawPlat<'_> {
+    /// Bake voxels of `src` layer into `dst` layer and clear `src` afterwards
+    ///
+    /// Result looks the same as both layers composed by their priority (see [RawPlat::order]).
+    /// If `src` is above `dst`, its voxels and explicit air ([Layer::AIR]) override `dst`,
+    /// otherwise they only fill empty voxels of `dst`. Air is not kept if there is nothing left to hide below `dst`
+    ///
+    /// On error `src` is not cleared and `dst` might be merged partially.
+    /// Merging is idempotent, so it can be repeated once `dst` has more free nodes
+    pub fn merge_layers(&mut self, src: usize, dst: usize) -> Result<(), LayerError> {
+        assert_ne!(src, dst, "Cannot merge layer into itself");
+
+        self[src].check_writable()?;
+        self[dst].check_writable()?;
+
+        let (src_on_top, dst_is_lowest) = {
+            let order = self.order();
+            let rank = |id| order.iter().position(|&i| i == id).unwrap();
+            let dst_rank = rank(dst);
+
+            (
+                rank(src) > dst_rank,
+                order[..dst_rank].iter().all(|&i| i == src),
+            )
+        };
+
+        let depth = self.depth;
+        let (src_layer, dst_layer) = if src < dst {
+            let (below, above) = self.layers.split_at_mut(dst);
+            (&below[src], &mut above[0])
+        } else {
+            let (below, above) = self.layers.split_at_mut(src);
+            (&above[0], &mut below[dst])
+        };
+
+        let mut result = Ok(());
+
+        src_layer.traverse(UVec3::ZERO, 0..=depth, |p| {
+            if p.level != 0 || result.is_err() {
+                return;
+            }
+
+            let mut voxel_id = p.voxel_id;
+
+            if voxel_id == Layer::AIR && dst_is_lowest {
+                voxel_id = 0;
+            }
+
+            if !src_on_top && (voxel_id == 0 || dst_layer.get_node(*p.position, 0, None).is_some())
+            {
+                return;
+            }
+
+            result = dst_layer.try_set(*p.position, voxel_id);
+        });
+
+        result?;
+
+        self[src].clear();
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+    extern crate std;
+
+    use spirv_std::glam::uvec3;
+
+    use crate::{
+        plat::{
+            layer::{error::LayerError, layer::Lr},
+            node::Node,
+            raw_plat::RawPlat,
+        },
+        quick_raw_plat,
+    };
+
+    #[test]
+    fn merge_canvas_into_base() {
+        quick_raw_plat!(plat, depth 6, len 1_000, len2 1_000, lenrest 1_000);
+
+        plat[Lr::BASE].set(uvec3(1, 1, 1), 1);
+        plat[Lr::BASE].set(uvec3(2, 2, 2), 1);
+        plat[Lr::BASE].set(uvec3(3, 3, 3), 1);
+
+        plat[Lr::CANVAS].set(uvec3(1, 1, 1), 2);
+        plat[Lr::CANVAS].set(uvec3(2, 2, 2), Lr::AIR);
+        plat[Lr::CANVAS].set(uvec3(40, 0, 0), 3);
+
+        plat.merge_layers(Lr::CANVAS, Lr::BASE).unwrap();
+
+        for (position, voxel_id) in [
+            (uvec3(1, 1, 1), 2),
+            (uvec3(2, 2, 2), 0),
+            (uvec3(3, 3, 3), 1),
+            (uvec3(40, 0, 0), 3),
+        ] {
+            assert_eq!(plat.get_voxel(position).voxel_id, voxel_id);
+            assert_eq!(
+                plat[Lr::BASE].get_node(position, 0, None).voxel_id,
+                voxel_id
+            );
+        }
+
+        // Source is cleared
+        assert!(plat[Lr::CANVAS][1].is_empty());
+        assert_eq!(plat[Lr::CANVAS].free(), 1_000 - 2);
+    }
+
+    #[test]
+    fn merge_respects_order() {
+        quick_raw_plat!(plat, depth 6, len 1_000, len2 1_000, lenrest 1_000);
+
+        // Tmp is below Schem by default
+        plat[Lr::TMP].set(uvec3(1, 1, 1), 1);
+        plat[Lr::TMP].set(uvec3(2, 2, 2), 1);
+        plat[Lr::TMP].set(uvec3(3, 3, 3), Lr::AIR);
+        plat[Lr::SCHEM].set(uvec3(1, 1, 1), 2);
+        plat[Lr::BASE].set(uvec3(3, 3, 3), 5);
+
+        // Merging lower layer into upper one, upper voxels win
+        plat.merge_layers(Lr::TMP, Lr::SCHEM).unwrap();
+
+        assert_eq!(
+            plat[Lr::SCHEM].get_node(uvec3(1, 1, 1), 0, None).voxel_id,
+            2
+        );
+        assert_eq!(
+            plat[Lr::SCHEM].get_node(uvec3(2, 2, 2), 0, None).voxel_id,
+            1
+        );
+        // Base is still below, so air has to stay
+        assert!(plat[Lr::SCHEM].get_node(uvec3(3, 3, 3), 0, None).is_air());
+        assert!(plat.get_voxel(uvec3(3, 3, 3)).is_none());
+    }
+
+    #[test]
+    fn merge_out_of_nodes() {
+        quick_raw_plat!(plat, depth 6, len 4, len2 2, lenrest 1_000);
+
+        plat[Lr::CANVAS].set(uvec3(1, 1, 1), 1);
+        plat[Lr::CANVAS].set(uvec3(40, 40, 40), 2);
+
+        assert_eq!(
+            plat.merge_layers(Lr::CANVAS, Lr::BASE),
+            Err(LayerError::OutOfNodes)
+        );
+        // Nothing is lost
+        assert_eq!(plat.get_voxel(uvec3(1, 1, 1)).voxel_id, 1);
+        assert_eq!(plat.get_voxel(uvec3(40, 40, 40)).voxel_id, 2);
+
+        plat[Lr::BASE].freezed = true;
+        assert_eq!(
+            plat.merge_layers(Lr::CANVAS, Lr::BASE),
+            Err(LayerError::Frozen)
+        );
+    }
+}
@@ -1,5 +1,16 @@
+pub mod collide;
+mod cow;
+pub mod diff;
+mod fill;
+pub mod gc;
 pub mod get;
 pub mod load;
+mod merge;
+pub mod raycast;
+mod region;
+mod remove;
 pub mod resize;
 mod set;
 pub mod traverse;
+pub mod validate;
+mod world;
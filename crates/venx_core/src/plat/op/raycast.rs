@@ -0,0 +1,379 @@
+use spirv_std::glam::{IVec3, UVec3, Vec3};
+
+use crate::{
+    plat::{layer::layer::Layer, node::Node, raw_plat::RawPlat},
+    utils::l2s,
+};
+
+/// Result of [RawPlat::raycast]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RaycastHit {
+    /// Position of hit voxel in octree space
+    pub position: UVec3,
+    /// Normal of the face ray entered hit voxel through. Zero if ray starts inside of it
+    pub normal: IVec3,
+    /// If 0 its none
+    pub voxel_id: u32,
+    pub layer_id: usize,
+    /// Distance from origin to the hit, in voxels
+    pub distance: f32,
+}
+
+impl RaycastHit {
+    #[inline]
+    pub fn is_some(&self) -> bool {
+        self.voxel_id != 0
+    }
+    #[inline]
+    pub fn is_none(&self) -> bool {
+        !self.is_some()
+    }
+
+    #[allow(non_snake_case)]
+    pub fn None() -> Self {
+        Self {
+            position: UVec3::ZERO,
+            normal: IVec3::ZERO,
+            voxel_id: 0,
+            layer_id: 0,
+            distance: 0.,
+        }
+    }
+}
+
+impl RawPlat<'_> {
+    /// Cast ray through layers in `layers_mask` and return first voxel it hits, see [RawPlat::get_node_in]
+    ///
+    /// `origin` is in octree space and ray can start outside of plat. Subtrees which are empty in all masked layers
+    /// are skipped at once, so long rays through sparse plats are cheap.
+    /// Explicit air ([Layer::AIR]) is not hit, it hides voxels below as usual
+    pub fn raycast(
+        &self,
+        origin: Vec3,
+        direction: Vec3,
+        max_distance: f32,
+        layers_mask: u32,
+    ) -> RaycastHit {
+        if direction == Vec3::ZERO {
+            return RaycastHit::None();
+        }
+        let direction = direction.normalize();
+        let size = self.size();
+
+        // Clip ray by plat bounds
+        let (mut t, mut t_exit) = (0., max_distance);
+        let mut normal = IVec3::ZERO;
+
+        for axis in 0..3 {
+            if direction[axis] == 0. {
+                if origin[axis] < 0. || origin[axis] >= size as f32 {
+                    return RaycastHit::None();
+                }
+                continue;
+            }
+
+            let mut near = -origin[axis] / direction[axis];
+            let mut far = (size as f32 - origin[axis]) / direction[axis];
+
+            if near > far {
+                (near, far) = (far, near);
+            }
+            if near > t {
+                t = near;
+                normal = IVec3::ZERO;
+                normal[axis] = if direction[axis] > 0. { -1 } else { 1 };
+            }
+            if far < t_exit {
+                t_exit = far;
+            }
+        }
+
+        if t > t_exit {
+            return RaycastHit::None();
+        }
+
+        let mut voxel = (origin + direction * t)
+            .floor()
+            .max(Vec3::ZERO)
+            .min(Vec3::splat((size - 1) as f32))
+            .as_uvec3();
+
+        loop {
+            let level = self.empty_level_in(voxel, layers_mask);
+
+            if level == 0 {
+                let res = self.get_node_in(voxel, 0, layers_mask);
+
+                if res.is_some() {
+                    return RaycastHit {
+                        position: voxel,
+                        normal,
+                        voxel_id: res.voxel_id as u32,
+                        layer_id: res.layer_id,
+                        distance: t,
+                    };
+                }
+            }
+
+            // Leave empty cube
+            let cube_size = l2s(level);
+            let cube_min = (voxel / cube_size) * cube_size;
+
+            let (mut t_next, mut exit_axis) = (f32::INFINITY, 0);
+
+            for axis in 0..3 {
+                if direction[axis] == 0. {
+                    continue;
+                }
+                let bound = if direction[axis] > 0. {
+                    cube_min[axis] + cube_size
+                } else {
+                    cube_min[axis]
+                };
+                let t_axis = (bound as f32 - origin[axis]) / direction[axis];
+
+                if t_axis < t_next {
+                    (t_next, exit_axis) = (t_axis, axis);
+                }
+            }
+
+            if t_next > t_exit {
+                return RaycastHit::None();
+            }
+            t = t_next;
+
+            // Voxel on the other side of exit face
+            let mut next = (origin + direction * t).floor().as_ivec3();
+            let (cube_min, cube_size) = (cube_min.as_ivec3(), cube_size as i32);
+
+            for axis in 0..3 {
+                next[axis] = next[axis]
+                    .max(cube_min[axis])
+                    .min(cube_min[axis] + cube_size - 1);
+            }
+
+            normal = IVec3::ZERO;
+            if direction[exit_axis] > 0. {
+                next[exit_axis] = cube_min[exit_axis] + cube_size;
+                normal[exit_axis] = -1;
+            } else {
+                next[exit_axis] = cube_min[exit_axis] - 1;
+                normal[exit_axis] = 1;
+            }
+
+            if next.min_element() < 0 || next.max_element() >= size as i32 {
+                return RaycastHit::None();
+            }
+            voxel = next.as_uvec3();
+        }
+    }
+
+    /// Level of the biggest cube containing `position`, which is empty in all layers in `layers_mask`
+    ///
+    /// 0 means that voxel itself might be occupied
+    pub fn empty_level_in(&self, position: UVec3, layers_mask: u32) -> usize {
+        let mut level = self.depth();
+
+        for &layer_idx in self.order() {
+            if layers_mask & (1 << layer_idx) == 0 {
+                continue;
+            }
+            level = level.min(self[layer_idx].empty_level(position));
+
+            if level == 0 {
+                return 0;
+            }
+        }
+        level
+    }
+}
+
+impl Layer<'_> {
+    /// Level of the biggest empty cube containing `position`. 0 if voxel is occupied or might be
+    pub fn empty_level(&self, position: UVec3) -> usize {
+        let mut idx = 1;
+        let mut level = self.depth;
+
+        // Down to fork
+        while level > 4 {
+            let child_index = Node::get_child_index(position % l2s(level), level - 1);
+
+            idx = self[idx].children[child_index] as usize;
+
+            if idx == 0 {
+                return level - 1;
+            }
+            level -= 1;
+        }
+
+        // Cube is empty only if it is empty in all branches
+        let mut empty_level = 3;
+
+        self.iter_fork(idx, &mut |props| {
+            let l3_idx =
+                self[props.node_idx].children[Node::get_child_index(position % 16, 3)] as usize;
+
+            if l3_idx == 0 {
+                return;
+            }
+
+            let l2_idx = self[l3_idx].children[Node::get_child_index(position % 8, 2)] as usize;
+
+            if l2_idx == 0 {
+                empty_level = empty_level.min(2);
+            } else {
+                empty_level = 0;
+                // Occupied, no need to look further
+                props.drop = self.level_2[l2_idx].is_at(position % 4);
+            }
+        });
+
+        empty_level
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+    extern crate std;
+
+    use spirv_std::glam::{ivec3, uvec3, vec3};
+
+    use crate::{
+        plat::{layer::layer::Lr, node::Node, raw_plat::RawPlat},
+        quick_raw_plat,
+    };
+
+    #[test]
+    fn raycast_hit() {
+        quick_raw_plat!(plat, depth 7, len 1_000, len2 1_000, lenrest 1_000);
+
+        plat[Lr::BASE].set(uvec3(100, 5, 7), 3);
+        plat[Lr::BASE].set(uvec3(110, 5, 7), 4);
+
+        let hit = plat.raycast(vec3(0.5, 5.5, 7.5), vec3(1., 0., 0.), 1_000., u32::MAX);
+
+        assert!(hit.is_some());
+        assert_eq!(hit.position, uvec3(100, 5, 7));
+        assert_eq!(hit.normal, ivec3(-1, 0, 0));
+        assert_eq!(hit.voxel_id, 3);
+        assert_eq!(hit.layer_id, Lr::BASE);
+        assert!((hit.distance - 99.5).abs() < 0.001);
+
+        // From the other side
+        let hit = plat.raycast(vec3(127.5, 5.5, 7.5), vec3(-1., 0., 0.), 1_000., u32::MAX);
+        assert_eq!(hit.position, uvec3(110, 5, 7));
+        assert_eq!(hit.normal, ivec3(1, 0, 0));
+
+        // Too short
+        assert!(plat
+            .raycast(vec3(0.5, 5.5, 7.5), vec3(1., 0., 0.), 50., u32::MAX)
+            .is_none());
+        // Miss
+        assert!(plat
+            .raycast(vec3(0.5, 6.5, 7.5), vec3(1., 0., 0.), 1_000., u32::MAX)
+            .is_none());
+        // Starts inside
+        let hit = plat.raycast(vec3(100.2, 5.5, 7.5), vec3(0., 1., 0.), 1_000., u32::MAX);
+        assert_eq!(hit.position, uvec3(100, 5, 7));
+        assert_eq!(hit.normal, ivec3(0, 0, 0));
+    }
+
+    #[test]
+    fn raycast_from_outside() {
+        quick_raw_plat!(plat, depth 6, len 1_000, len2 1_000, lenrest 1_000);
+
+        plat[Lr::BASE].set(uvec3(20, 30, 40), 1);
+
+        let target = vec3(20.5, 30.5, 40.5);
+        let origin = vec3(-50., 100., -10.);
+
+        let hit = plat.raycast(origin, target - origin, 1_000., u32::MAX);
+
+        assert_eq!(hit.position, uvec3(20, 30, 40));
+        assert_ne!(hit.normal, ivec3(0, 0, 0));
+
+        // Pointing away
+        assert!(plat
+            .raycast(origin, origin - target, 1_000., u32::MAX)
+            .is_none());
+    }
+
+    #[test]
+    fn raycast_layers() {
+        quick_raw_plat!(plat, depth 6, len 1_000, len2 1_000, lenrest 1_000);
+
+        plat[Lr::BASE].set(uvec3(10, 0, 0), 1);
+        plat[Lr::BASE].set(uvec3(20, 0, 0), 1);
+        plat[Lr::CANVAS].set(uvec3(10, 0, 0), Lr::AIR);
+        plat[Lr::CANVAS].set(uvec3(30, 0, 0), 2);
+
+        let origin = vec3(0.5, 0.5, 0.5);
+        let direction = vec3(1., 0., 0.);
+
+        // Air hides first voxel
+        let hit = plat.raycast(origin, direction, 1_000., u32::MAX);
+        assert_eq!(hit.position, uvec3(20, 0, 0));
+
+        let hit = plat.raycast(origin, direction, 1_000., 1 << Lr::BASE);
+        assert_eq!(hit.position, uvec3(10, 0, 0));
+
+        let hit = plat.raycast(origin, direction, 1_000., 1 << Lr::CANVAS);
+        assert_eq!(hit.position, uvec3(30, 0, 0));
+        assert_eq!(hit.layer_id, Lr::CANVAS);
+    }
+
+    #[test]
+    fn raycast_matches_get_voxel() {
+        use rand::Rng;
+
+        quick_raw_plat!(plat, depth 6, len 10_000, len2 10_000, lenrest 10);
+        let mut rng = rand::thread_rng();
+
+        for _ in 0..300 {
+            let position = uvec3(
+                rng.gen_range(0..64),
+                rng.gen_range(0..64),
+                rng.gen_range(0..64),
+            );
+            plat[Lr::BASE].set(position, rng.gen_range(1..4));
+        }
+
+        for _ in 0..200 {
+            let origin = vec3(
+                rng.gen_range(0.0..64.0),
+                rng.gen_range(0.0..64.0),
+                rng.gen_range(0.0..64.0),
+            );
+            let direction = vec3(
+                rng.gen_range(-1.0..1.0),
+                rng.gen_range(-1.0..1.0),
+                rng.gen_range(-1.0..1.0),
+            );
+            if direction.length() < 0.01 {
+                continue;
+            }
+            let hit = plat.raycast(origin, direction, 1_000., u32::MAX);
+
+            // Naive march with small steps finds nothing in front of the hit
+            let direction = direction.normalize();
+            let until = if hit.is_some() { hit.distance } else { 120. };
+            let mut t = 0.;
+            while t < until - 0.02 {
+                let p = origin + direction * t;
+                if p.min_element() < 0. || p.max_element() >= 64. {
+                    break;
+                }
+                assert!(plat.get_voxel(p.as_uvec3()).is_none());
+                t += 0.01;
+            }
+
+            if hit.is_some() {
+                assert!(plat.get_voxel(hit.position).is_some());
+                let p = origin + direction * (hit.distance + 0.001);
+                assert_eq!(p.as_uvec3(), hit.position);
+            }
+        }
+    }
+}
@@ -0,0 +1,304 @@
+use spirv_std::glam::UVec3;
+
+use crate::{
+    plat::{
+        layer::{error::LayerError, layer::Layer},
+        node::Node,
+        node_l2::NodeL2,
+        raw_plat::MAX_DEPTH,
+    },
+    utils::l2s,
+};
+
+impl Layer<'_> {
+    /// Call `callback` on every voxel of this layer in box from `min` to `max` (exclusive)
+    ///
+    /// Explicit air ([Layer::AIR]) is reported as well. Subtrees outside of box are skipped
+    /// and voxels on level 2 are read from whole [NodeL2] at once
+    pub fn extract_region<F>(&self, min: UVec3, max: UVec3, mut callback: F)
+    where
+        F: FnMut(UVec3, u32),
+    {
+        let max = max.min(UVec3::splat(l2s(self.depth)));
+
+        if min.cmpge(max).any() {
+            return;
+        }
+
+        let intersects = |position: UVec3, level: usize| {
+            (position + l2s(level)).cmpgt(min).all() && position.cmplt(max).all()
+        };
+
+        // (idx, level, position)
+        let mut stack = [(0usize, 0usize, UVec3::ZERO); 8 * MAX_DEPTH];
+        stack[0] = (1, self.depth, UVec3::ZERO);
+        let mut stack_len = 1;
+
+        while stack_len > 0 {
+            stack_len -= 1;
+            let (idx, level, position) = stack[stack_len];
+
+            for child_index in 0..8 {
+                let child_idx = self[idx][child_index] as usize;
+                let child_position =
+                    position + Node::get_child_position(child_index as u32) * l2s(level - 1);
+
+                if child_idx == 0 || !intersects(child_position, level - 1) {
+                    continue;
+                }
+
+                if level > 5 {
+                    stack[stack_len] = (child_idx, level - 1, child_position);
+                    stack_len += 1;
+                    continue;
+                }
+
+                // Children of level 5 are forks
+                self.iter_fork(child_idx, &mut |props| {
+                    for l3_child in 0..8 {
+                        let l3_idx = self[props.node_idx][l3_child] as usize;
+                        let l3_position =
+                            child_position + Node::get_child_position(l3_child as u32) * 8;
+
+                        if l3_idx == 0 || !intersects(l3_position, 3) {
+                            continue;
+                        }
+
+                        for l2_child in 0..8 {
+                            let l2_idx = self[l3_idx][l2_child] as usize;
+                            let l2_position =
+                                l3_position + Node::get_child_position(l2_child as u32) * 4;
+
+                            if l2_idx == 0 || !intersects(l2_position, 2) {
+                                continue;
+                            }
+
+                            let node_l2: NodeL2 = self.level_2[l2_idx];
+
+                            for i in 0..64 {
+                                let local = UVec3::new(i % 4, (i / 4) % 4, i / 16);
+                                let voxel_position = l2_position + local;
+
+                                if node_l2.is_at(local)
+                                    && voxel_position.cmpge(min).all()
+                                    && voxel_position.cmplt(max).all()
+                                {
+                                    callback(voxel_position, props.voxel_id as u32);
+                                }
+                            }
+                        }
+                    }
+                });
+            }
+        }
+    }
+
+    /// Make node on `level` at `dst_position` point to the subtree at `src_position`, without copying it
+    ///
+    /// Positions are in voxels and aligned to the size of `level`, which is at least 4 (fork).
+    /// Subtree is marked as shared (see [Node::is_shared]) and so is layer (see [Layer::shared]).
+    /// Previous content of destination is replaced and left for garbage collection
+    pub fn link_subtree(
+        &mut self,
+        src_position: UVec3,
+        dst_position: UVec3,
+        level: usize,
+    ) -> Result<(), LayerError> {
+        assert!(level >= 4 && level < self.depth);
+        assert!(
+            src_position % l2s(level) == UVec3::ZERO && dst_position % l2s(level) == UVec3::ZERO
+        );
+
+        self.check_writable()?;
+
+        let size = l2s(self.depth);
+
+        if src_position.cmpge(UVec3::splat(size)).any()
+            || dst_position.cmpge(UVec3::splat(size)).any()
+        {
+            return Err(LayerError::OutOfBounds);
+        }
+
+        if src_position == dst_position {
+            return Ok(());
+        }
+
+        // Copy or missing node on each level down to the parent
+        if !self.has_free::<Node>(self.depth - level - 1) {
+            return Err(LayerError::OutOfNodes);
+        }
+
+        let mut idx = 1;
+        let mut current_level = self.depth;
+
+        while current_level > level + 1 {
+            let child_index =
+                Node::get_child_index(dst_position % l2s(current_level), current_level - 1);
+
+            idx = if self[idx][child_index] == 0 {
+                let new_idx = self.allocate_node::<Node>();
+                self[idx][child_index] = new_idx as u32;
+                new_idx
+            } else {
+                self.own_node(idx, child_index, current_level - 1)
+            };
+            current_level -= 1;
+        }
+
+        // Source is looked up only now, since its parents might be copied on the way
+        let (src_parent_idx, src_child_index) = self.parent_at(src_position, level);
+        let src_idx = if src_parent_idx == 0 {
+            0
+        } else {
+            self[src_parent_idx][src_child_index]
+        };
+
+        let child_index = Node::get_child_index(dst_position % l2s(level + 1), level);
+        self[idx][child_index] = src_idx;
+
+        if level == 4 {
+            // Fork chains are marked in parents
+            let mask = self[idx].shared_children() & !(1 << child_index);
+            self[idx].set_shared_children(mask | ((src_idx != 0) as u8) << child_index);
+        }
+
+        if src_idx == 0 {
+            return Ok(());
+        }
+
+        if level == 4 {
+            let mask = self[src_parent_idx].shared_children();
+            self[src_parent_idx].set_shared_children(mask | 1 << src_child_index);
+        } else {
+            self[src_idx as usize].set_shared(true);
+        }
+        self.shared = true;
+
+        Ok(())
+    }
+
+    /// Node on `level + 1` containing `position` and child index of node on `level` in it. Parent is 0 if there is none
+    fn parent_at(&self, position: UVec3, level: usize) -> (usize, usize) {
+        let mut idx = 1;
+        let mut current_level = self.depth;
+
+        while current_level > level + 1 && idx != 0 {
+            let child_index =
+                Node::get_child_index(position % l2s(current_level), current_level - 1);
+
+            idx = self[idx][child_index] as usize;
+            current_level -= 1;
+        }
+        (idx, Node::get_child_index(position % l2s(level + 1), level))
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+    extern crate std;
+
+    use alloc::{vec, vec::Vec};
+    use rand::Rng;
+    use spirv_std::glam::{uvec3, UVec3};
+
+    use crate::{
+        plat::{
+            layer::{error::LayerError, layer::Lr},
+            node::Node,
+            raw_plat::RawPlat,
+        },
+        quick_raw_plat,
+    };
+
+    #[test]
+    fn extract_region() {
+        quick_raw_plat!(plat, depth 7, len 10_000, len2 10_000, lenrest 10);
+        let mut rng = rand::thread_rng();
+
+        for _ in 0..1_000 {
+            let position = uvec3(
+                rng.gen_range(0..128),
+                rng.gen_range(0..128),
+                rng.gen_range(0..128),
+            );
+            plat[Lr::BASE].set(position, rng.gen_range(1..4));
+        }
+        plat[Lr::BASE].set(uvec3(10, 10, 20), Lr::AIR);
+
+        let (min, max) = (uvec3(5, 0, 17), uvec3(90, 64, 100));
+
+        let mut found = Vec::new();
+        plat[Lr::BASE].extract_region(min, max, |position, voxel_id| {
+            found.push((position, voxel_id))
+        });
+        found.sort_by_key(|(p, _)| p.to_array());
+
+        let mut expected = Vec::new();
+        for x in min.x..max.x {
+            for y in min.y..max.y {
+                for z in min.z..max.z {
+                    let res = plat[Lr::BASE].get_node(uvec3(x, y, z), 0, None);
+                    if res.is_some() {
+                        expected.push((uvec3(x, y, z), res.voxel_id as u32));
+                    }
+                }
+            }
+        }
+        expected.sort_by_key(|(p, _)| p.to_array());
+
+        assert_eq!(found, expected);
+        assert!(found.contains(&(uvec3(10, 10, 20), Lr::AIR)));
+    }
+
+    #[test]
+    fn link_subtree() {
+        quick_raw_plat!(plat, depth 7, len 1_000, len2 1_000, lenrest 10);
+
+        plat[Lr::BASE].set(uvec3(1, 2, 3), 1);
+        plat[Lr::BASE].set(uvec3(20, 2, 3), 2);
+        plat[Lr::BASE].set(uvec3(31, 31, 31), 1);
+
+        let free = (plat[Lr::BASE].free(), plat[Lr::BASE].free_l2());
+
+        plat[Lr::BASE]
+            .link_subtree(UVec3::ZERO, uvec3(64, 32, 0), 5)
+            .unwrap();
+        plat[Lr::BASE]
+            .link_subtree(uvec3(16, 0, 0), uvec3(0, 96, 16), 4)
+            .unwrap();
+
+        assert!(plat[Lr::BASE].shared);
+        let level_6 = plat[Lr::BASE][1][0] as usize;
+        assert!(plat[Lr::BASE][plat[Lr::BASE][level_6][0] as usize].is_shared());
+        // Only nodes on the way are allocated
+        assert_eq!(plat[Lr::BASE].free_l2(), free.1);
+        assert!(plat[Lr::BASE].free() >= free.0 - 4);
+
+        assert_eq!(plat.get_voxel(uvec3(65, 34, 3)).voxel_id, 1);
+        assert_eq!(plat.get_voxel(uvec3(84, 34, 3)).voxel_id, 2);
+        assert_eq!(plat.get_voxel(uvec3(95, 63, 31)).voxel_id, 1);
+        assert_eq!(plat.get_voxel(uvec3(4, 98, 19)).voxel_id, 2);
+
+        // Copies are independent
+        plat[Lr::BASE].set(uvec3(65, 34, 3), 0);
+        plat[Lr::BASE].set(uvec3(20, 2, 3), 3);
+
+        assert!(plat.get_voxel(uvec3(65, 34, 3)).is_none());
+        assert_eq!(plat.get_voxel(uvec3(1, 2, 3)).voxel_id, 1);
+        assert_eq!(plat.get_voxel(uvec3(84, 34, 3)).voxel_id, 2);
+        assert_eq!(plat.get_voxel(uvec3(4, 98, 19)).voxel_id, 2);
+        assert_eq!(plat.get_voxel(uvec3(20, 2, 3)).voxel_id, 3);
+
+        assert!(plat
+            .validate(&mut vec![0; 1_000], &mut vec![false; 1_000], None)
+            .is_correct());
+
+        plat[Lr::BASE].freezed = true;
+        assert_eq!(
+            plat[Lr::BASE].link_subtree(UVec3::ZERO, uvec3(0, 64, 0), 5),
+            Err(LayerError::Frozen)
+        );
+    }
+}
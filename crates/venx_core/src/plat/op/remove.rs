@@ -0,0 +1,425 @@
+use spirv_std::glam::UVec3;
+
+use crate::{
+    plat::{
+        layer::layer::Layer,
+        node::{AllocatableNode, Node},
+        node_l2::NodeL2,
+        raw_plat::MAX_DEPTH,
+    },
+    utils::l2s,
+};
+
+impl Layer<'_> {
+    /// Remove every voxel. All nodes except free-head and root are returned to holder-pools
+    ///
+    /// Nothing is walked, so it works on shared layers as well. Panics if layer is frozen or read-only
+    pub fn clear(&mut self) {
+        if let Err(err) = self.check_writable() {
+            panic!("{}", err);
+        }
+
+        self.nodes[0].children[0] = 0;
+        self.nodes[1] = Node::default();
+        self.level_2[0].set_child(0, 0);
+
+        let (len, len_l2) = (self.nodes.len(), self.level_2.len());
+        self.link_free::<Node>(2, len);
+        self.link_free::<NodeL2>(1, len_l2);
+
+        self.shared = false;
+    }
+
+    /// Remove voxel on given position, no matter which voxel_id it has
+    ///
+    /// Clears the bit in [NodeL2] and walks back up to the root,
+    /// returning every node, branch and fork entry which became empty to the holder-pool
+    ///
+    /// Panics if layer is frozen or read-only
+    pub fn unset(&mut self, mut position: UVec3) {
+        if let Err(err) = self.check_writable() {
+            panic!("{}", err);
+        }
+
+        let mut size = l2s(self.depth);
+        let mut level = self.depth;
+
+        assert!(level > 4);
+
+        // If given position is out of bound
+        if position.y >= size || position.x >= size || position.z >= size {
+            return;
+        }
+
+        if self.shared {
+            self.copy_path(position);
+        }
+
+        // Path from root down to the node which points to forks
+        // Each entry is (node_idx, child_index)
+        // Depth is bounded to [MAX_DEPTH], so is path
+        let mut path = [(0usize, 0usize); MAX_DEPTH];
+        let mut path_len = 0;
+        // Root is always on 1 idx;
+        let mut idx = 1;
+
+        while level > 5 {
+            let child_index = Node::get_child_index(position, level - 1);
+
+            path[path_len] = (idx, child_index);
+            path_len += 1;
+
+            let below_node_idx = self[idx][child_index];
+
+            if below_node_idx == 0 {
+                // Nothing to remove
+                return;
+            }
+
+            idx = below_node_idx as usize;
+
+            {
+                size /= 2;
+                level -= 1;
+                position %= size;
+            }
+        }
+
+        // We are on level 5. Children of this node are forks
+        let child_index = Node::get_child_index(position, 4);
+        path[path_len] = (idx, child_index);
+        path_len += 1;
+        position %= size / 2;
+
+        if self[idx][child_index] == 0 {
+            return;
+        }
+
+        self.unset_in_fork(idx, child_index, position, 0);
+
+        // Walk back up and clean all branches which became empty
+        // Root is never deallocated
+        for i in (1..path_len).rev() {
+            let (node_idx, child_index) = path[i];
+
+            if self[node_idx][child_index] != 0 || !self[node_idx].is_empty() {
+                break;
+            }
+
+            self.deallocate_node::<Node>(node_idx);
+
+            let (parent_idx, parent_child_index) = path[i - 1];
+            self[parent_idx][parent_child_index] = 0;
+        }
+    }
+
+    /// Unset voxel in each branch of fork chain linked from `parent_idx` by `child_index`
+    ///
+    /// `position` is local to the branch (0..16)
+    ///
+    /// Branch with `keep_voxel_id` is not touched. Use 0 to unset in all branches
+    pub(crate) fn unset_in_fork(
+        &mut self,
+        parent_idx: usize,
+        child_index: usize,
+        position: UVec3,
+        keep_voxel_id: u32,
+    ) {
+        let mut fork_idx = self[parent_idx][child_index] as usize;
+        let mut slot = 0;
+
+        loop {
+            let voxel_id = self[fork_idx][slot * 2];
+
+            // Entries in forks are always packed, so first empty one is the end of chain
+            if voxel_id == 0 {
+                return;
+            }
+
+            let branch_idx = self[fork_idx][slot * 2 + 1] as usize;
+
+            if voxel_id != keep_voxel_id && self.unset_in_branch(branch_idx, position) {
+                // Branch is deallocated, so its entry needs to go
+                // Gap is filled with last entry in chain, so we need to check the same slot again
+                if self.remove_fork_entry(parent_idx, child_index, fork_idx, slot) {
+                    return;
+                }
+                continue;
+            }
+
+            slot += 1;
+
+            if slot == 4 {
+                let flag = self[fork_idx].flag;
+                if flag > 0 {
+                    // Switch to next fork in chain
+                    fork_idx = flag as usize;
+                    slot = 0;
+                } else {
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Unset voxel within branch on level 4
+    ///
+    /// Returns true if branch became empty and was deallocated
+    fn unset_in_branch(&mut self, branch_idx: usize, mut position: UVec3) -> bool {
+        let l3_child_index = Node::get_child_index(position, 3);
+        let l3_idx = self[branch_idx][l3_child_index] as usize;
+
+        if l3_idx == 0 {
+            return false;
+        }
+
+        position %= 8;
+
+        let l2_child_index = Node::get_child_index(position, 2);
+        let l2_idx = self[l3_idx][l2_child_index] as usize;
+
+        if l2_idx == 0 {
+            return false;
+        }
+
+        position %= 4;
+
+        let node_l2 = &mut self.level_2[l2_idx];
+
+        if !node_l2.is_at(position) {
+            return false;
+        }
+
+        node_l2.unset(position);
+
+        if !node_l2.is_empty() {
+            return false;
+        }
+
+        self.deallocate_node::<NodeL2>(l2_idx);
+        self[l3_idx][l2_child_index] = 0;
+
+        if !self[l3_idx].is_empty() {
+            return false;
+        }
+
+        self.deallocate_node::<Node>(l3_idx);
+        self[branch_idx][l3_child_index] = 0;
+
+        if !self[branch_idx].is_empty() {
+            return false;
+        }
+
+        self.deallocate_node::<Node>(branch_idx);
+
+        true
+    }
+
+    /// Remove entry on `slot` in `fork_idx` by moving the last entry of chain in its place
+    ///
+    /// Deallocates the tail fork if it became empty.
+    ///
+    /// Returns true if removed entry was the last one in chain, so there is nothing left to iterate
+    fn remove_fork_entry(
+        &mut self,
+        parent_idx: usize,
+        child_index: usize,
+        fork_idx: usize,
+        slot: usize,
+    ) -> bool {
+        // Find tail of chain and the fork before it
+        let mut prev_idx = 0;
+        let mut tail_idx = self[parent_idx][child_index] as usize;
+
+        while self[tail_idx].flag > 0 {
+            prev_idx = tail_idx;
+            tail_idx = self[tail_idx].flag as usize;
+        }
+
+        let mut last_slot = 3;
+        while self[tail_idx][last_slot * 2] == 0 {
+            last_slot -= 1;
+        }
+
+        let is_last = tail_idx == fork_idx && last_slot == slot;
+
+        if !is_last {
+            self[fork_idx][slot * 2] = self[tail_idx][last_slot * 2];
+            self[fork_idx][slot * 2 + 1] = self[tail_idx][last_slot * 2 + 1];
+        }
+
+        self[tail_idx][last_slot * 2] = 0;
+        self[tail_idx][last_slot * 2 + 1] = 0;
+
+        if last_slot == 0 {
+            // Tail is empty now
+            self.deallocate_node::<Node>(tail_idx);
+
+            if prev_idx != 0 {
+                self[prev_idx].flag = -3;
+            } else {
+                self[parent_idx][child_index] = 0;
+            }
+        }
+
+        is_last
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+    extern crate std;
+
+    use spirv_std::glam::uvec3;
+
+    use crate::{
+        plat::{node::Node, raw_plat::RawPlat},
+        quick_raw_plat,
+        test_utils::set_rand_plat,
+    };
+
+    #[test]
+    fn unset_single() {
+        quick_raw_plat!(plat, depth 6, len 100, len2 100, lenrest 10);
+
+        plat[0].set(uvec3(7, 20, 5), 1);
+        assert!(plat.get_voxel(uvec3(7, 20, 5)).is_some());
+
+        plat[0].unset(uvec3(7, 20, 5));
+        assert!(plat.get_voxel(uvec3(7, 20, 5)).is_none());
+
+        // Everything except free-head and root is returned
+        assert_eq!(plat[0].free(), 100 - 2);
+        assert_eq!(plat[0].free_l2(), 100 - 1);
+        assert!(plat[0][1].is_empty());
+    }
+
+    #[test]
+    fn clear_all() {
+        quick_raw_plat!(plat, depth 7, len 100_000, len2 100_000, lenrest 10);
+
+        set_rand_plat::<32>(&mut plat, 20);
+        plat[0].clear();
+
+        assert_eq!(plat[0].free(), 100_000 - 2);
+        assert_eq!(plat[0].free_l2(), 100_000 - 1);
+        assert!(plat[0][1].is_empty());
+        assert!(plat.get_voxel(uvec3(0, 0, 0)).is_none());
+
+        // Layer is usable afterwards
+        plat[0].set(uvec3(3, 4, 5), 7);
+        assert_eq!(plat.get_voxel(uvec3(3, 4, 5)).voxel_id, 7);
+    }
+
+    #[test]
+    fn unset_keeps_neighbors() {
+        quick_raw_plat!(plat, depth 6, len 100, len2 100, lenrest 10);
+
+        plat[0].set(uvec3(0, 0, 0), 1);
+        plat[0].set(uvec3(1, 0, 0), 1);
+        plat[0].set(uvec3(0, 0, 1), 2);
+
+        plat[0].unset(uvec3(0, 0, 0));
+
+        assert!(plat.get_voxel(uvec3(0, 0, 0)).is_none());
+        assert_eq!(plat.get_voxel(uvec3(1, 0, 0)).voxel_id, 1);
+        assert_eq!(plat.get_voxel(uvec3(0, 0, 1)).voxel_id, 2);
+    }
+
+    #[test]
+    fn unset_non_existing() {
+        quick_raw_plat!(plat, depth 6, len 100, len2 100, lenrest 10);
+
+        plat[0].unset(uvec3(3, 3, 3));
+        plat[0].set(uvec3(0, 0, 0), 1);
+        plat[0].unset(uvec3(3, 3, 3));
+        // Out of bound
+        plat[0].unset(uvec3(300, 3, 3));
+
+        assert_eq!(plat.get_voxel(uvec3(0, 0, 0)).voxel_id, 1);
+    }
+
+    #[test]
+    fn unset_with_set_zero() {
+        quick_raw_plat!(plat, depth 6, len 100, len2 100, lenrest 10);
+
+        plat[0].set(uvec3(5, 5, 5), 3);
+        plat[0].set(uvec3(5, 5, 5), 0);
+
+        assert!(plat.get_voxel(uvec3(5, 5, 5)).is_none());
+        assert_eq!(plat[0].free(), 100 - 2);
+    }
+
+    #[test]
+    fn unset_fork_chain() {
+        quick_raw_plat!(plat, depth 6, len 1_000, len2 1_000, lenrest 10);
+
+        // 10 voxel types in single chunk. Fork chain of 3 forks
+        for i in 0..10 {
+            plat[0].set(uvec3(i, 0, 0), i + 1);
+        }
+
+        // Remove from the middle of chain
+        plat[0].unset(uvec3(2, 0, 0));
+        plat[0].unset(uvec3(5, 0, 0));
+
+        for i in 0..10 {
+            let res = plat.get_voxel(uvec3(i, 0, 0));
+            if i == 2 || i == 5 {
+                assert!(res.is_none());
+            } else {
+                assert_eq!(res.voxel_id, i as usize + 1);
+            }
+        }
+
+        for i in 0..10 {
+            plat[0].unset(uvec3(i, 0, 0));
+        }
+
+        assert_eq!(plat[0].free(), 1_000 - 2);
+        assert_eq!(plat[0].free_l2(), 1_000 - 1);
+    }
+
+    #[test]
+    fn unset_all_random() {
+        quick_raw_plat!(plat, depth 7, len 200_000, len2 200_000, lenrest 10);
+
+        let mtx = set_rand_plat::<32>(&mut plat, 50);
+
+        for x in 0..32 {
+            for y in 0..32 {
+                for z in 0..32 {
+                    if (x + y + z) % 2 == 0 {
+                        plat[0].unset(uvec3(x, y, z));
+                    }
+                }
+            }
+        }
+
+        for x in 0..32 {
+            for y in 0..32 {
+                for z in 0..32 {
+                    let res = plat.get_voxel(uvec3(x, y, z));
+                    if (x + y + z) % 2 == 0 {
+                        assert!(res.is_none());
+                    } else {
+                        assert_eq!(res.voxel_id as u32, mtx[x as usize][y as usize][z as usize]);
+                    }
+                }
+            }
+        }
+
+        for x in 0..32 {
+            for y in 0..32 {
+                for z in 0..32 {
+                    plat[0].unset(uvec3(x, y, z));
+                }
+            }
+        }
+
+        assert_eq!(plat[0].free(), 200_000 - 2);
+        assert_eq!(plat[0].free_l2(), 200_000 - 1);
+    }
+}
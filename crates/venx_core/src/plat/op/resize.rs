@@ -1,11 +1,234 @@
-use crate::plat::{node::AllocatableNode, node_l2::NodeL2};
+use crate::plat::{
+    node::{AllocatableNode, Node},
+    node_l2::NodeL2,
+    raw_plat::MAX_DEPTH,
+};
 
 impl crate::plat::layer::layer::Layer<'_> {
     /// Rearrange all nodes so `free` nodes can be just cutted of
     /// Returns index, by which user can cut
     /// First index is `nodes` and second for `level2`
-    pub fn shrink_to_fit_all() -> (usize, usize) {
-        todo!()
+    ///
+    /// Both holder-pools are empty afterwards, so nothing can be allocated until layer gets extended
+    /// and new nodes are linked with [Layer::link_free]
+    pub fn shrink_to_fit_all(&mut self) -> (usize, usize) {
+        /*
+            Two-finger compaction:
+
+            1. Move live nodes from the end into free slots at the beginning
+            and leave forwarding index in the old place
+            2. Walk the tree from root and replace every link which points behind cut
+            with forwarding index. Links to not moved nodes are always in front of cut,
+            so relinking the same (shared) node twice is harmless.
+            Still, shared nodes are marked as visited, so each subtree is walked once
+            3. Clean everything behind cut
+        */
+        let cut = self.compact_nodes();
+        let cut_l2 = self.compact_l2();
+
+        self.relink(cut, cut_l2);
+
+        for node in self.nodes[..cut].iter_mut() {
+            if node.is_visited() {
+                node.set_visited(false);
+            }
+        }
+
+        for node in self.nodes.iter_mut().skip(cut) {
+            *node = Node {
+                flag: -1,
+                children: [0; 8],
+            };
+        }
+
+        for node in self.level_2.iter_mut().skip(cut_l2) {
+            *node = NodeL2::default();
+        }
+
+        // Pools are empty now
+        Node::set_first_free_link(self, 0);
+        NodeL2::set_first_free_link(self, 0);
+
+        (cut, cut_l2)
+    }
+
+    /// Move all live [Node]s in front of free ones. Returns amount of nodes in front
+    ///
+    /// Each moved node leaves its new index in `children[0]` of the old place
+    fn compact_nodes(&mut self) -> usize {
+        let nodes = &mut self.nodes;
+        // Free-head and root are never moved
+        let mut lo = 2;
+        let mut hi = nodes.len() - 1;
+
+        loop {
+            while lo < hi && nodes[lo].flag != -1 {
+                lo += 1;
+            }
+            while hi > lo && nodes[hi].flag == -1 {
+                hi -= 1;
+            }
+            if lo >= hi {
+                break;
+            }
+
+            nodes[lo] = nodes[hi];
+            // Forward
+            nodes[hi] = Node {
+                flag: -1,
+                children: [lo as u32, 0, 0, 0, 0, 0, 0, 0],
+            };
+            hi -= 1;
+        }
+
+        if lo < nodes.len() && nodes[lo].flag != -1 {
+            lo + 1
+        } else {
+            lo
+        }
+    }
+
+    /// Same as [Layer::compact_nodes], but for `level_2`
+    fn compact_l2(&mut self) -> usize {
+        // NodeL2 has no flag, so free nodes are zeroed to be recognizable.
+        // Live NodeL2 are never empty, otherwise they would be deallocated
+        let mut idx = NodeL2::get_first_free_link(self) as usize;
+        while idx != 0 {
+            let next = self.level_2[idx].packed_children[0] as usize;
+            self.level_2[idx] = NodeL2::default();
+            idx = next;
+        }
+
+        let l2 = &mut self.level_2;
+        // Free-head is never moved
+        let mut lo = 1;
+        let mut hi = l2.len() - 1;
+
+        loop {
+            while lo < hi && !l2[lo].is_empty() {
+                lo += 1;
+            }
+            while hi > lo && l2[hi].is_empty() {
+                hi -= 1;
+            }
+            if lo >= hi {
+                break;
+            }
+
+            l2[lo] = l2[hi];
+            // Forward
+            l2[hi] = NodeL2 {
+                packed_children: [lo as u32, 0],
+            };
+            // Forwarding node is not empty, so it has to be skipped explicitly
+            hi -= 1;
+        }
+
+        if lo < l2.len() && !l2[lo].is_empty() {
+            lo + 1
+        } else {
+            lo
+        }
+    }
+
+    /// Replace all links pointing behind cuts with forwarding indices
+    fn relink(&mut self, cut: usize, cut_l2: usize) {
+        let forward = |nodes: &[Node], idx: u32| {
+            if idx as usize >= cut {
+                nodes[idx as usize].children[0]
+            } else {
+                idx
+            }
+        };
+
+        // (node_idx, level). Each level pushes at most 8 children
+        let mut stack = [(0usize, 0usize); 8 * MAX_DEPTH];
+        let mut stack_len = 1;
+        // Root is always on 1 idx;
+        stack[0] = (1, self.depth);
+
+        while stack_len > 0 {
+            stack_len -= 1;
+            let (idx, level) = stack[stack_len];
+
+            if level > 5 {
+                for i in 0..8 {
+                    let child = forward(self.nodes, self[idx][i]);
+                    self[idx][i] = child;
+
+                    if child != 0 && self.visit(child as usize) {
+                        stack[stack_len] = (child as usize, level - 1);
+                        stack_len += 1;
+                    }
+                }
+                continue;
+            }
+
+            // Level 5. Children are forks
+            for i in 0..8 {
+                let mut fork_idx = forward(self.nodes, self[idx][i]);
+                self[idx][i] = fork_idx;
+
+                while fork_idx != 0 {
+                    let fork = fork_idx as usize;
+
+                    for slot in 0..4 {
+                        if self[fork][slot * 2] == 0 {
+                            break;
+                        }
+                        let branch_idx = forward(self.nodes, self[fork][slot * 2 + 1]);
+                        self[fork][slot * 2 + 1] = branch_idx;
+
+                        if !self.visit(branch_idx as usize) {
+                            continue;
+                        }
+
+                        // Level 4 and 3 are handled right here, so stack stays small
+                        for j in 0..8 {
+                            let l3_idx = forward(self.nodes, self[branch_idx as usize][j]);
+                            self[branch_idx as usize][j] = l3_idx;
+
+                            if l3_idx == 0 || !self.visit(l3_idx as usize) {
+                                continue;
+                            }
+
+                            for k in 0..8 {
+                                let l2_idx = self[l3_idx as usize][k] as usize;
+                                if l2_idx >= cut_l2 {
+                                    self[l3_idx as usize][k] =
+                                        self.level_2[l2_idx].packed_children[0];
+                                }
+                            }
+                        }
+                    }
+
+                    let flag = self[fork].flag;
+                    if flag > 0 {
+                        let next = forward(self.nodes, flag as u32);
+                        self[fork].flag = next as i32;
+                        fork_idx = next;
+                    } else {
+                        fork_idx = 0;
+                    }
+                }
+            }
+        }
+    }
+
+    /// True if node is reached for the first time by [Layer::relink]
+    ///
+    /// Only shared nodes can be reached again, so only they are marked as visited
+    fn visit(&mut self, idx: usize) -> bool {
+        let node = &mut self[idx];
+
+        if !node.is_shared() {
+            return true;
+        }
+        if node.is_visited() {
+            return false;
+        }
+        node.set_visited(true);
+        true
     }
 
     /// Shrinks only `layer.level2`
@@ -177,8 +400,30 @@ impl crate::plat::layer::layer::Layer<'_> {
     /// Shrinks only `layer.nodes`
     /// Almost Zero-cost operation
     /// Removes only free nodes that are on tail and have no fragmentation
-    pub fn freeze_upper_fast() -> usize {
-        todo!()
+    ///
+    /// Returns index, by which user can cut
+    pub fn freeze_upper_fast(&mut self) -> usize {
+        let mut cut = self.nodes.len();
+
+        while cut > 2 && self.nodes[cut - 1].flag == -1 {
+            cut -= 1;
+        }
+
+        // Unlink tail from holder-pool
+        let mut prev = 0;
+        loop {
+            let next = self.nodes[prev].children[0] as usize;
+            if next == 0 {
+                break;
+            }
+            if next >= cut {
+                self.nodes[prev].children[0] = self.nodes[next].children[0];
+            } else {
+                prev = next;
+            }
+        }
+
+        cut
     }
 }
 
@@ -187,8 +432,11 @@ mod tests {
     use crate::quick_raw_plat;
     extern crate alloc;
     extern crate std;
-    use crate::plat::{node::Node, raw_plat::RawPlat};
+    use crate::plat::{node::Node, node_l2::NodeL2, raw_plat::RawPlat};
     use crate::test_utils::set_rand_plat;
+    use crate::utils::l2s;
+    use alloc::vec;
+    use spirv_std::glam::{uvec3, UVec3};
 
     #[test]
     fn freeze_l2_empty() {
@@ -217,4 +465,138 @@ mod tests {
         set_rand_plat::<64>(&mut plat, 90);
         plat.layers[0].freeze_upper(&mut alloc::vec![0; plat.layers[0].level_2.len()]);
     }
+
+    #[test]
+    fn shrink_to_fit_all_empty() {
+        quick_raw_plat!(plat, depth 6, len 100, len2 100, lenrest 10);
+
+        assert_eq!(plat[0].shrink_to_fit_all(), (2, 1));
+        assert_eq!(plat[0].free(), 0);
+        assert_eq!(plat[0].free_l2(), 0);
+    }
+
+    #[test]
+    fn shrink_to_fit_all_fragmented() {
+        quick_raw_plat!(plat, depth 7, len 200_000, len2 200_000, lenrest 10);
+
+        let mtx = set_rand_plat::<32>(&mut plat, 50);
+
+        // Make holes all over the place
+        for x in 0..32 {
+            for y in 0..32 {
+                for z in 0..32 {
+                    if (x * 7 + y * 3 + z) % 5 == 0 {
+                        plat[0].unset(uvec3(x, y, z));
+                    }
+                }
+            }
+        }
+
+        let used = plat[0].nodes.len() - plat[0].free();
+        let used_l2 = plat[0].level_2.len() - plat[0].free_l2();
+
+        assert_eq!(plat[0].shrink_to_fit_all(), (used, used_l2));
+
+        for x in 0..32 {
+            for y in 0..32 {
+                for z in 0..32 {
+                    let res = plat.get_voxel(uvec3(x, y, z));
+                    if (x * 7 + y * 3 + z) % 5 == 0 {
+                        assert!(res.is_none());
+                    } else {
+                        assert_eq!(res.voxel_id as u32, mtx[x as usize][y as usize][z as usize]);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn shrink_to_fit_all_shared() {
+        quick_raw_plat!(plat, depth 16, len 1_000, len2 1_000, lenrest 10);
+        let size = plat.size();
+
+        plat[0].set(uvec3(0, 0, 0), 1);
+        // Becomes garbage, so there are holes to compact
+        plat[0].set(uvec3(size - 1, 5, size - 1), 2);
+
+        // Every node links the same child 8 times, so there are 8^11 paths to the only voxel
+        for level in 4..16 {
+            for child in 1..8 {
+                plat[0]
+                    .link_subtree(
+                        UVec3::ZERO,
+                        Node::get_child_position(child) * l2s(level),
+                        level,
+                    )
+                    .unwrap();
+            }
+        }
+        plat[0].collect_garbage(&mut vec![false; 1_000], &mut vec![false; 1_000]);
+
+        let used = plat[0].nodes.len() - plat[0].free();
+        assert_eq!(plat[0].shrink_to_fit_all().0, used);
+
+        assert!(plat[0].nodes.iter().all(|node| !node.is_visited()));
+        assert_eq!(plat.get_voxel(uvec3(0, 0, 0)).voxel_id, 1);
+        assert_eq!(plat.get_voxel(uvec3(size - 16, 16, size - 16)).voxel_id, 1);
+        assert!(plat.get_voxel(uvec3(size - 1, 5, size - 1)).is_none());
+        assert!(plat
+            .validate(&mut vec![0; 1_000], &mut vec![false; 1_000], None)
+            .is_correct());
+    }
+
+    #[test]
+    fn shrink_to_fit_all_and_extend() {
+        quick_raw_plat!(plat, depth 6, len 1_000, len2 1_000, lenrest 10);
+
+        for i in 0..20 {
+            plat[0].set(uvec3(i, i, i), i % 3 + 1);
+        }
+        for i in 0..10 {
+            plat[0].unset(uvec3(i * 2, i * 2, i * 2));
+        }
+
+        let (cut, cut_l2) = plat[0].shrink_to_fit_all();
+        // Everything behind cut is not linked anymore
+        assert!(!plat[0].has_free::<Node>(1));
+        assert!(!plat[0].has_free::<NodeL2>(1));
+
+        // Unfreeze
+        let (len, len_l2) = (plat[0].nodes.len(), plat[0].level_2.len());
+        plat[0].link_free::<Node>(cut, len);
+        plat[0].link_free::<NodeL2>(cut_l2, len_l2);
+
+        for i in 0..10 {
+            plat[0].set(uvec3(i * 2, i * 2, i * 2), 5);
+        }
+
+        for i in 0..20 {
+            let expected = if i % 2 == 0 { 5 } else { i % 3 + 1 };
+            assert_eq!(plat.get_voxel(uvec3(i, i, i)).voxel_id as u32, expected);
+        }
+    }
+
+    #[test]
+    fn freeze_upper_fast_tail() {
+        quick_raw_plat!(plat, depth 6, len 100, len2 100, lenrest 10);
+
+        plat[0].set(uvec3(0, 0, 0), 1);
+        plat[0].set(uvec3(40, 0, 0), 2);
+
+        let used = 100 - plat[0].free();
+        let cut = plat[0].freeze_upper_fast();
+
+        // Nothing was deallocated yet, so all free nodes are on tail
+        assert_eq!(cut, used);
+        assert_eq!(plat[0].free(), 0);
+
+        plat[0].unset(uvec3(0, 0, 0));
+        // Now there is a gap in front of live nodes
+        let free = plat[0].free();
+        assert!(free > 0);
+        assert_eq!(plat[0].freeze_upper_fast(), cut);
+        assert_eq!(plat[0].free(), free);
+        assert_eq!(plat.get_voxel(uvec3(40, 0, 0)).voxel_id, 2);
+    }
 }
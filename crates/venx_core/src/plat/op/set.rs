@@ -3,33 +3,62 @@ use crate::plat::node_l2::NodeL2;
 use spirv_std::glam::UVec3;
 
 use crate::{
-    plat::{layer::layer::Layer, node::Node},
+    plat::{
+        layer::{error::LayerError, layer::Layer},
+        node::Node,
+        raw_plat::MAX_DEPTH,
+    },
     utils::l2s,
 };
 
 impl Layer<'_> {
-    pub fn set(&mut self, mut position: UVec3, voxel_id: u32) {
+    /// Set voxel on given position
+    ///
+    /// If there is already voxel with different voxel_id, it gets replaced
+    ///
+    /// `voxel_id` 0 removes voxel, see [Layer::unset]
+    ///
+    /// Panics if layer is frozen or read-only, use [Layer::try_set] to handle that
+    pub fn set(&mut self, position: UVec3, voxel_id: u32) {
+        if let Err(err) = self.check_writable() {
+            panic!("{}", err);
+        }
+
         if voxel_id == 0 {
+            self.unset(position);
             return;
         }
 
-        // Root is always on 1 idx;
-        let mut idx = 1;
+        let size = l2s(self.depth);
 
-        let mut size = l2s(self.depth);
-
-        let mut level = self.depth;
-
-        assert!(level > 4);
+        assert!(self.depth > 4);
 
         // If given position is out of bound
         if position.y >= size || position.x >= size || position.z >= size {
             return;
         }
 
+        if self.shared {
+            self.copy_path(position);
+        }
+
+        // Root is always on 1 idx;
+        self.set_below(1, self.depth, position, voxel_id);
+    }
+
+    /// Set voxel within node on `idx` at `level`. `position` is local to that node
+    fn set_below(&mut self, mut idx: usize, mut level: usize, mut position: UVec3, voxel_id: u32) {
+        let mut size = l2s(level);
+
         while level > 3 {
             let child_index = Node::get_child_index(position, level - 1);
 
+            if level == 5 && self[idx][child_index] != 0 {
+                // Voxel might already be there with different voxel_id
+                // Clear it, so position only ever resolves to one type
+                self.unset_in_fork(idx, child_index, position % (size / 2), voxel_id);
+            }
+
             idx = self.set_child(idx, voxel_id, child_index, level, 4);
 
             {
@@ -63,6 +92,220 @@ impl Layer<'_> {
         }
         return;
     }
+
+    /// Fallible version of [Layer::set]
+    ///
+    /// Checks bounds, forks and amount of free nodes before mutating anything,
+    /// so on error layer stays untouched
+    pub fn try_set(&mut self, position: UVec3, voxel_id: u32) -> Result<(), LayerError> {
+        self.check_writable()?;
+
+        let size = l2s(self.depth);
+
+        if position.y >= size || position.x >= size || position.z >= size {
+            return Err(LayerError::OutOfBounds);
+        }
+
+        let (upper, l2) = self.required_nodes(position, voxel_id)?;
+
+        if !self.has_free::<Node>(upper) {
+            return Err(LayerError::OutOfNodes);
+        }
+
+        if !self.has_free::<NodeL2>(l2) {
+            return Err(LayerError::OutOfL2Nodes);
+        }
+
+        if voxel_id == 0 {
+            self.unset(position);
+        } else {
+            self.set(position, voxel_id);
+        }
+
+        Ok(())
+    }
+
+    /// Set many voxels at once, see [Layer::set]
+    ///
+    /// Path from the root is shared with previous voxel, so it is walked only below their common node.
+    /// Sort voxels in octree order (Morton order with z as the most significant axis) to make most of it.
+    /// On shared layers shared nodes on the path are copied first (see [Layer::copy_path]). Removals fall back to [Layer::try_set]
+    ///
+    /// On error voxels before failed one are set. Setting is idempotent, so the same batch can be repeated
+    pub fn try_set_many(&mut self, voxels: &[(UVec3, u32)]) -> Result<(), LayerError> {
+        self.check_writable()?;
+
+        let size = l2s(self.depth);
+
+        // Nodes on each level containing previous voxel, valid down to level 5
+        let mut path = [0usize; MAX_DEPTH + 1];
+        path[self.depth] = 1;
+        // Nothing is shared with the first voxel
+        let mut previous = UVec3::splat(size);
+
+        for &(position, voxel_id) in voxels {
+            if position.y >= size || position.x >= size || position.z >= size {
+                return Err(LayerError::OutOfBounds);
+            }
+
+            if voxel_id == 0 {
+                self.try_set(position, voxel_id)?;
+                // Removal might deallocate nodes on the path
+                previous = UVec3::splat(size);
+                continue;
+            }
+
+            let (copy_upper, copy_l2) = if self.shared {
+                self.copy_path_cost(position)
+            } else {
+                (0, 0)
+            };
+
+            // Upper bound of what [Layer::set] allocates
+            if !self.has_free::<Node>(self.depth - 2 + copy_upper) {
+                return Err(LayerError::OutOfNodes);
+            }
+            if !self.has_free::<NodeL2>(1 + copy_l2) {
+                return Err(LayerError::OutOfL2Nodes);
+            }
+
+            if copy_upper + copy_l2 > 0 {
+                self.copy_path(position);
+                // Nodes on the path are replaced by copies
+                previous = UVec3::splat(size);
+            }
+
+            // Deepest node containing both voxels
+            let mut level = self.depth;
+            while level > 5 && previous >> (level as u32 - 1) == position >> (level as u32 - 1) {
+                level -= 1;
+            }
+
+            let mut idx = path[level];
+
+            while level > 5 {
+                let child_index = Node::get_child_index(position % l2s(level), level - 1);
+
+                idx = self.try_set_child(idx, voxel_id, child_index, level, 4)?;
+                level -= 1;
+                path[level] = idx;
+            }
+
+            self.set_below(idx, 5, position % 32, voxel_id);
+            previous = position;
+        }
+
+        Ok(())
+    }
+
+    /// Amount of nodes on upper levels and level 2 which [Layer::set] would allocate
+    ///
+    /// Includes copies of shared path (see [Layer::copy_path]). Does not mutate layer
+    pub fn required_nodes(
+        &self,
+        position: UVec3,
+        voxel_id: u32,
+    ) -> Result<(usize, usize), LayerError> {
+        let size = l2s(self.depth);
+
+        if position.y >= size || position.x >= size || position.z >= size {
+            return Err(LayerError::OutOfBounds);
+        }
+
+        let (copy_upper, copy_l2) = if self.shared {
+            self.copy_path_cost(position)
+        } else {
+            (0, 0)
+        };
+
+        // Unset only deallocates
+        if voxel_id == 0 {
+            return Ok((copy_upper, copy_l2));
+        }
+
+        let (upper, l2) = self.required_nodes_unshared(position, voxel_id)?;
+
+        Ok((upper + copy_upper, l2 + copy_l2))
+    }
+
+    fn required_nodes_unshared(
+        &self,
+        mut position: UVec3,
+        voxel_id: u32,
+    ) -> Result<(usize, usize), LayerError> {
+        // Root is always on 1 idx;
+        let mut idx = 1;
+        let mut size = l2s(self.depth);
+        let mut level = self.depth;
+
+        while level > 5 {
+            let child_index = Node::get_child_index(position, level - 1);
+            let below_node_idx = self[idx][child_index];
+
+            if below_node_idx == 0 {
+                // Nodes from level below down to 5, fork, branch on level 4 and node on level 3
+                return Ok((level - 5 + 3, 1));
+            }
+
+            idx = below_node_idx as usize;
+
+            {
+                size /= 2;
+                level -= 1;
+                position %= size;
+            }
+        }
+
+        let child_index = Node::get_child_index(position, 4);
+        let mut fork_idx = self[idx][child_index] as usize;
+        position %= size / 2;
+
+        if fork_idx == 0 {
+            // Fork, branch on level 4 and node on level 3
+            return Ok((3, 1));
+        }
+
+        let branch_idx = 'search: loop {
+            let fork = &self[fork_idx];
+
+            if !fork.is_fork() {
+                return Err(LayerError::InvalidFork);
+            }
+
+            for voxel_id_idx in 0..4 {
+                let found_voxel_id = fork[voxel_id_idx * 2];
+
+                if found_voxel_id == voxel_id {
+                    break 'search fork[voxel_id_idx * 2 + 1] as usize;
+                }
+                if found_voxel_id == 0 {
+                    // Branch on level 4 and node on level 3
+                    return Ok((2, 1));
+                }
+            }
+
+            if fork.flag > 0 {
+                fork_idx = fork.flag as usize;
+            } else {
+                // Chain is full, so it needs to be extended
+                return Ok((3, 1));
+            }
+        };
+
+        let l3_idx = self[branch_idx][Node::get_child_index(position, 3)] as usize;
+
+        if l3_idx == 0 {
+            return Ok((1, 1));
+        }
+
+        position %= 8;
+
+        if self[l3_idx][Node::get_child_index(position, 2)] == 0 {
+            return Ok((0, 1));
+        }
+
+        Ok((0, 0))
+    }
 }
 
 #[cfg(feature = "std")]
@@ -72,13 +315,21 @@ mod tests {
     extern crate alloc;
     extern crate std;
 
-    use alloc::vec::Vec;
+    use alloc::{vec, vec::Vec};
     use rand::thread_rng;
-    use spirv_std::glam::uvec3;
+    use spirv_std::glam::{uvec3, UVec3};
 
     use crate::{
-        plat::{node::Node, raw_plat::RawPlat},
+        plat::{
+            layer::{
+                error::LayerError,
+                layer::{Lr, Persistence},
+            },
+            node::Node,
+            raw_plat::RawPlat,
+        },
         quick_raw_plat,
+        test_utils::gen_rand_mtx,
     };
 
     #[test]
@@ -298,6 +549,344 @@ mod tests {
         assert_eq!(fork_count, 65536);
     }
 
+    #[test]
+    fn set_replace() {
+        quick_raw_plat!(plat, depth 6, len 100, len2 100, lenrest 10);
+
+        plat[0].set(uvec3(7, 20, 5), 1);
+        plat[0].set(uvec3(7, 20, 5), 2);
+
+        assert_eq!(plat.get_voxel(uvec3(7, 20, 5)).voxel_id, 2);
+        assert_eq!(plat[0].get_node(uvec3(7, 20, 5), 0, Some(1)).voxel_id, 0);
+
+        let mut seq = alloc::vec![];
+        plat[0].traverse(uvec3(0, 0, 0), 0..=6, |p| {
+            if p.level == 0 {
+                seq.push((*p.position, p.voxel_id));
+            }
+        });
+        assert_eq!(seq, [(uvec3(7, 20, 5), 2)]);
+
+        // Branch of old voxel_id is returned to pool
+        // Free-head, root, l5, fork, l4, l3
+        assert_eq!(plat[0].free(), 100 - 6);
+        assert_eq!(plat[0].free_l2(), 100 - 2);
+    }
+
+    #[test]
+    fn set_replace_same_voxel_id() {
+        quick_raw_plat!(plat, depth 6, len 100, len2 100, lenrest 10);
+
+        plat[0].set(uvec3(7, 20, 5), 1);
+        let free = plat[0].free();
+        plat[0].set(uvec3(7, 20, 5), 1);
+
+        assert_eq!(plat.get_voxel(uvec3(7, 20, 5)).voxel_id, 1);
+        assert_eq!(plat[0].free(), free);
+    }
+
+    #[test]
+    fn set_replace_random() {
+        quick_raw_plat!(plat, depth 6, len 200_000, len2 200_000, lenrest 10);
+
+        let first = gen_rand_mtx::<32>(30);
+        let second = gen_rand_mtx::<32>(30);
+
+        for mtx in [&first, &second] {
+            for x in 0..32 {
+                for y in 0..32 {
+                    for z in 0..32 {
+                        let voxel_id = mtx[x][y][z];
+                        if voxel_id != 0 {
+                            plat[0].set(uvec3(x as u32, y as u32, z as u32), voxel_id);
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut count = 0;
+        plat[0].traverse(uvec3(0, 0, 0), 0..=6, |p| {
+            if p.level == 0 {
+                count += 1;
+                let (x, y, z) = (
+                    p.position.x as usize,
+                    p.position.y as usize,
+                    p.position.z as usize,
+                );
+                let expected = if second[x][y][z] != 0 {
+                    second[x][y][z]
+                } else {
+                    first[x][y][z]
+                };
+                assert_eq!(p.voxel_id, expected);
+            }
+        });
+
+        let mut expected_count = 0;
+        for x in 0..32 {
+            for y in 0..32 {
+                for z in 0..32 {
+                    if first[x][y][z] != 0 || second[x][y][z] != 0 {
+                        expected_count += 1;
+                    }
+                }
+            }
+        }
+        assert_eq!(count, expected_count);
+    }
+
+    #[test]
+    fn set_deep() {
+        quick_raw_plat!(plat, depth 24, len 1_000, len2 1_000, lenrest 10);
+        let max = (1 << 24) - 1;
+
+        plat[0].set(uvec3(max, max, max), 1);
+        plat[0].set(uvec3(max, 0, 1 << 23), 2);
+        plat[0].set(uvec3(12_345, 6_789_012, 3), 3);
+
+        assert_eq!(plat.get_voxel(uvec3(max, max, max)).voxel_id, 1);
+        assert_eq!(plat.get_voxel(uvec3(max, 0, 1 << 23)).voxel_id, 2);
+        assert_eq!(plat.get_voxel(uvec3(12_345, 6_789_012, 3)).voxel_id, 3);
+        assert!(plat.get_voxel(uvec3(max - 1, max, max)).is_none());
+
+        plat[0].unset(uvec3(max, max, max));
+        assert!(plat.get_voxel(uvec3(max, max, max)).is_none());
+        assert!(plat
+            .validate(
+                &mut alloc::vec![0; 1_000],
+                &mut alloc::vec![false; 1_000],
+                None
+            )
+            .is_correct());
+    }
+
+    #[test]
+    fn try_set_out_of_bounds() {
+        quick_raw_plat!(plat, depth 6, len 100, len2 100, lenrest 10);
+
+        assert_eq!(
+            plat[0].try_set(uvec3(64, 0, 0), 1),
+            Err(LayerError::OutOfBounds)
+        );
+        assert_eq!(plat[0].try_set(uvec3(63, 0, 0), 1), Ok(()));
+    }
+
+    #[test]
+    fn try_set_out_of_nodes() {
+        // Needs 4 free nodes, but there are only 3
+        quick_raw_plat!(plat, depth 6, len 5, len2 100, lenrest 10);
+
+        assert_eq!(
+            plat[0].try_set(uvec3(7, 20, 5), 1),
+            Err(LayerError::OutOfNodes)
+        );
+        // Untouched
+        assert_eq!(plat[0].free(), 3);
+        assert!(plat[0][1].is_empty());
+    }
+
+    #[test]
+    fn try_set_out_of_l2_nodes() {
+        quick_raw_plat!(plat, depth 6, len 100, len2 2, lenrest 10);
+
+        assert_eq!(plat[0].try_set(uvec3(0, 0, 0), 1), Ok(()));
+        // Same NodeL2
+        assert_eq!(plat[0].try_set(uvec3(1, 0, 0), 1), Ok(()));
+        let free = plat[0].free();
+        assert_eq!(
+            plat[0].try_set(uvec3(4, 0, 0), 1),
+            Err(LayerError::OutOfL2Nodes)
+        );
+        assert_eq!(plat[0].free(), free);
+    }
+
+    #[test]
+    fn try_set_exact_fit() {
+        quick_raw_plat!(plat, depth 6, len 6, lenrest 10);
+
+        assert_eq!(plat[0].required_nodes(uvec3(7, 20, 5), 1), Ok((4, 1)));
+        assert_eq!(plat[0].try_set(uvec3(7, 20, 5), 1), Ok(()));
+        assert_eq!(plat[0].required_nodes(uvec3(7, 20, 5), 1), Ok((0, 0)));
+        assert_eq!(plat[0].free(), 0);
+    }
+
+    #[test]
+    fn try_set_frozen() {
+        quick_raw_plat!(plat, depth 6, len 100, len2 100, lenrest 10);
+
+        plat[0].set(uvec3(0, 0, 0), 1);
+        plat[0].freezed = true;
+
+        let free = plat[0].free();
+        assert_eq!(plat[0].try_set(uvec3(1, 0, 0), 2), Err(LayerError::Frozen));
+        assert_eq!(plat[0].try_set(uvec3(0, 0, 0), 0), Err(LayerError::Frozen));
+        assert_eq!(plat[0].free(), free);
+        assert_eq!(plat.get_voxel(uvec3(0, 0, 0)).voxel_id, 1);
+
+        plat[0].freezed = false;
+        assert_eq!(plat[0].try_set(uvec3(1, 0, 0), 2), Ok(()));
+    }
+
+    #[test]
+    fn try_set_read_only() {
+        quick_raw_plat!(plat, depth 6, len 1_000, len2 1_000, lenrest 1_000);
+
+        plat[Lr::BASE].set(uvec3(0, 0, 0), 1);
+        plat[Lr::CANVAS].set(uvec3(1, 0, 0), 2);
+        plat[Lr::BASE].persistence = Persistence::ReadOnly;
+
+        let free = plat[Lr::BASE].free();
+        let read_only = Err(LayerError::ReadOnly);
+
+        assert_eq!(plat[Lr::BASE].try_set(uvec3(1, 0, 0), 2), read_only);
+        assert_eq!(plat[Lr::BASE].try_set(uvec3(0, 0, 0), 0), read_only);
+        assert_eq!(
+            plat[Lr::BASE].try_set_many(&[(uvec3(1, 0, 0), 2)]),
+            read_only
+        );
+        assert_eq!(
+            plat[Lr::BASE].fill_box(uvec3(0, 0, 0), uvec3(8, 8, 8), 3),
+            read_only
+        );
+        assert_eq!(
+            plat[Lr::BASE].link_subtree(UVec3::ZERO, uvec3(32, 0, 0), 5),
+            read_only
+        );
+        assert_eq!(plat.set_at(Lr::BASE, (2, 0, 0).into(), 1), read_only);
+        assert_eq!(plat.merge_layers(Lr::CANVAS, Lr::BASE), read_only);
+        assert_eq!(plat.merge_layers(Lr::BASE, Lr::CANVAS), read_only);
+
+        assert_eq!(plat[Lr::BASE].free(), free);
+        assert_eq!(plat.get_voxel(uvec3(0, 0, 0)).voxel_id, 1);
+        assert_eq!(plat.get_voxel(uvec3(1, 0, 0)).layer_id, Lr::CANVAS);
+
+        plat[Lr::BASE].persistence = Persistence::Persistent;
+        assert_eq!(plat[Lr::BASE].try_set(uvec3(1, 0, 0), 2), Ok(()));
+    }
+
+    #[test]
+    #[should_panic]
+    fn set_read_only() {
+        quick_raw_plat!(plat, depth 6, len 100, len2 100, lenrest 10);
+
+        plat[0].persistence = Persistence::ReadOnly;
+        plat[0].set(uvec3(0, 0, 0), 1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn set_frozen() {
+        quick_raw_plat!(plat, depth 6, len 100, len2 100, lenrest 10);
+
+        plat[0].freezed = true;
+        plat[0].set(uvec3(0, 0, 0), 1);
+    }
+
+    #[test]
+    fn try_set_invalid_fork() {
+        quick_raw_plat!(plat, depth 6, len 100, len2 100, lenrest 10);
+
+        plat[0].set(uvec3(0, 0, 0), 1);
+
+        // Corrupt fork
+        for node in plat[0].nodes.iter_mut() {
+            if node.is_fork() {
+                node.flag = 0;
+            }
+        }
+
+        assert_eq!(
+            plat[0].try_set(uvec3(1, 0, 0), 2),
+            Err(LayerError::InvalidFork)
+        );
+    }
+
+    #[test]
+    fn try_set_many() {
+        use rand::Rng;
+
+        quick_raw_plat!(plat, depth 7, len 100_000, len2 100_000, lenrest 100_000);
+        let mut rng = thread_rng();
+
+        let mut voxels = Vec::new();
+        for _ in 0..3_000 {
+            let position = uvec3(
+                rng.gen_range(0..128),
+                rng.gen_range(0..40),
+                rng.gen_range(0..128),
+            );
+            voxels.push((position, rng.gen_range(0..6)));
+        }
+
+        // Same result as one by one
+        plat[Lr::BASE].try_set_many(&voxels).unwrap();
+        for &(position, voxel_id) in &voxels {
+            plat[Lr::CANVAS].set(position, voxel_id);
+        }
+
+        for &(position, _) in &voxels {
+            assert_eq!(
+                plat[Lr::BASE].get_node(position, 0, None).voxel_id,
+                plat[Lr::CANVAS].get_node(position, 0, None).voxel_id
+            );
+        }
+        assert_eq!(plat[Lr::BASE].free(), plat[Lr::CANVAS].free());
+        assert_eq!(plat[Lr::BASE].free_l2(), plat[Lr::CANVAS].free_l2());
+    }
+
+    #[test]
+    fn try_set_many_shared() {
+        quick_raw_plat!(plat, depth 7, len 10_000, len2 10_000, lenrest 10_000);
+
+        // Filled interiors are shared
+        for layer in [Lr::BASE, Lr::CANVAS] {
+            plat[layer]
+                .fill_box(UVec3::ZERO, UVec3::splat(32), 1)
+                .unwrap();
+            assert!(plat[layer].shared);
+        }
+
+        let voxels: Vec<_> = (0..32)
+            .map(|i| (uvec3(i, i % 7, 31 - i), 2 + i % 3))
+            .collect();
+
+        plat[Lr::BASE].try_set_many(&voxels).unwrap();
+        for &(position, voxel_id) in &voxels {
+            plat[Lr::CANVAS].set(position, voxel_id);
+        }
+
+        for &(position, voxel_id) in &voxels {
+            assert_eq!(
+                plat[Lr::BASE].get_node(position, 0, None).voxel_id as u32,
+                voxel_id
+            );
+        }
+        // Shared nodes are not written through
+        assert_eq!(plat[Lr::BASE].get_node(uvec3(1, 0, 0), 0, None).voxel_id, 1);
+        assert_eq!(plat[Lr::BASE].free(), plat[Lr::CANVAS].free());
+        assert_eq!(plat[Lr::BASE].free_l2(), plat[Lr::CANVAS].free_l2());
+        assert!(plat
+            .validate(&mut vec![0; 10_000], &mut vec![false; 10_000], None)
+            .is_correct());
+    }
+
+    #[test]
+    fn try_set_many_out_of_nodes() {
+        quick_raw_plat!(plat, depth 6, len 9, len2 100, lenrest 10);
+
+        let voxels = [(uvec3(1, 0, 0), 1), (uvec3(40, 40, 40), 2)];
+
+        assert_eq!(plat[0].try_set_many(&voxels), Err(LayerError::OutOfNodes));
+        assert_eq!(plat.get_voxel(uvec3(1, 0, 0)).voxel_id, 1);
+        assert!(plat.get_voxel(uvec3(40, 40, 40)).is_none());
+
+        assert_eq!(
+            plat[0].try_set_many(&[(uvec3(64, 0, 0), 1)]),
+            Err(LayerError::OutOfBounds)
+        );
+    }
+
     // TODO:
     // #[test]
     // fn set_single() {
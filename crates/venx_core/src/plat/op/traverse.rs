@@ -66,9 +66,7 @@ impl Layer<'_> {
             return;
         }
 
-        // Emulate stack with max depth 21 (max graph depth)
-        // (Depth is bounded to [NodeAddr], which is essentially single u64,
-        // each index is 3 bits. So it can be only 21 indexes in single u64)
+        // Emulate stack with max depth [MAX_DEPTH] (max graph depth)
         // Why? This code should compile to SpirV
         let mut stack: EStack<(
             /* 0 node_idx */
@@ -874,4 +872,31 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn traverse_deep() {
+        quick_raw_plat!(plat, depth 22, len 1_000, len2 1_000, lenrest 10);
+        let max = (1 << 22) - 1;
+        // Base
+        plat[Lr::BASE].set(uvec3(0, 0, 0), 1);
+        plat[Lr::BASE].set(uvec3(1 << 21, 5, 1 << 20), 2);
+        plat[Lr::BASE].set(uvec3(max, max, max), 3);
+
+        let mut seq = alloc::vec![];
+
+        plat[Lr::BASE].traverse(UVec3::ZERO, 0..=22, |p| {
+            if p.level == 0 {
+                seq.push((*p.position, p.voxel_id));
+            }
+        });
+
+        assert_eq!(
+            seq,
+            [
+                (uvec3(0, 0, 0), 1),
+                (uvec3(1 << 21, 5, 1 << 20), 2),
+                (uvec3(max, max, max), 3),
+            ]
+        );
+    }
 }
@@ -1,11 +1,575 @@
-impl Plat {
-    pub fn validate(&self) -> ValidationResult {
-        // TODO: Hashsum (Wrong one: this plat is corrupted or compromised)
-        todo!()
+use core::fmt::Display;
+
+use crate::plat::{
+    layer::layer::{Layer, Persistence},
+    raw_plat::{RawPlat, MAX_DEPTH, MAX_LAYERS},
+};
+
+/// Problem found by [RawPlat::validate]
+///
+/// Indices and levels are u32, like links in nodes, to keep [ValidationResult] small
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Diagnostic {
+    /// Layer depth is not the same as plat depth
+    DepthMismatch { depth: u32 },
+    /// Layer has no space for free-heads and root
+    TooSmall,
+    /// Root is free node or fork
+    InvalidRoot,
+    /// Free-list links out of bounds or is cyclic
+    BrokenFreeList { level_2: bool },
+    /// Node is linked in free-list, but not marked as free
+    FreeNodeInUse { idx: u32 },
+    /// Child of node on given level points out of bounds
+    OutOfBounds { parent_idx: u32, level: u32 },
+    /// Child of node on given level points to free node
+    LinkToFree { parent_idx: u32, level: u32 },
+    /// Node on fork level is not a fork, or fork is where it should not be
+    InvalidFork { idx: u32 },
+    /// Fork chain is cyclic or does not terminate with -3
+    BrokenForkChain { idx: u32 },
+    /// Node has several parents, but is not marked as shared, so writes would leak into other parents
+    UnmarkedShared { idx: u32 },
+    /// Node is linked from parents on different levels, like from its own subtree, which makes a cycle
+    WrongLevel { idx: u32 },
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Diagnostic::DepthMismatch { depth } => {
+                write!(f, "Layer depth {depth} is not the same as plat depth")
+            }
+            Diagnostic::TooSmall => write!(f, "Layer is too small"),
+            Diagnostic::InvalidRoot => write!(f, "Root is not a regular node"),
+            Diagnostic::BrokenFreeList { level_2 } => {
+                write!(f, "Free-list is broken (level_2: {level_2})")
+            }
+            Diagnostic::FreeNodeInUse { idx } => {
+                write!(f, "Node {idx} is in free-list, but is not free")
+            }
+            Diagnostic::OutOfBounds { parent_idx, level } => {
+                write!(
+                    f,
+                    "Child of node {parent_idx} on level {level} is out of bounds"
+                )
+            }
+            Diagnostic::LinkToFree { parent_idx, level } => {
+                write!(
+                    f,
+                    "Child of node {parent_idx} on level {level} is free node"
+                )
+            }
+            Diagnostic::InvalidFork { idx } => write!(f, "Node {idx} is not a valid fork"),
+            Diagnostic::BrokenForkChain { idx } => write!(f, "Fork chain of {idx} is broken"),
+            Diagnostic::UnmarkedShared { idx } => {
+                write!(
+                    f,
+                    "Node {idx} has several parents, but is not marked as shared"
+                )
+            }
+            Diagnostic::WrongLevel { idx } => {
+                write!(f, "Node {idx} is linked from parents on different levels")
+            }
+        }
     }
 }
 
-enum ValidationResult {
+#[cfg(feature = "std")]
+extern crate std;
+
+#[cfg(feature = "std")]
+impl std::error::Error for Diagnostic {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidationResult {
     Correct,
-    Error(bool),
+    /// First problem found on each layer. Index is id of layer
+    Error([Option<Diagnostic>; MAX_LAYERS]),
+    /// Structure is fine, but content is not the expected one: plat is corrupted or compromised
+    WrongHash {
+        expected: u64,
+        found: u64,
+    },
+}
+
+impl ValidationResult {
+    pub fn is_correct(&self) -> bool {
+        *self == ValidationResult::Correct
+    }
+}
+
+impl RawPlat<'_> {
+    /// Check invariants of all layers
+    ///
+    /// `marks` and `marks_l2` are helpers with at least the length of the largest `nodes` and `level_2` among layers.
+    /// Their content is overwritten. `marks` holds level of parent each node is linked from, so cycles are found as well
+    ///
+    /// If `expected_hash` is given, it is compared with [RawPlat::content_hash]
+    pub fn validate(
+        &self,
+        marks: &mut [u8],
+        marks_l2: &mut [bool],
+        expected_hash: Option<u64>,
+    ) -> ValidationResult {
+        let mut diagnostics = [None; MAX_LAYERS];
+
+        for (i, layer) in self.layers().iter().enumerate() {
+            if layer.depth != self.depth {
+                diagnostics[i] = Some(Diagnostic::DepthMismatch {
+                    depth: layer.depth as u32,
+                });
+                continue;
+            }
+
+            let marks = &mut marks[..layer.nodes.len()];
+            let marks_l2 = &mut marks_l2[..layer.level_2.len()];
+
+            if let Err(diagnostic) = layer.validate_structure(marks, marks_l2) {
+                diagnostics[i] = Some(diagnostic);
+            }
+        }
+
+        if diagnostics.iter().any(|d| d.is_some()) {
+            return ValidationResult::Error(diagnostics);
+        }
+
+        if let Some(expected) = expected_hash {
+            let found = self.content_hash();
+            if found != expected {
+                return ValidationResult::WrongHash { expected, found };
+            }
+        }
+
+        ValidationResult::Correct
+    }
+
+    /// FNV-1a hash of all nodes in all layers, except transient ones
+    pub fn content_hash(&self) -> u64 {
+        let mut hash = 0xcbf29ce484222325_u64;
+
+        let mut write = |word: u32| {
+            for byte in word.to_le_bytes() {
+                hash ^= byte as u64;
+                hash = hash.wrapping_mul(0x100000001b3);
+            }
+        };
+
+        for layer in self
+            .layers()
+            .iter()
+            .filter(|layer| layer.persistence != Persistence::Transient)
+        {
+            write(layer.nodes.len() as u32);
+            for node in layer.nodes.iter() {
+                write(node.flag as u32);
+                for child in node.children {
+                    write(child);
+                }
+            }
+
+            write(layer.level_2.len() as u32);
+            for node in layer.level_2.iter() {
+                write(node.packed_children[0]);
+                write(node.packed_children[1]);
+            }
+        }
+
+        hash
+    }
+}
+
+impl Layer<'_> {
+    /// Validate free-lists and everything reachable from root
+    ///
+    /// Does not index anything before bound checking, so it is safe to run on corrupted data
+    fn validate_structure(
+        &self,
+        marks: &mut [u8],
+        marks_l2: &mut [bool],
+    ) -> Result<(), Diagnostic> {
+        let len = self.nodes.len();
+        let len_l2 = self.level_2.len();
+
+        if len < 2 || len_l2 < 1 {
+            return Err(Diagnostic::TooSmall);
+        }
+
+        marks.fill(0);
+        marks_l2.fill(false);
+
+        // Free-list of nodes
+        {
+            let mut idx = self.nodes[0].children[0] as usize;
+            let mut steps = 0;
+
+            while idx != 0 {
+                if idx < 2 || idx >= len || steps > len {
+                    return Err(Diagnostic::BrokenFreeList { level_2: false });
+                }
+                if self.nodes[idx].flag != -1 {
+                    return Err(Diagnostic::FreeNodeInUse { idx: idx as u32 });
+                }
+                idx = self.nodes[idx].children[0] as usize;
+                steps += 1;
+            }
+        }
+
+        // Free-list of level_2. NodeL2 has no flag, so free nodes are marked
+        {
+            let mut idx = self.level_2[0].packed_children[0] as usize;
+
+            while idx != 0 {
+                if idx >= len_l2 || marks_l2[idx] {
+                    return Err(Diagnostic::BrokenFreeList { level_2: true });
+                }
+                marks_l2[idx] = true;
+                idx = self.level_2[idx].packed_children[0] as usize;
+            }
+        }
+
+        // Root is always on 1 idx;
+        if self[1].is_fork() || self[1].flag == -1 || self[1].is_shared() {
+            return Err(Diagnostic::InvalidRoot);
+        }
+
+        // Checks link to regular node and marks it with level of parent. Returns true if it has to be visited
+        let check = |marks: &mut [u8], parent_idx: usize, level: usize, child: u32| {
+            let child = child as usize;
+
+            if child == 0 {
+                return Ok(false);
+            }
+            let (parent_idx, level) = (parent_idx as u32, level as u32);
+
+            if child >= len {
+                return Err(Diagnostic::OutOfBounds { parent_idx, level });
+            }
+            if self[child].flag == -1 {
+                return Err(Diagnostic::LinkToFree { parent_idx, level });
+            }
+            if marks[child] != 0 {
+                // Node on the way back to the root is always linked from higher level
+                if marks[child] as u32 != level {
+                    return Err(Diagnostic::WrongLevel { idx: child as u32 });
+                }
+                if !self[child].is_shared() {
+                    return Err(Diagnostic::UnmarkedShared { idx: child as u32 });
+                }
+                return Ok(false);
+            }
+            marks[child] = level as u8;
+            Ok(true)
+        };
+
+        // (node_idx, level). Each level pushes at most 8 children
+        let mut stack = [(0usize, 0usize); 8 * MAX_DEPTH];
+        let mut stack_len = 1;
+        stack[0] = (1, self.depth);
+        marks[1] = self.depth as u8 + 1;
+
+        while stack_len > 0 {
+            stack_len -= 1;
+            let (idx, level) = stack[stack_len];
+
+            if level > 5 {
+                for &child in self[idx].children.iter() {
+                    if check(marks, idx, level, child)? {
+                        if self[child as usize].is_fork() {
+                            return Err(Diagnostic::InvalidFork { idx: child });
+                        }
+                        stack[stack_len] = (child as usize, level - 1);
+                        stack_len += 1;
+                    }
+                }
+                continue;
+            }
+
+            // Level 5. Children are forks
+            for (child_index, &fork_idx) in self[idx].children.iter().enumerate() {
+                if fork_idx == 0 {
+                    continue;
+                }
+
+                let first_fork_idx = fork_idx as usize;
+
+                // Chains are marked in parent. The first parent is not known here
+                if first_fork_idx < len
+                    && marks[first_fork_idx] != 0
+                    && self[idx].shared_children() & (1 << child_index) == 0
+                {
+                    return Err(Diagnostic::UnmarkedShared {
+                        idx: first_fork_idx as u32,
+                    });
+                }
+                let mut fork_idx = first_fork_idx;
+                let mut parent_idx = idx;
+                let mut steps = 0;
+
+                // Chains can be shared, so they are walked every time to find cycles,
+                // but entries are checked only once
+                loop {
+                    if fork_idx >= len {
+                        return Err(Diagnostic::OutOfBounds {
+                            parent_idx: parent_idx as u32,
+                            level: 5,
+                        });
+                    }
+                    if steps > len || !self[fork_idx].is_fork() {
+                        return Err(Diagnostic::BrokenForkChain {
+                            idx: first_fork_idx as u32,
+                        });
+                    }
+
+                    if marks[fork_idx] == 0 {
+                        marks[fork_idx] = 5;
+                        self.validate_fork(marks, marks_l2, fork_idx, &check)?;
+                    }
+
+                    let flag = self[fork_idx].flag;
+
+                    if flag == -3 {
+                        break;
+                    }
+
+                    parent_idx = fork_idx;
+                    fork_idx = flag as usize;
+                    steps += 1;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn validate_fork<F>(
+        &self,
+        marks: &mut [u8],
+        marks_l2: &mut [bool],
+        fork_idx: usize,
+        check: &F,
+    ) -> Result<(), Diagnostic>
+    where
+        F: Fn(&mut [u8], usize, usize, u32) -> Result<bool, Diagnostic>,
+    {
+        for slot in 0..4 {
+            // Entries are packed
+            if self[fork_idx][slot * 2] == 0 {
+                break;
+            }
+
+            let branch_idx = self[fork_idx][slot * 2 + 1];
+
+            if branch_idx == 0 {
+                return Err(Diagnostic::InvalidFork {
+                    idx: fork_idx as u32,
+                });
+            }
+
+            if !check(marks, fork_idx, 4, branch_idx)? {
+                continue;
+            }
+
+            if self[branch_idx as usize].is_fork() {
+                return Err(Diagnostic::InvalidFork { idx: branch_idx });
+            }
+
+            for &l3_idx in self[branch_idx as usize].children.iter() {
+                if !check(marks, branch_idx as usize, 4, l3_idx)? {
+                    continue;
+                }
+
+                if self[l3_idx as usize].is_fork() {
+                    return Err(Diagnostic::InvalidFork { idx: l3_idx });
+                }
+
+                for &l2_idx in self[l3_idx as usize].children.iter() {
+                    let l2_idx = l2_idx as usize;
+
+                    if l2_idx >= self.level_2.len() {
+                        return Err(Diagnostic::OutOfBounds {
+                            parent_idx: l3_idx,
+                            level: 3,
+                        });
+                    }
+                    if marks_l2[l2_idx] {
+                        return Err(Diagnostic::LinkToFree {
+                            parent_idx: l3_idx,
+                            level: 3,
+                        });
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+    extern crate std;
+
+    use alloc::vec;
+    use spirv_std::glam::uvec3;
+
+    use crate::{
+        plat::{
+            layer::layer::Lr,
+            node::Node,
+            raw_plat::{RawPlat, MAX_LAYERS},
+        },
+        quick_raw_plat,
+        test_utils::set_rand_plat,
+    };
+
+    use super::{Diagnostic, ValidationResult};
+
+    macro_rules! validate {
+        ($plat:ident) => {
+            $plat.validate(&mut vec![0; 100_000], &mut vec![false; 100_000], None)
+        };
+    }
+
+    fn first(result: ValidationResult) -> Option<Diagnostic> {
+        match result {
+            ValidationResult::Error(diagnostics) => diagnostics[0],
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn validate_correct() {
+        quick_raw_plat!(plat, depth 7, len 100_000, len2 100_000, lenrest 10);
+
+        assert!(validate!(plat).is_correct());
+
+        set_rand_plat::<32>(&mut plat, 50);
+        for i in 0..32 {
+            plat[0].unset(uvec3(i, i, i));
+        }
+
+        assert!(validate!(plat).is_correct());
+    }
+
+    #[test]
+    fn validate_hash() {
+        quick_raw_plat!(plat, depth 6, len 1_000, len2 1_000, lenrest 10);
+
+        plat[0].set(uvec3(1, 2, 3), 4);
+        let hash = plat.content_hash();
+
+        assert!(plat
+            .validate(&mut vec![0; 1_000], &mut vec![false; 1_000], Some(hash))
+            .is_correct());
+
+        // Transient layer is not saved, so it is ignored
+        plat[Lr::TMP].set(uvec3(1, 2, 4), 4);
+        assert_eq!(plat.content_hash(), hash);
+
+        plat[0].set(uvec3(1, 2, 4), 4);
+
+        assert_eq!(
+            plat.validate(&mut vec![0; 1_000], &mut vec![false; 1_000], Some(hash)),
+            ValidationResult::WrongHash {
+                expected: hash,
+                found: plat.content_hash()
+            }
+        );
+    }
+
+    #[test]
+    fn validate_link_to_free() {
+        quick_raw_plat!(plat, depth 6, len 1_000, len2 1_000, lenrest 10);
+
+        plat[0].set(uvec3(0, 0, 0), 1);
+        let idx = plat[0][1][0] as usize;
+        plat[0].deallocate_node::<Node>(idx);
+
+        assert_eq!(
+            first(validate!(plat)),
+            Some(Diagnostic::LinkToFree {
+                parent_idx: 1,
+                level: 6
+            })
+        );
+    }
+
+    #[test]
+    fn validate_broken_free_list() {
+        quick_raw_plat!(plat, depth 6, len 1_000, len2 1_000, lenrest 10);
+
+        // Cycle
+        plat[0][5][0] = 3;
+        assert_eq!(
+            first(validate!(plat)),
+            Some(Diagnostic::BrokenFreeList { level_2: false })
+        );
+    }
+
+    #[test]
+    fn validate_broken_fork_chain() {
+        quick_raw_plat!(plat, depth 6, len 1_000, len2 1_000, lenrest 10);
+
+        plat[0].set(uvec3(0, 0, 0), 1);
+        let fork_idx = plat[0][plat[0][1][0] as usize][0] as usize;
+
+        // Link to itself
+        plat[0][fork_idx].flag = fork_idx as i32;
+
+        assert_eq!(
+            first(validate!(plat)),
+            Some(Diagnostic::BrokenForkChain {
+                idx: fork_idx as u32
+            })
+        );
+    }
+
+    #[test]
+    fn validate_unmarked_shared() {
+        quick_raw_plat!(plat, depth 6, len 1_000, len2 1_000, lenrest 10);
+
+        plat[0].set(uvec3(0, 0, 0), 1);
+        let idx = plat[0][1][0] as usize;
+        plat[0][1][1] = idx as u32;
+
+        assert_eq!(
+            first(validate!(plat)),
+            Some(Diagnostic::UnmarkedShared { idx: idx as u32 })
+        );
+
+        plat[0][idx].set_shared(true);
+        assert!(validate!(plat).is_correct());
+    }
+
+    #[test]
+    fn validate_cycle_through_shared() {
+        quick_raw_plat!(plat, depth 7, len 1_000, len2 1_000, lenrest 10);
+
+        plat[0].set(uvec3(0, 0, 0), 1);
+        let idx = plat[0][1][0] as usize;
+
+        // Node on level 6 links to itself as to its child on level 5
+        plat[0][idx][1] = idx as u32;
+        plat[0][idx].set_shared(true);
+
+        assert_eq!(
+            first(validate!(plat)),
+            Some(Diagnostic::WrongLevel { idx: idx as u32 })
+        );
+    }
+
+    #[test]
+    fn validate_depth_mismatch() {
+        quick_raw_plat!(plat, depth 6, len 1_000, len2 1_000, lenrest 10);
+
+        plat[2].depth = 7;
+
+        let mut expected = [None; MAX_LAYERS];
+        expected[2] = Some(Diagnostic::DepthMismatch { depth: 7 });
+
+        assert_eq!(validate!(plat), ValidationResult::Error(expected));
+    }
 }
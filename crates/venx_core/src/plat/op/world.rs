@@ -0,0 +1,135 @@
+use spirv_std::glam::{IVec3, UVec3};
+
+use crate::plat::{layer::error::LayerError, op::get::GetNodeResult, raw_plat::RawPlat};
+
+impl RawPlat<'_> {
+    /// World position of octree's (0, 0, 0). Same as [RawPlat::position]
+    pub fn origin(&self) -> IVec3 {
+        let (x, y, z) = self.position;
+        IVec3::new(x, y, z)
+    }
+
+    /// Move plat in the world. Content of layers is not touched
+    pub fn set_origin(&mut self, origin: IVec3) {
+        self.position = (origin.x, origin.y, origin.z);
+    }
+
+    /// Map world position into octree space
+    ///
+    /// Fails with [LayerError::OutOfBounds] if position is not covered by plat
+    pub fn to_local(&self, world_position: IVec3) -> Result<UVec3, LayerError> {
+        let origin = self.origin();
+        let size = self.size() as i64;
+        let mut local = [0; 3];
+
+        for axis in 0..3 {
+            // i64, so it cannot overflow on far away positions
+            let coord = world_position[axis] as i64 - origin[axis] as i64;
+
+            if coord < 0 || coord >= size {
+                return Err(LayerError::OutOfBounds);
+            }
+            local[axis] = coord as u32;
+        }
+
+        Ok(UVec3::from_array(local))
+    }
+
+    /// Inverse of [RawPlat::to_local]
+    pub fn to_world(&self, local_position: UVec3) -> IVec3 {
+        self.origin() + local_position.as_ivec3()
+    }
+
+    /// Same as [RawPlat::get_node], but takes world position
+    pub fn get_node_at(
+        &self,
+        world_position: IVec3,
+        level: usize,
+    ) -> Result<GetNodeResult, LayerError> {
+        Ok(self.get_node(self.to_local(world_position)?, level))
+    }
+
+    /// Same as [RawPlat::get_voxel], but takes world position
+    pub fn get_voxel_at(&self, world_position: IVec3) -> Result<GetNodeResult, LayerError> {
+        self.get_node_at(world_position, 0)
+    }
+
+    /// Set voxel on given layer by world position. `voxel_id` 0 removes voxel
+    ///
+    /// See [crate::plat::layer::layer::Layer::try_set]
+    pub fn set_at(
+        &mut self,
+        layer: usize,
+        world_position: IVec3,
+        voxel_id: u32,
+    ) -> Result<(), LayerError> {
+        let position = self.to_local(world_position)?;
+        self[layer].try_set(position, voxel_id)
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+    extern crate std;
+
+    use spirv_std::glam::{ivec3, uvec3};
+
+    use crate::{
+        plat::{layer::error::LayerError, node::Node, raw_plat::RawPlat},
+        quick_raw_plat,
+    };
+
+    #[test]
+    fn to_local_round_trip() {
+        quick_raw_plat!(plat, depth 6, len 100, len2 100, lenrest 10);
+        plat.set_origin(ivec3(-32, -5, 100));
+
+        assert_eq!(plat.to_local(ivec3(-32, -5, 100)), Ok(uvec3(0, 0, 0)));
+        assert_eq!(plat.to_local(ivec3(31, 58, 163)), Ok(uvec3(63, 63, 63)));
+        assert_eq!(plat.to_local(ivec3(-1, 0, 120)), Ok(uvec3(31, 5, 20)));
+        assert_eq!(plat.to_world(uvec3(31, 5, 20)), ivec3(-1, 0, 120));
+
+        assert_eq!(
+            plat.to_local(ivec3(-33, 0, 100)),
+            Err(LayerError::OutOfBounds)
+        );
+        assert_eq!(
+            plat.to_local(ivec3(32, 0, 100)),
+            Err(LayerError::OutOfBounds)
+        );
+        assert_eq!(plat.to_local(ivec3(0, 0, 99)), Err(LayerError::OutOfBounds));
+        assert_eq!(
+            plat.to_local(ivec3(i32::MIN, i32::MAX, 0)),
+            Err(LayerError::OutOfBounds)
+        );
+    }
+
+    #[test]
+    fn set_get_negative() {
+        quick_raw_plat!(plat, depth 6, len 100, len2 100, lenrest 10);
+        plat.set_origin(ivec3(-32, -32, -32));
+
+        plat.set_at(0, ivec3(-1, -1, -1), 1).unwrap();
+        plat.set_at(3, ivec3(-32, 0, 31), 2).unwrap();
+
+        assert_eq!(plat.get_voxel_at(ivec3(-1, -1, -1)).unwrap().voxel_id, 1);
+        assert_eq!(plat.get_voxel_at(ivec3(-32, 0, 31)).unwrap().voxel_id, 2);
+        assert!(plat.get_voxel_at(ivec3(0, 0, 0)).unwrap().is_none());
+        // Same voxel in octree space
+        assert_eq!(plat.get_voxel(uvec3(31, 31, 31)).voxel_id, 1);
+
+        assert_eq!(
+            plat.set_at(0, ivec3(32, 0, 0), 1),
+            Err(LayerError::OutOfBounds)
+        );
+        assert_eq!(
+            plat.get_voxel_at(ivec3(0, -33, 0)),
+            Err(LayerError::OutOfBounds)
+        );
+
+        plat.set_at(0, ivec3(-1, -1, -1), 0).unwrap();
+        assert!(plat.get_voxel_at(ivec3(-1, -1, -1)).unwrap().is_none());
+    }
+}
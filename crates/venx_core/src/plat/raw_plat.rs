@@ -1,11 +1,28 @@
-use super::{layer::layer::Layer, node::Node, node_l2::NodeL2};
+use super::{
+    layer::layer::{Layer, Persistence},
+    node::Node,
+    node_l2::NodeL2,
+};
 use core::ops::{Index, IndexMut};
 
+/// Maximal depth of plat
+///
+/// Positions are [spirv_std::glam::UVec3], so world can be at most 2^31 voxels on each axis
+pub const MAX_DEPTH: usize = 31;
+
+/// Maximal amount of layers in single plat
+///
+/// Layers are stored inline, so plat stays usable without allocator (e.g. in shaders)
+pub const MAX_LAYERS: usize = 16;
+
+/// Names of standard layers, which are created by default. Their ids are [Layer::BASE], [Layer::TMP], etc.
+pub const STANDARD_LAYERS: [&str; 4] = ["base", "tmp", "schem", "canvas"];
+
 // TODO: rename to RawPlatMut and create LayerMut
 #[derive(PartialEq, Debug)]
 pub struct RawPlat<'a> {
+    /// World position of octree's (0, 0, 0), see [RawPlat::origin]. Placement in the scene is kept by host
     pub position: (i32, i32, i32),
-    pub rotation: (i32, i32, i32),
     // pub bcs: Vec<()>,
     // pub texs: Vec<()>,
     // pub sbc: SmallBlockCollection,
@@ -13,21 +30,27 @@ pub struct RawPlat<'a> {
     /// 2^depth represents maximum world size
     pub depth: usize,
 
-    /// Each layer is laying on top of layers behind
-    /// To provide cross-game exprience, layers specified
-    /// Quick tour of layers and its responsobilities:
+    /// Each layer is laying on top of layers behind, see [RawPlat::order]
+    /// To provide cross-game exprience, standard layers specified
+    /// Quick tour of standard layers and its responsobilities:
     /// 0 - Base layer: Used for simple terrain generation
-    /// 1 - Tmp layer: Quick layer for temprorary voxel generated by FWGen
-    /// 2 - Schematic: Used to place autopasted schematics, also used for AI buildings provided by FWGen
+    /// 1 - Tmp layer: Quick layer for temprorary voxel generated by FWGen. It is not saved, see [Persistence::Transient]
+    /// 2 - Schem: Used to place autopasted schematics, also used for AI buildings provided by FWGen
     /// 3 - Canvas: Each voxel you want to place as a player will go there
-    // pub base: Layer<'a>,
-    // pub tmp: Layer<'a>,
-    // pub schem: Layer<'a>,
-    // pub canvas: Layer<'a>,
-    pub layers: [Layer<'a>; 4],
+    ///
+    /// Only first [RawPlat::len] layers are used. Index of layer is its id
+    pub layers: [Layer<'a>; MAX_LAYERS],
+
+    /// Amount of used layers
+    pub len: usize,
+
+    /// Ids of layers sorted by priority, from bottom to top.
+    /// Voxels of layer on top are overriding voxels of layers behind
+    pub order: [usize; MAX_LAYERS],
 }
 
 impl<'a> RawPlat<'a> {
+    /// Create plat with 4 standard layers, see [STANDARD_LAYERS]
     pub fn new(
         depth: usize,
         _chunk_level: usize,
@@ -37,28 +60,78 @@ impl<'a> RawPlat<'a> {
         schem: (&'a mut [Node], &'a mut [NodeL2]),
         canvas: (&'a mut [Node], &'a mut [NodeL2]),
     ) -> Self {
+        let mut plat = Self::empty(depth);
+
+        for (nodes, level_2) in [base, tmp, schem, canvas] {
+            plat.push_layer(Layer::new(depth, nodes, level_2));
+        }
+        plat[Layer::TMP].persistence = Persistence::Transient;
+        plat
+    }
+
+    /// Create plat without layers
+    pub fn empty(depth: usize) -> Self {
         assert!(depth > 4);
+        assert!(depth <= MAX_DEPTH);
         RawPlat {
             position: (0, 0, 0),
-            rotation: (0, 0, 0),
             depth,
-            layers: [
-                Layer::new(depth, base.0, base.1),
-                Layer::new(depth, tmp.0, tmp.1),
-                Layer::new(depth, schem.0, schem.1),
-                Layer::new(depth, canvas.0, canvas.1),
-            ],
+            layers: core::array::from_fn(|_| Layer {
+                id: 0,
+                depth,
+                level_2: &mut [],
+                nodes: &mut [],
+                freezed: false,
+                shared: false,
+                persistence: Persistence::Persistent,
+            }),
+            len: 0,
+            order: [0; MAX_LAYERS],
         }
     }
-    /// Return named layers. Might be useful if you want to get all names in single array
-    /// TODO: Actually it needs to be moved in layer itself
-    pub fn layers(&'a self) -> [(&'a str, &'a Layer<'a>); 4] {
-        [
-            ("base", &self.layers[0]),
-            ("tmp", &self.layers[1]),
-            ("schem", &self.layers[2]),
-            ("canvas", &self.layers[3]),
-        ]
+
+    /// Add layer on top of all others. Returns its id
+    ///
+    /// Layer is taken as is, so it should be either initialized or already filled
+    pub fn push_layer(&mut self, mut layer: Layer<'a>) -> usize {
+        assert!(
+            self.len < MAX_LAYERS,
+            "Plat cannot have more than {MAX_LAYERS} layers"
+        );
+        assert_eq!(layer.depth, self.depth);
+
+        let id = self.len;
+        layer.id = id;
+        self.layers[id] = layer;
+        self.order[id] = id;
+        self.len += 1;
+        id
+    }
+
+    /// Used layers. Index of layer is its id
+    pub fn layers(&self) -> &[Layer<'a>] {
+        &self.layers[..self.len]
+    }
+
+    pub fn layers_mut(&mut self) -> &mut [Layer<'a>] {
+        &mut self.layers[..self.len]
+    }
+
+    /// Ids of layers from bottom to top
+    pub fn order(&self) -> &[usize] {
+        &self.order[..self.len]
+    }
+
+    /// Declare priority of layers. `order` is list of all layer ids from bottom to top
+    pub fn set_order(&mut self, order: &[usize]) {
+        assert_eq!(order.len(), self.len);
+
+        for (i, id) in order.iter().enumerate() {
+            assert!(*id < self.len, "There is no layer with id {id}");
+            assert!(!order[..i].contains(id), "Layer {id} is listed twice");
+        }
+
+        self.order[..self.len].copy_from_slice(order);
     }
 
     pub fn depth(&self) -> usize {
@@ -73,13 +146,13 @@ impl<'a> Index<usize> for RawPlat<'a> {
     type Output = Layer<'a>;
 
     fn index(&self, index: usize) -> &Self::Output {
-        &self.layers[index]
+        &self.layers()[index]
     }
 }
 
 impl<'a> IndexMut<usize> for RawPlat<'a> {
     fn index_mut(&mut self, index_mut: usize) -> &mut Self::Output {
-        &mut self.layers[index_mut]
+        &mut self.layers_mut()[index_mut]
     }
 }
 // #[deprecated = "No easy way to import in scope with shortcuts. Use [Layer] associated constants instead: [Lr::BASE]"]
@@ -95,13 +168,13 @@ impl<'a> Index<LayerIndex> for RawPlat<'a> {
     type Output = Layer<'a>;
 
     fn index(&self, index: LayerIndex) -> &Self::Output {
-        &self.layers[index as usize]
+        &self.layers()[index as usize]
     }
 }
 
 impl<'a> IndexMut<LayerIndex> for RawPlat<'a> {
     fn index_mut(&mut self, index_mut: LayerIndex) -> &mut Self::Output {
-        &mut self.layers[index_mut as usize]
+        &mut self.layers_mut()[index_mut as usize]
     }
 }
 /// Quickly create raw plat for testing
@@ -1,6 +1,9 @@
+use super::raw_plat::MAX_DEPTH;
+
 // TODO: Needs optimization. (Options not working)
+/// Fixed size stack, which can hold path from root of the deepest plat down to voxel
 pub struct EStack<T: Clone> {
-    stack: [T; 21],
+    stack: [T; MAX_DEPTH + 1],
     pointer: usize,
 }
 
@@ -40,6 +43,17 @@ impl<T: Clone> EStack<T> {
                 vars.clone(),
                 vars.clone(),
                 vars.clone(),
+                vars.clone(),
+                vars.clone(),
+                vars.clone(),
+                vars.clone(),
+                vars.clone(),
+                vars.clone(),
+                vars.clone(),
+                vars.clone(),
+                vars.clone(),
+                vars.clone(),
+                vars.clone(),
             ],
             pointer: 0,
         }
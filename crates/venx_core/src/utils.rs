@@ -30,7 +30,7 @@ pub mod test_utils {
             $plat[$layer].traverse(UVec3::ZERO, 0..=$plat.depth, $callback);
         };
         ($plat:ident, $callback:tt) => {
-            for layer_idx in 0..4 {
+            for layer_idx in 0..$plat.len {
                 $plat[layer_idx].traverse(spirv_std::glam::UVec3::ZERO, 0..=$plat.depth, $callback);
             }
         };
@@ -42,7 +42,7 @@ pub mod test_utils {
             $plat[$layer].traverse($position, $range, $callback);
         };
         ($plat:ident, rng $range:expr, pos $position:expr, $callback:tt) => {
-            for layer_idx in 0..4 {
+            for layer_idx in 0..$plat.len {
                 $plat[layer_idx].traverse($position, $range, $callback);
             }
         };
@@ -13,17 +13,30 @@ use venx_core::plat::{
 pub fn load_chunk(
     #[spirv(global_invocation_id)] id: UVec3,
     // TODO: Write macro to improve it
-    #[spirv(storage_buffer, descriptor_set = 0, binding = 0)] nodes: &mut [Node],
-    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] l2: &mut [NodeL2],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 0)] base_nodes: &mut [Node],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] base_l2: &mut [NodeL2],
 
-    #[spirv(storage_buffer, descriptor_set = 4, binding = 0)] _meta: &mut [usize],
+    #[spirv(storage_buffer, descriptor_set = 1, binding = 0)] tmp_nodes: &mut [Node],
+    #[spirv(storage_buffer, descriptor_set = 1, binding = 1)] tmp_l2: &mut [NodeL2],
+
+    #[spirv(storage_buffer, descriptor_set = 2, binding = 0)] schem_nodes: &mut [Node],
+    #[spirv(storage_buffer, descriptor_set = 2, binding = 1)] schem_l2: &mut [NodeL2],
+
+    #[spirv(storage_buffer, descriptor_set = 3, binding = 0)] canvas_nodes: &mut [Node],
+    #[spirv(storage_buffer, descriptor_set = 3, binding = 1)] canvas_l2: &mut [NodeL2],
+
+    // [depth, ids of standard layers from bottom to top]
+    #[spirv(storage_buffer, descriptor_set = 4, binding = 0)] meta: &[u32],
     #[spirv(storage_buffer, descriptor_set = 5, binding = 0)] chunks: &mut [Chunk],
     #[spirv(storage_buffer, descriptor_set = 5, binding = 1)]
     chunks_requests: &[ChunkLoadRequest],
 ) {
-    // let depth = meta[id.x as usize] as usize;
+    let depth = meta[0] as usize;
 
-    let layer: Layer = (nodes, l2, 12).into();
+    let base: Layer = (base_nodes, base_l2, depth).into();
+    let tmp: Layer = (tmp_nodes, tmp_l2, depth).into();
+    let schem: Layer = (schem_nodes, schem_l2, depth).into();
+    let canvas: Layer = (canvas_nodes, canvas_l2, depth).into();
 
     let chunk = &mut chunks[id.x as usize];
 
@@ -37,14 +50,17 @@ pub fn load_chunk(
         request.chunk_level as usize,
     );
 
-    // for i in 6..(32 * 32 * 32 + 6) {
-    //     let p = chunk.from_flatten(i);
-    //     let voxel = layer.get_node_gpu_no_enum(p + (chunk.position() * chunk.width()), lod_level);
-    //     chunk.set(p, voxel as u32);
-    //     //chunk.data[i as usize] = voxel as u32;
-    // }
-
-    layer.load_chunk_gpu(&mut chunks[id.x as usize]);
+    // Same as RawPlat::load_chunk_gpu. From bottom to top, so upper layers override lower ones
+    // Only standard layers are bound, plats with custom ones are not transferred
+    for i in 0..4 {
+        match meta[1 + i] as usize {
+            Layer::BASE => base.load_chunk_gpu(chunk),
+            Layer::TMP => tmp.load_chunk_gpu(chunk),
+            Layer::SCHEM => schem.load_chunk_gpu(chunk),
+            Layer::CANVAS => canvas.load_chunk_gpu(chunk),
+            _ => (),
+        }
+    }
 }
 // Per chunk basis
 // pub const MESH_SIZE: usize = 39_000;
@@ -37,7 +37,7 @@ impl BlockCollection {
         }
     }
 
-    pub(super) fn testing() -> Self {
+    pub(crate) fn testing() -> Self {
         let mut bc = BlockCollection::template("testing");
 
         bc.add_block(Block {
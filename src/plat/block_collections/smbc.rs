@@ -108,6 +108,11 @@ impl SMBC {
     pub fn id(&self, name: &str) -> Option<&usize> {
         self.name_id.get(name)
     }
+
+    /// Reflection of block with given id
+    pub fn block(&self, id: usize) -> Option<&BlockReflection> {
+        self.blocks.get(id)
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
@@ -188,6 +193,10 @@ mod tests {
 
         let id = smbc.id("Dirt").unwrap();
         assert_eq!(&smbc.blocks[*id].name, "Dirt");
+
+        let water = smbc.block(*smbc.id("Water").unwrap()).unwrap();
+        assert!(!water.is_solid);
+        assert!(smbc.block(smbc.alloc).is_none());
     }
 
     #[test]
@@ -0,0 +1,274 @@
+use glam::{uvec3, UVec3};
+
+/// Box of voxels copied out of a layer, see [super::interfaces::layer::LayerInterface::extract_region]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Clipboard {
+    size: UVec3,
+    voxels: Vec<u32>,
+    /// Layer and position box was copied from.
+    /// Lets [super::interfaces::layer::LayerInterface::paste] link subtrees instead of copying voxels
+    pub(crate) source: Option<(usize, UVec3)>,
+}
+
+impl Clipboard {
+    pub fn new(size: UVec3) -> Self {
+        Clipboard {
+            size,
+            voxels: vec![0; (size.x * size.y * size.z) as usize],
+            source: None,
+        }
+    }
+    pub fn size(&self) -> UVec3 {
+        self.size
+    }
+    pub fn set(&mut self, position: impl Into<UVec3>, block: u32) {
+        let idx = self.idx(position.into());
+        self.voxels[idx] = block;
+    }
+    pub fn get(&self, position: impl Into<UVec3>) -> u32 {
+        self.voxels[self.idx(position.into())]
+    }
+    /// Iterate over local coordinates and its block types
+    pub fn iter<F>(&self, mut callback: F)
+    where
+        F: FnMut(UVec3, u32),
+    {
+        for (idx, block) in self.voxels.iter().enumerate() {
+            callback(self.position(idx), *block);
+        }
+    }
+    /// Copy of clipboard with `transform` applied
+    pub fn transformed(&self, transform: PasteTransform) -> Self {
+        if transform.is_identity() {
+            return self.clone();
+        }
+
+        let mut clipboard = Clipboard::new(transform.size(self.size));
+        self.iter(|position, block| clipboard.set(transform.apply(position, self.size), block));
+        clipboard
+    }
+
+    fn idx(&self, position: UVec3) -> usize {
+        assert!(position.cmplt(self.size).all());
+        (position.x + self.size.x * (position.y + self.size.y * position.z)) as usize
+    }
+    fn position(&self, idx: usize) -> UVec3 {
+        let idx = idx as u32;
+        uvec3(
+            idx % self.size.x,
+            (idx / self.size.x) % self.size.y,
+            idx / (self.size.x * self.size.y),
+        )
+    }
+}
+
+/// Rotation and mirroring of [Clipboard] on paste
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PasteTransform {
+    /// Quarter turns around Y axis, counter-clockwise when looking from above
+    pub rotation: u32,
+    /// Mirror along X, Y and Z axes. Applied before rotation
+    pub mirror: [bool; 3],
+}
+
+impl PasteTransform {
+    pub const IDENTITY: Self = PasteTransform {
+        rotation: 0,
+        mirror: [false; 3],
+    };
+
+    pub fn is_identity(&self) -> bool {
+        self.rotation % 4 == 0 && self.mirror == [false; 3]
+    }
+    /// Size of box with given `size` after transformation
+    pub fn size(&self, size: UVec3) -> UVec3 {
+        if self.rotation % 2 == 1 {
+            uvec3(size.z, size.y, size.x)
+        } else {
+            size
+        }
+    }
+    /// Where local `position` in box of given `size` ends up after transformation
+    pub fn apply(&self, position: UVec3, size: UVec3) -> UVec3 {
+        let mut position = position;
+
+        for axis in 0..3 {
+            if self.mirror[axis] {
+                position[axis] = size[axis] - 1 - position[axis];
+            }
+        }
+
+        let mut size = size;
+
+        for _ in 0..self.rotation % 4 {
+            position = uvec3(position.z, position.y, size.x - 1 - position.x);
+            size = uvec3(size.z, size.y, size.x);
+        }
+        position
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{uvec3, UVec3};
+    use pollster::block_on;
+    use venx_core::plat::layer::{error::LayerError, layer::Lr};
+
+    use crate::plat::{
+        interfaces::layer::LayerInterface,
+        normal::cpu_plat::{
+            tests::{new_plat, set_voxels},
+            CpuPlat,
+        },
+    };
+
+    use super::{Clipboard, PasteTransform};
+
+    /// Plat with base layer of `size` x `size` ground and given voxels on it
+    fn ground(size: u32, voxels: &[(UVec3, u32)]) -> CpuPlat {
+        let mut plat = new_plat();
+
+        let mut ground = vec![];
+        for x in 0..size {
+            for z in 0..size {
+                ground.push((uvec3(x, 0, z), 1));
+            }
+        }
+        ground.extend_from_slice(voxels);
+        block_on(plat.set_voxel_many(Lr::BASE, &ground)).unwrap();
+
+        plat
+    }
+
+    fn paste(plat: &mut CpuPlat, clipboard: &Clipboard, offset: UVec3) {
+        block_on(plat.paste(Lr::BASE, clipboard, offset, PasteTransform::IDENTITY)).unwrap();
+    }
+
+    #[test]
+    fn transform() {
+        let mut clipboard = Clipboard::new(uvec3(3, 2, 1));
+        clipboard.set(uvec3(0, 0, 0), 1);
+        clipboard.set(uvec3(2, 1, 0), 2);
+
+        let rotated = clipboard.transformed(PasteTransform {
+            rotation: 1,
+            ..Default::default()
+        });
+        assert_eq!(rotated.size(), uvec3(1, 2, 3));
+        assert_eq!(rotated.get(uvec3(0, 0, 2)), 1);
+        assert_eq!(rotated.get(uvec3(0, 1, 0)), 2);
+
+        let mirrored = clipboard.transformed(PasteTransform {
+            mirror: [true, false, false],
+            ..Default::default()
+        });
+        assert_eq!(mirrored.get(uvec3(2, 0, 0)), 1);
+        assert_eq!(mirrored.get(uvec3(0, 1, 0)), 2);
+
+        // Full turn changes nothing
+        let turned = clipboard.transformed(PasteTransform {
+            rotation: 4,
+            ..Default::default()
+        });
+        assert_eq!(turned, clipboard);
+
+        let twice = rotated.transformed(PasteTransform {
+            rotation: 3,
+            ..Default::default()
+        });
+        assert_eq!(twice, clipboard);
+    }
+
+    #[test]
+    fn copy_paste_and_move() {
+        let mut plat = ground(32, &[(uvec3(1, 1, 2), 2), (uvec3(20, 5, 30), 3)]);
+
+        let clipboard = plat.extract_region(Lr::BASE, uvec3(0, 0, 0), uvec3(32, 32, 32));
+        assert_eq!(clipboard.get(uvec3(1, 1, 2)), 2);
+
+        // Aligned paste links subtrees
+        let (_, free_l2) = plat.free(Lr::BASE);
+        paste(&mut plat, &clipboard, uvec3(64, 32, 0));
+        assert_eq!(plat.free(Lr::BASE).1, free_l2);
+
+        assert_eq!(plat.get_voxel(uvec3(95, 32, 31)).unwrap().voxel_id, 1);
+        assert_eq!(plat.get_voxel(uvec3(65, 33, 2)).unwrap().voxel_id, 2);
+        assert_eq!(plat.get_voxel(uvec3(84, 37, 30)).unwrap().voxel_id, 3);
+        assert!(plat.get_voxel(uvec3(64, 33, 0)).is_none());
+
+        // Editing source does not change linked copy, nor pastes of old clipboard
+        set_voxels(&mut plat, Lr::BASE, &[(uvec3(1, 1, 2), 4)]);
+        assert_eq!(plat.get_voxel(uvec3(65, 33, 2)).unwrap().voxel_id, 2);
+
+        paste(&mut plat, &clipboard, uvec3(0, 64, 0));
+        assert_eq!(plat.get_voxel(uvec3(1, 65, 2)).unwrap().voxel_id, 2);
+
+        // Rotated and mirrored
+        block_on(plat.paste(
+            Lr::BASE,
+            &clipboard,
+            uvec3(64, 64, 64),
+            PasteTransform {
+                rotation: 1,
+                mirror: [false, true, false],
+            },
+        ))
+        .unwrap();
+        assert_eq!(plat.get_voxel(uvec3(66, 94, 94)).unwrap().voxel_id, 2);
+        assert_eq!(plat.get_voxel(uvec3(94, 90, 75)).unwrap().voxel_id, 3);
+        assert_eq!(plat.get_voxel(uvec3(64, 95, 64)).unwrap().voxel_id, 1);
+
+        // Not aligned
+        block_on(plat.move_region(Lr::BASE, uvec3(0, 0, 0), uvec3(32, 32, 32), uvec3(3, 1, 40)))
+            .unwrap();
+        assert!(plat.get_voxel(uvec3(1, 1, 2)).is_none());
+        assert!(plat.get_voxel(uvec3(0, 0, 0)).is_none());
+        assert_eq!(plat.get_voxel(uvec3(4, 2, 42)).unwrap().voxel_id, 4);
+        assert_eq!(plat.get_voxel(uvec3(34, 1, 71)).unwrap().voxel_id, 1);
+        assert_eq!(plat.get_voxel(uvec3(65, 33, 2)).unwrap().voxel_id, 2);
+        // Cleared source on shared layer is copied, not leaked
+        assert_eq!(plat.collect_garbage(Lr::BASE), Default::default());
+
+        assert_eq!(
+            block_on(plat.paste(
+                Lr::BASE,
+                &clipboard,
+                uvec3(100, 0, 0),
+                PasteTransform::IDENTITY
+            )),
+            Err(LayerError::OutOfBounds)
+        );
+    }
+
+    #[test]
+    fn paste_twice_into_filled() {
+        let mut plat = ground(64, &[(uvec3(1, 1, 2), 2), (uvec3(9, 3, 4), 3)]);
+
+        let clipboard = plat.extract_region(Lr::BASE, uvec3(0, 0, 0), uvec3(16, 8, 16));
+
+        // Not aligned, over the ground and previous paste
+        let mut free = None;
+        for _ in 0..3 {
+            paste(&mut plat, &clipboard, uvec3(5, 0, 7));
+
+            // Nothing is allocated by the same paste again
+            let now = plat.free(Lr::BASE);
+            if let Some(free) = free {
+                assert_eq!(free, now);
+            }
+            free = Some(now);
+        }
+        paste(&mut plat, &clipboard, uvec3(13, 1, 7));
+
+        assert_eq!(plat.get_voxel(uvec3(6, 1, 9)).unwrap().voxel_id, 2);
+        assert_eq!(plat.get_voxel(uvec3(14, 3, 11)).unwrap().voxel_id, 3);
+        assert_eq!(plat.get_voxel(uvec3(14, 2, 9)).unwrap().voxel_id, 2);
+        assert_eq!(plat.get_voxel(uvec3(22, 4, 11)).unwrap().voxel_id, 3);
+        assert_eq!(plat.get_voxel(uvec3(20, 1, 20)).unwrap().voxel_id, 1);
+        assert_eq!(plat.get_voxel(uvec3(40, 0, 40)).unwrap().voxel_id, 1);
+        assert!(plat.get_voxel(uvec3(6, 2, 9)).is_none());
+
+        // Replaced nodes were returned, not leaked
+        assert_eq!(plat.collect_garbage(Lr::BASE), Default::default());
+    }
+}
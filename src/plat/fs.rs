@@ -1,12 +1,19 @@
 use std::{fs::*, io::Write};
 
+use anyhow::bail;
 use glam::Quat;
 use log::info;
-use venx_core::plat::{node::Node, node_l2::NodeL2};
+use venx_core::plat::{
+    layer::layer::{Layer, Persistence},
+    node::Node,
+    node_l2::NodeL2,
+    raw_plat::{MAX_DEPTH, MAX_LAYERS},
+};
 
 use crate::plat::{
     loader::{external_buffer::FakeBuffer, VenxLoader},
     normal::cpu_plat::CpuPlat,
+    transform::PlatTransform,
     MetaSerDeser, Plat,
 };
 
@@ -14,6 +21,8 @@ use super::{loader::vertex_pool::VertexPool, VenxPlat};
 
 impl VenxPlat {
     /// Save plat to .cache directory
+    ///
+    /// Content of transient layers is not written, see [Persistence]
     pub fn save(&self, name: &str) -> anyhow::Result<()> {
         info!("Saving {name}.plat");
         let path = ".cache/".to_owned() + name;
@@ -28,20 +37,37 @@ impl VenxPlat {
         let mut file = File::create(format!("{}.plat/meta.ron", path))?;
 
         // TODO: make use of transfer.    XXXXXXXXXXXXXXXXXXXXX
-        let raw_plat = self.get_normal_unchecked().borrow_raw_plat();
-        //                                ^^^^^^^^^^^^^^^^^^^^^-- Will fail if plat lives on GPU
+        let cpu_plat = self.get_normal_unchecked();
+        //                  ^^^^^^^^^^^^^^^^^^^^^-- Will fail if plat lives on GPU
+        let raw_plat = cpu_plat.borrow_raw_plat();
+        let names = cpu_plat.borrow_names();
         let meta: String = ron::ser::to_string_pretty(
             &MetaSerDeser {
                 depth: raw_plat.depth,
-                position: (0., 0., 0.),
-                rotation: (0., 0., 0.),
+                transform: (self.transform.translation.into(), self.transform.euler()),
+                origin: raw_plat.position,
+                layers: names.clone(),
+                order: raw_plat.order().to_vec(),
+                freezed: raw_plat.layers().iter().map(|l| l.freezed).collect(),
+                shared: raw_plat.layers().iter().map(|l| l.shared).collect(),
+                persistence: raw_plat.layers().iter().map(|l| l.persistence).collect(),
+                capacity: raw_plat
+                    .layers()
+                    .iter()
+                    .map(|l| (l.nodes.len(), l.level_2.len()))
+                    .collect(),
+                hash: Some(raw_plat.content_hash()),
             },
             ron::ser::PrettyConfig::default(),
         )?;
         file.write_all(meta.as_bytes())?;
 
         // Create layers dirs
-        for (layer_name, layer) in raw_plat.layers() {
+        for (layer_name, layer) in names.iter().zip(raw_plat.layers()) {
+            if layer.persistence == Persistence::Transient {
+                continue;
+            }
+
             let layer_path = format!("{path}.plat/layers/{layer_name}");
 
             create_dir_all(&layer_path)?;
@@ -60,40 +86,227 @@ impl VenxPlat {
     }
 
     pub fn load(path: &str, vertex_pool: VertexPool) -> anyhow::Result<Self> {
+        Self::load_with(path, vertex_pool, false)
+    }
+
+    /// Same as [VenxPlat::load], but validates plat first and fails if it is corrupted
+    pub fn load_validated(path: &str, vertex_pool: VertexPool) -> anyhow::Result<Self> {
+        Self::load_with(path, vertex_pool, true)
+    }
+
+    fn load_with(path: &str, vertex_pool: VertexPool, validate: bool) -> anyhow::Result<Self> {
         info!("Loading {path}.plat");
         let path = ".cache/".to_owned() + path;
         let meta: MetaSerDeser = ron::from_str(&read_to_string(format!("{path}.plat/meta.ron"))?)?;
 
-        let mut components = [
-            (vec![], vec![]),
-            (vec![], vec![]),
-            (vec![], vec![]),
-            (vec![], vec![]),
-        ];
+        // Plat asserts all of it, so it is checked before anything is built
+        if !(5..=MAX_DEPTH).contains(&meta.depth) {
+            bail!("{path}.plat has invalid depth {}", meta.depth);
+        }
+        if meta.layers.len() > MAX_LAYERS {
+            bail!("{path}.plat has more than {MAX_LAYERS} layers");
+        }
+        if !meta.order.is_empty() {
+            let mut sorted = meta.order.clone();
+            sorted.sort();
+            if sorted != (0..meta.layers.len()).collect::<Vec<_>>() {
+                bail!("{path}.plat has invalid order of layers: {:?}", meta.order);
+            }
+        }
+        // Free-heads, root and at least one free node of each kind, indices are u32
+        if let Some(capacity) = meta.capacity.iter().find(|&&(upper_len, l2_len)| {
+            !(3..=u32::MAX as usize).contains(&upper_len)
+                || !(2..=u32::MAX as usize).contains(&l2_len)
+        }) {
+            bail!("{path}.plat has invalid capacity of layer: {capacity:?}");
+        }
+
+        let persistence = |i: usize| meta.persistence.get(i).copied().unwrap_or_default();
+        let mut components = vec![];
+
+        for (i, layer_name) in meta.layers.iter().enumerate() {
+            if persistence(i) == Persistence::Transient {
+                let (mut nodes, mut l2) = match meta.capacity.get(i) {
+                    Some(&(upper_len, l2_len)) => (
+                        vec![Node::default(); upper_len],
+                        vec![NodeL2::default(); l2_len],
+                    ),
+                    None => CpuPlat::blank_components(),
+                };
+                // Setup and drop
+                Layer::new(meta.depth, &mut nodes, &mut l2);
+
+                components.push((nodes, l2));
+                continue;
+            }
 
-        for (i, layer_name) in ["base", "tmp", "schem", "canvas"].iter().enumerate() {
             let l2_path = format!("{path}.plat/layers/{layer_name}/level_2");
             let nodes_path = format!("{path}.plat/layers/{layer_name}/nodes");
 
             let l2: Vec<NodeL2> = bitcode::decode(&read(l2_path)?)?;
             let nodes: Vec<Node> = bitcode::decode(&read(nodes_path)?)?;
 
-            components[i] = (nodes, l2);
+            components.push((nodes, l2));
+        }
+
+        let mut plat = CpuPlat::from_existing(meta.depth, 5, 5, meta.layers, components);
+
+        plat.with_raw_plat_mut(|raw_plat| {
+            raw_plat.position = meta.origin;
+            for (i, layer) in raw_plat.layers_mut().iter_mut().enumerate() {
+                layer.persistence = persistence(i);
+                if layer.persistence == Persistence::Transient {
+                    continue;
+                }
+                layer.freezed = meta.freezed.get(i).copied().unwrap_or_default();
+                layer.shared = meta.shared.get(i).copied().unwrap_or_default();
+            }
+        });
+
+        if !meta.order.is_empty() {
+            plat.with_raw_plat_mut(|raw_plat| raw_plat.set_order(&meta.order));
+        }
+
+        if validate {
+            let raw_plat = plat.borrow_raw_plat();
+            let upper_len = raw_plat
+                .layers()
+                .iter()
+                .map(|l| l.nodes.len())
+                .max()
+                .unwrap_or_default();
+            let l2_len = raw_plat
+                .layers()
+                .iter()
+                .map(|l| l.level_2.len())
+                .max()
+                .unwrap_or_default();
+
+            let result =
+                raw_plat.validate(&mut vec![0; upper_len], &mut vec![false; l2_len], meta.hash);
+
+            if !result.is_correct() {
+                bail!("{path}.plat is corrupted: {result:?}");
+            }
         }
 
-        // TODO: overcome bottleneck. Handle this without cloning
         Ok(VenxPlat {
-            plat: Plat::Cpu(CpuPlat::from_existing(
-                meta.depth,
-                5,
-                5,
-                components[0].clone(),
-                components[1].clone(),
-                components[2].clone(),
-                components[3].clone(),
-            )),
+            plat: Plat::Cpu(plat),
             loader: VenxLoader::new(([0., 0., 0.].into(), Quat::default(), 50), vertex_pool),
             smbcs: vec![],
+            transform: PlatTransform::from_euler(meta.transform.0, meta.transform.1),
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, remove_dir_all, write};
+
+    use glam::{uvec3, vec3};
+    use pollster::block_on;
+    use venx_core::plat::layer::{
+        error::LayerError,
+        layer::{Lr, Persistence},
+    };
+
+    use crate::plat::{
+        interfaces::layer::LayerInterface,
+        normal::cpu_plat::tests::{new_plat, set_voxels},
+        standard_layers,
+        tests::vertex_pool,
+        transform::PlatTransform,
+        MetaSerDeser, VenxPlat,
+    };
+
+    #[test]
+    fn load_invalid_meta() {
+        let name = "test_load_invalid_meta";
+        create_dir_all(format!(".cache/{name}.plat")).unwrap();
+
+        // Transient layers are not read from files
+        let load = |f: fn(&mut MetaSerDeser)| {
+            let mut meta = MetaSerDeser {
+                depth: 6,
+                layers: standard_layers(),
+                persistence: vec![Persistence::Transient; 4],
+                capacity: vec![(8, 2); 4],
+                ..Default::default()
+            };
+            f(&mut meta);
+            write(
+                format!(".cache/{name}.plat/meta.ron"),
+                ron::to_string(&meta).unwrap(),
+            )
+            .unwrap();
+
+            VenxPlat::load_validated(name, vertex_pool())
+        };
+
+        assert!(load(|_| ()).is_ok());
+        assert!(load(|meta| meta.depth = 40).is_err());
+        assert!(load(|meta| meta.depth = 3).is_err());
+        assert!(load(|meta| meta.order = vec![0, 1, 1, 3]).is_err());
+        assert!(load(|meta| meta.capacity[1] = (0, 0)).is_err());
+        assert!(load(|meta| meta.layers = vec!["base".into(); 20]).is_err());
+
+        remove_dir_all(format!(".cache/{name}.plat")).unwrap();
+    }
+
+    #[test]
+    fn load_transform() {
+        let name = "test_load_transform";
+        let meta_path = format!(".cache/{name}.plat/meta.ron");
+        create_dir_all(format!(".cache/{name}.plat")).unwrap();
+
+        // Older keys are not a part of transform anymore
+        write(
+            &meta_path,
+            "(depth: 6, position: (5., 0., 0.), rotation: (0., 1., 0.), \
+            layers: [\"base\"], persistence: [Transient], capacity: [(8, 2)])",
+        )
+        .unwrap();
+        let plat = VenxPlat::load(name, vertex_pool()).unwrap();
+        assert_eq!(plat.transform(), PlatTransform::IDENTITY);
+
+        write(
+            &meta_path,
+            "(depth: 6, transform: ((5., 0., 0.), (0., 0., 0.)), \
+            layers: [\"base\"], persistence: [Transient], capacity: [(8, 2)])",
+        )
+        .unwrap();
+        let plat = VenxPlat::load(name, vertex_pool()).unwrap();
+        assert_eq!(plat.transform().translation, vec3(5., 0., 0.));
+
+        remove_dir_all(format!(".cache/{name}.plat")).unwrap();
+    }
+
+    #[test]
+    fn persistence_policies() {
+        let mut plat = new_plat();
+
+        assert_eq!(plat.persistence(Lr::BASE), Persistence::Persistent);
+        assert_eq!(plat.persistence(Lr::TMP), Persistence::Transient);
+
+        let decals = plat.add_layer("decals").unwrap();
+        assert_eq!(plat.persistence(decals), Persistence::Persistent);
+
+        set_voxels(&mut plat, Lr::CANVAS, &[(uvec3(1, 1, 1), 1)]);
+        plat.set_persistence(Lr::BASE, Persistence::ReadOnly);
+
+        assert_eq!(
+            block_on(plat.set_voxel(Lr::BASE, uvec3(1, 1, 1), 1)),
+            Err(LayerError::ReadOnly)
+        );
+        assert_eq!(
+            block_on(plat.merge_layers(Lr::CANVAS, Lr::BASE)),
+            Err(LayerError::ReadOnly)
+        );
+        assert_eq!(plat.get_voxel(uvec3(1, 1, 1)).unwrap().layer_id, Lr::CANVAS);
+
+        // Policies survive growing
+        plat.reserve(Lr::TMP, (1_000, 1_000));
+        assert_eq!(plat.persistence(Lr::TMP), Persistence::Transient);
+        assert_eq!(plat.persistence(Lr::BASE), Persistence::ReadOnly);
+    }
+}
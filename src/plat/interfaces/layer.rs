@@ -1,12 +1,87 @@
 use std::collections::HashMap;
 
 use async_trait::async_trait;
-use glam::UVec3;
-use venx_core::plat::{node::Node, node_l2::NodeL2, op::get::GetNodeResult};
+use glam::{IVec3, UVec3};
+use venx_core::plat::{
+    layer::{error::LayerError, layer::Persistence},
+    node::Node,
+    node_l2::NodeL2,
+    op::{gc::GarbageReport, get::GetNodeResult},
+};
+
+use crate::plat::{
+    clipboard::{Clipboard, PasteTransform},
+    segment::Segment,
+};
+
+/// Amount of nodes freed by [LayerInterface::compress_dag]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CompressReport {
+    /// Nodes on upper levels (3..depth) including forks
+    pub freed_upper: usize,
+    pub freed_l2: usize,
+}
 
 #[async_trait]
 pub trait LayerInterface {
-    async fn compress_dag(&mut self, _layer: usize, _position: UVec3, _level: u32) {
+    /// Add new empty layer on top of all others. Returns its id
+    ///
+    /// Fails if name is already taken or plat has [venx_core::plat::raw_plat::MAX_LAYERS] layers
+    ///
+    /// Custom layers live on CPU only. Shaders bind standard layers ([venx_core::plat::raw_plat::STANDARD_LAYERS]),
+    /// so plat with custom layers cannot be transferred to GPU
+    fn add_layer(&mut self, _name: &str) -> anyhow::Result<usize> {
+        todo!()
+    }
+
+    /// Id of layer with given name
+    fn layer_id(&self, _name: &str) -> Option<usize> {
+        todo!()
+    }
+
+    /// Names of all layers. Index is id of layer
+    fn layer_names(&self) -> Vec<String> {
+        todo!()
+    }
+
+    /// Ids of layers from bottom to top. Voxels of layers on top are overriding layers behind
+    fn layer_order(&self) -> Vec<usize> {
+        todo!()
+    }
+
+    /// Declare priority of layers. `order` lists all layer ids from bottom to top
+    fn set_layer_order(&mut self, _order: &[usize]) -> anyhow::Result<()> {
+        todo!()
+    }
+
+    /// What happens to layer on save and load
+    fn persistence(&self, _layer: usize) -> Persistence {
+        todo!()
+    }
+
+    /// Change persistence policy of layer, see [Persistence]
+    fn set_persistence(&mut self, _layer: usize, _persistence: Persistence) {
+        todo!()
+    }
+
+    /// Deduplicate identical subtrees of the whole layer, turning it into DAG
+    ///
+    /// Layer stays writable, further edits copy shared nodes on write.
+    /// Garbage is collected as well and counted in report. Use [freeze] to cut freed nodes off
+    async fn compress_dag(&mut self, _layer: usize) -> CompressReport {
+        todo!()
+    }
+
+    /// Bake voxels of `src` layer into `dst` and clear `src`, e.g. commit player edits from Canvas into Base
+    ///
+    /// Priority of layers and explicit air are respected, see [venx_core::plat::raw_plat::RawPlat::merge_layers].
+    /// `dst` grows as needed. If it was frozen, it gets frozen again afterwards
+    async fn merge_layers(&mut self, _src: usize, _dst: usize) -> Result<(), LayerError> {
+        todo!()
+    }
+
+    /// Return all nodes, which are not reachable from root, to free nodes
+    fn collect_garbage(&mut self, _layer: usize) -> GarbageReport {
         todo!()
     }
 
@@ -24,8 +99,15 @@ pub trait LayerInterface {
         todo!()
     }
 
-    /// Make sure (upper, l2) have specified amount of nodes
-    fn reserve(&self, _layer: usize, _amounts: (u32, u32)) {
+    /// Check if layer is frozen, see [freeze]
+    fn is_frozen(&self, _layer: usize) -> bool {
+        todo!()
+    }
+
+    /// Make sure (upper, l2) have specified amount of free nodes
+    ///
+    /// Extends layer if needed, so it also unfreezes it
+    fn reserve(&mut self, _layer: usize, _amounts: (u32, u32)) {
         todo!()
     }
 
@@ -34,17 +116,89 @@ pub trait LayerInterface {
         todo!()
     }
 
-    async fn set_voxel(&mut self, _layer: usize, _position: UVec3, _voxel_id: usize) {
+    /// Set voxel on given layer. `voxel_id` 0 removes voxel
+    ///
+    /// Fails on frozen and read-only layers. On error layer is left untouched
+    async fn set_voxel(
+        &mut self,
+        _layer: usize,
+        _position: UVec3,
+        _voxel_id: usize,
+    ) -> Result<(), LayerError> {
+        todo!()
+    }
+
+    /// Same as [set_voxel], but takes world position, which is shifted by origin of plat
+    ///
+    /// Positions not covered by plat fail with [LayerError::OutOfBounds]
+    async fn set_voxel_at(
+        &mut self,
+        _layer: usize,
+        _position: IVec3,
+        _voxel_id: usize,
+    ) -> Result<(), LayerError> {
         todo!()
     }
 
-    async fn set_voxel_many(&mut self, _layer: usize, _voxels: (UVec3, usize)) {
+    /// Set many voxels on given layer at once. `voxel_id` 0 removes voxel
+    ///
+    /// Voxels are sorted in octree order, so consecutive ones share path from the root.
+    /// Layer grows as needed. Fails on frozen and read-only layers
+    async fn set_voxel_many(
+        &mut self,
+        _layer: usize,
+        _voxels: &[(UVec3, u32)],
+    ) -> Result<(), LayerError> {
         todo!()
     }
 
     /// Fastest way to set many voxels
-    async fn set_segment(&mut self, _segment: ()) {
-        todo!();
+    ///
+    /// Dense block of voxels is inserted with its (0, 0, 0) at `offset`, building nodes from bottom to top.
+    /// Empty voxels of segment leave layer untouched. Layer grows as needed. Fails on frozen and read-only layers
+    async fn set_segment(
+        &mut self,
+        _layer: usize,
+        _segment: &Segment,
+        _offset: UVec3,
+    ) -> Result<(), LayerError> {
+        todo!()
+    }
+
+    /// Copy voxels of given layer in box from `min` to `max` (exclusive)
+    ///
+    /// Box is clamped to the plat. Explicit air is copied as well
+    fn extract_region(&self, _layer: usize, _min: UVec3, _max: UVec3) -> Clipboard {
+        todo!()
+    }
+
+    /// Insert `clipboard` with its (0, 0, 0) at `offset`, after applying `transform` to it
+    ///
+    /// Empty voxels of clipboard leave layer untouched.
+    /// If clipboard was copied from the same layer and `offset` is aligned with its source,
+    /// empty destination subtrees are linked to source ones instead of copying voxel by voxel.
+    /// Layer grows as needed. Fails on frozen and read-only layers
+    async fn paste(
+        &mut self,
+        _layer: usize,
+        _clipboard: &Clipboard,
+        _offset: UVec3,
+        _transform: PasteTransform,
+    ) -> Result<(), LayerError> {
+        todo!()
+    }
+
+    /// Move voxels in box from `min` to `max` (exclusive) so that `min` ends up at `offset`
+    ///
+    /// Source box is cleared first, then pasted, see [paste]
+    async fn move_region(
+        &mut self,
+        _layer: usize,
+        _min: UVec3,
+        _max: UVec3,
+        _offset: UVec3,
+    ) -> Result<(), LayerError> {
+        todo!()
     }
 
     // TODO: Make async
@@ -52,6 +206,18 @@ pub trait LayerInterface {
         todo!()
     }
 
+    /// Same as [get_voxel], but only layers in `layers_mask` are taken into account
+    ///
+    /// Layer with id `i` is included if `i`-th bit is set
+    fn get_voxel_in(&self, _position: UVec3, _layers_mask: u32) -> Option<GetNodeResult> {
+        todo!()
+    }
+
+    /// Same as [get_voxel], but takes world position, see [set_voxel_at]
+    fn get_voxel_at(&self, _position: IVec3) -> Result<Option<GetNodeResult>, LayerError> {
+        todo!()
+    }
+
     #[deprecated = "Use [compress_dag]"]
     fn compress(
         &mut self,
@@ -1,7 +1,8 @@
 use anyhow::{bail, Result};
 use fastanvil::{complete, Chunk, Region};
-use glam::uvec3;
+use glam::{ivec3, uvec3};
 use log::info;
+use pollster::block_on;
 
 use std::{collections::HashMap, fs, ops::Range, path::PathBuf, usize};
 use venx_core::{
@@ -27,6 +28,8 @@ impl VenxPlat {
 
         let rgs = from_dir(PathBuf::from(dir_path), region_range)?;
         let mut plat = VenxPlat::new(s2l(max_width as u32), 5, 9);
+        // Regions are stored in world coordinates, so plat starts at the first one
+        plat.set_origin(ivec3(rr.0.start * 512, 0, rr.1.start * 512))?;
 
         let mut lookup_tables: Vec<HashMap<Node, usize>> = vec![HashMap::new(); 15];
         let mut lookup_table_l2: HashMap<NodeL2, usize> = HashMap::new();
@@ -85,14 +88,13 @@ impl VenxPlat {
 
                                             //let block_id = 1;
 
-                                            plat.set_voxel(
+                                            block_on(plat.set_voxel_at(
                                                 0,
-                                                uvec3(x as u32, y as u32, z as u32)
-                                                    + uvec3(ch_x as u32, 0, ch_z as u32) * 16
-                                                    + uvec3(rg_pos[0] as u32, 0, rg_pos[1] as u32)
-                                                        * 512,
+                                                ivec3(x as i32, y as i32, z as i32)
+                                                    + ivec3(ch_x as i32, 0, ch_z as i32) * 16
+                                                    + ivec3(rg_pos[0], 0, rg_pos[1]) * 512,
                                                 block_id,
-                                            );
+                                            ))?;
                                         }
                                     }
                                 }
@@ -103,7 +105,11 @@ impl VenxPlat {
                 if merge && ch_x % 8 == 0 {
                     plat.compress(
                         0,
-                        uvec3(rg_pos[0] as u32, 0, rg_pos[1] as u32),
+                        uvec3(
+                            (rg_pos[0] - rr.0.start) as u32,
+                            0,
+                            (rg_pos[1] - rr.1.start) as u32,
+                        ),
                         9,
                         &mut lookup_tables,
                         &mut lookup_table_l2,
@@ -143,6 +149,8 @@ impl VenxPlat {
 
         let rgs = from_dir(PathBuf::from(dir_path), region_range)?;
         let mut plat = VenxPlat::new(s2l(max_width as u32), 5, 9);
+        // Regions are stored in world coordinates, so plat starts at the first one
+        plat.set_origin(ivec3(rr.0.start * 512, 0, rr.1.start * 512))?;
 
         let mut lookup_tables: Vec<HashMap<Node, usize>> = vec![HashMap::new(); 15];
         let mut lookup_table_l2: HashMap<NodeL2, usize> = HashMap::new();
@@ -172,14 +180,13 @@ impl VenxPlat {
                                                 last_id += 1;
                                             }
 
-                                            plat.set_voxel(
+                                            block_on(plat.set_voxel_at(
                                                 0,
-                                                uvec3(x as u32, y as u32, z as u32)
-                                                    + uvec3(ch_x as u32, 0, ch_z as u32) * 16
-                                                    + uvec3(rg_pos[0] as u32, 0, rg_pos[1] as u32)
-                                                        * 512,
+                                                ivec3(x as i32, y as i32, z as i32)
+                                                    + ivec3(ch_x as i32, 0, ch_z as i32) * 16
+                                                    + ivec3(rg_pos[0], 0, rg_pos[1]) * 512,
                                                 block_id,
-                                            );
+                                            ))?;
                                         }
                                     }
                                 }
@@ -190,7 +197,11 @@ impl VenxPlat {
                 if ch_x % 8 == 0 {
                     plat.compress(
                         0,
-                        uvec3(rg_pos[0] as u32, 0, rg_pos[1] as u32),
+                        uvec3(
+                            (rg_pos[0] - rr.0.start) as u32,
+                            0,
+                            (rg_pos[1] - rr.1.start) as u32,
+                        ),
                         9,
                         &mut lookup_tables,
                         &mut lookup_table_l2,
@@ -245,7 +256,7 @@ fn from_dir(
                     let file = std::fs::File::open(path).unwrap();
 
                     let region = Region::from_stream(file).unwrap();
-                    out.push(([x - start.0, z - start.1], region));
+                    out.push(([x, z], region));
                 }
 
                 continue;
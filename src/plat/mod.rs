@@ -7,26 +7,38 @@ use std::{
 };
 
 use async_trait::async_trait;
-use glam::{uvec3, Quat, UVec3, Vec3, Vec4};
+use glam::{uvec3, IVec3, Quat, UVec3, Vec3, Vec4};
 use log::info;
 use serde::{Deserialize, Serialize};
 use venx_core::plat::{
     chunk::chunk::Chunk,
+    layer::{
+        error::LayerError,
+        layer::{Layer, Persistence},
+    },
     node::Node,
     node_l2::NodeL2,
-    op::get::GetNodeResult,
-    raw_plat::LayerIndex::{Base, Canvas, Schem, Tmp},
+    op::{gc::GarbageReport, get::GetNodeResult},
+    raw_plat::STANDARD_LAYERS,
 };
 
 use self::{
     block_collections::smbc::SMBC,
-    interfaces::{layer::LayerInterface, load::LoadInterface, PlatInterface},
+    clipboard::{Clipboard, PasteTransform},
+    interfaces::{
+        layer::{CompressReport, LayerInterface},
+        load::LoadInterface,
+        PlatInterface,
+    },
     loader::{vertex_pool::VertexPool, VenxLoader},
     normal::{cpu_plat::CpuPlat, mesh::Mesh},
+    segment::Segment,
+    transform::PlatTransform,
 };
 
-mod block_collections;
+pub mod block_collections;
 mod charts;
+pub mod clipboard;
 pub mod fs;
 
 pub mod interfaces;
@@ -35,6 +47,8 @@ pub mod loader;
 mod mca_converter;
 mod minecraft_blocks;
 pub mod normal;
+pub mod segment;
+pub mod transform;
 #[cfg(feature = "turbo")]
 pub mod turbo;
 
@@ -43,6 +57,7 @@ pub struct VenxPlat {
     plat: Plat,
     loader: VenxLoader,
     smbcs: Vec<SMBC>,
+    transform: PlatTransform,
 }
 
 pub(crate) enum Plat {
@@ -51,11 +66,80 @@ pub(crate) enum Plat {
     Gpu(GpuPlat),
 }
 
+/// Hit of [VenxPlat::raycast_world]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WorldRaycastHit {
+    /// Plat space voxel which was hit, see [LayerInterface::set_voxel_at]
+    pub voxel: IVec3,
+    /// World space position of hit voxel's (0, 0, 0) corner
+    pub position: Vec3,
+    /// World space normal of the face ray entered hit voxel through. Zero if ray starts inside of it
+    pub normal: Vec3,
+    pub voxel_id: u32,
+    pub layer_id: usize,
+    /// Distance from origin to the hit, in voxels
+    pub distance: f32,
+}
+
+/// Hit of [VenxPlat::sweep_aabb]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WorldSweepHit {
+    /// Plat space voxel box runs into, see [LayerInterface::set_voxel_at]
+    pub voxel: IVec3,
+    /// World space position of that voxel's (0, 0, 0) corner
+    pub position: Vec3,
+    /// World space normal of the face box touches
+    pub normal: Vec3,
+    pub voxel_id: u32,
+    /// Time of impact. Part of velocity box can move before contact, from 0 to 1
+    pub time: f32,
+}
+
 #[derive(Default, Serialize, Deserialize)]
 struct MetaSerDeser {
     depth: usize,
-    position: (f32, f32, f32),
-    rotation: (f32, f32, f32),
+    /// Translation of [PlatTransform] and its rotation as XYZ euler angles in radians
+    ///
+    /// Older saves have `position` and `rotation` instead, which were always zero. They are ignored
+    #[serde(default)]
+    transform: ((f32, f32, f32), (f32, f32, f32)),
+    /// See [VenxPlat::origin]
+    #[serde(default)]
+    origin: (i32, i32, i32),
+    /// Names of layers, index is id of layer
+    #[serde(default = "standard_layers")]
+    layers: Vec<String>,
+    /// Ids of layers from bottom to top, see [venx_core::plat::raw_plat::RawPlat::order]
+    #[serde(default)]
+    order: Vec<usize>,
+    /// Frozen state of each layer
+    #[serde(default)]
+    freezed: Vec<bool>,
+    /// If layer has shared nodes, see [venx_core::plat::layer::layer::Layer::shared]
+    #[serde(default)]
+    shared: Vec<bool>,
+    /// Persistence policy of each layer
+    #[serde(default = "standard_persistence")]
+    persistence: Vec<Persistence>,
+    /// Lengths of `nodes` and `level_2` of each layer. Transient layers are re-created with it
+    #[serde(default)]
+    capacity: Vec<(usize, usize)>,
+    /// See [venx_core::plat::raw_plat::RawPlat::content_hash]
+    #[serde(default)]
+    hash: Option<u64>,
+}
+
+fn standard_layers() -> Vec<String> {
+    STANDARD_LAYERS
+        .iter()
+        .map(|name| name.to_string())
+        .collect()
+}
+
+fn standard_persistence() -> Vec<Persistence> {
+    let mut persistence = vec![Persistence::Persistent; STANDARD_LAYERS.len()];
+    persistence[Layer::TMP] = Persistence::Transient;
+    persistence
 }
 
 impl VenxPlat {
@@ -88,6 +172,7 @@ impl VenxPlat {
             plat,
             loader: VenxLoader::new(([0., 0., 0.].into(), Quat::default(), 50), vertex_pool),
             smbcs: vec![],
+            transform: PlatTransform::IDENTITY,
         }
     }
 
@@ -98,10 +183,9 @@ impl VenxPlat {
                 let plat = cpu_plat.borrow_raw_plat();
                 let plat_depth = plat.depth;
 
-                assert_eq!(plat[Base].depth, plat_depth);
-                assert_eq!(plat[Tmp].depth, plat_depth);
-                assert_eq!(plat[Schem].depth, plat_depth);
-                assert_eq!(plat[Canvas].depth, plat_depth);
+                for layer in plat.layers() {
+                    assert_eq!(layer.depth, plat_depth);
+                }
 
                 plat_depth
             }
@@ -109,6 +193,214 @@ impl VenxPlat {
             Plat::Gpu(_) => todo!("You cant get depth from plat on gpu, yet"),
         }
     }
+    /// World position of plat's (0, 0, 0) voxel
+    pub fn origin(&self) -> anyhow::Result<IVec3> {
+        match &self.plat {
+            Plat::Cpu(cpu_plat) => Ok(cpu_plat.borrow_raw_plat().origin().to_array().into()),
+            #[cfg(feature = "turbo")]
+            Plat::Gpu(_) => anyhow::bail!("You cant get origin from plat on gpu, yet"),
+        }
+    }
+
+    /// Move plat in the world, so world positions in `origin..origin + size` are covered by plat
+    ///
+    /// Content is not touched, see [LayerInterface::set_voxel_at]
+    pub fn set_origin(&mut self, origin: IVec3) -> anyhow::Result<()> {
+        match &mut self.plat {
+            Plat::Cpu(cpu_plat) => {
+                cpu_plat.with_raw_plat_mut(|plat| plat.set_origin(origin.to_array().into()));
+                Ok(())
+            }
+            #[cfg(feature = "turbo")]
+            Plat::Gpu(_) => anyhow::bail!("You cant set origin of plat on gpu, yet"),
+        }
+    }
+    /// Placement of plat in the scene
+    pub fn transform(&self) -> PlatTransform {
+        self.transform
+    }
+
+    pub fn set_transform(&mut self, transform: PlatTransform) {
+        self.transform = transform;
+    }
+
+    /// World space -> plat space, see [PlatTransform]
+    pub fn world_to_plat(&self, position: Vec3) -> Vec3 {
+        self.transform.world_to_plat(position)
+    }
+
+    /// Plat space -> world space, see [PlatTransform]
+    pub fn plat_to_world(&self, position: Vec3) -> Vec3 {
+        self.transform.plat_to_world(position)
+    }
+
+    /// Get voxel containing given world space position
+    pub fn get_voxel_world(&self, position: Vec3) -> Result<Option<GetNodeResult>, LayerError> {
+        self.get_voxel_at(self.transform.world_to_voxel(position))
+    }
+
+    /// Cast world space ray through all layers, see [venx_core::plat::raw_plat::RawPlat::raycast]
+    ///
+    /// Hit is mapped back into world space, distance is the same in all spaces
+    pub fn raycast_world(
+        &self,
+        origin: Vec3,
+        direction: Vec3,
+        max_distance: f32,
+    ) -> anyhow::Result<Option<WorldRaycastHit>> {
+        let (origin, direction) = self.transform.ray_to_plat(origin, direction);
+        let plat_origin = self.origin()?;
+        let origin = origin - plat_origin.as_vec3();
+
+        let hit = match &self.plat {
+            Plat::Cpu(cpu_plat) => cpu_plat.borrow_raw_plat().raycast(
+                origin.to_array().into(),
+                direction.to_array().into(),
+                max_distance,
+                u32::MAX,
+            ),
+            #[cfg(feature = "turbo")]
+            Plat::Gpu(_) => anyhow::bail!("You cant raycast plat on gpu, yet"),
+        };
+
+        if hit.is_none() {
+            return Ok(None);
+        }
+
+        let (voxel, position) =
+            self.octree_to_world(hit.position.to_array().into(), plat_origin);
+
+        Ok(Some(WorldRaycastHit {
+            voxel,
+            position,
+            normal: self.transform.rotation * IVec3::from_array(hit.normal.to_array()).as_vec3(),
+            voxel_id: hit.voxel_id,
+            layer_id: hit.layer_id,
+            distance: hit.distance,
+        }))
+    }
+
+    /// Solid voxels intersecting world space box from `min` to `max`, see [venx_core::plat::raw_plat::RawPlat::collide_aabb]
+    ///
+    /// On rotated plat box is covered by axis aligned box in plat space, see [PlatTransform::aabb_to_plat],
+    /// so voxels next to it might be reported as well
+    ///
+    /// Returns plat space positions and voxel ids. Solidity comes from plat's [SMBC], see [VenxPlat::is_solid]
+    pub fn collide_aabb(&self, min: Vec3, max: Vec3) -> anyhow::Result<Vec<(IVec3, u32)>> {
+        let origin = self.origin()?;
+        let (min, max) = self.transform.aabb_to_plat(min, max);
+        let mut found = vec![];
+
+        match &self.plat {
+            Plat::Cpu(cpu_plat) => cpu_plat.borrow_raw_plat().collide_aabb(
+                (min - origin.as_vec3()).to_array().into(),
+                (max - origin.as_vec3()).to_array().into(),
+                u32::MAX,
+                |voxel_id| self.is_solid(voxel_id),
+                |position, voxel_id| {
+                    found.push((
+                        IVec3::from_array(position.as_ivec3().to_array()) + origin,
+                        voxel_id,
+                    ))
+                },
+            ),
+            #[cfg(feature = "turbo")]
+            Plat::Gpu(_) => anyhow::bail!("You cant collide with plat on gpu, yet"),
+        }
+
+        Ok(found)
+    }
+
+    /// Move world space box from `min` to `max` by world space `velocity`, see [venx_core::plat::raw_plat::RawPlat::sweep_aabb]
+    ///
+    /// Box is moved in plat space. On rotated plat it is covered by axis aligned box there, see [PlatTransform::aabb_to_plat].
+    /// Hit is mapped back into world space
+    pub fn sweep_aabb(
+        &self,
+        min: Vec3,
+        max: Vec3,
+        velocity: Vec3,
+    ) -> anyhow::Result<Option<WorldSweepHit>> {
+        let plat_origin = self.origin()?;
+        let origin = plat_origin.as_vec3();
+        let (min, max) = self.transform.aabb_to_plat(min, max);
+        let velocity = self.transform.rotation.inverse() * velocity;
+
+        let hit = match &self.plat {
+            Plat::Cpu(cpu_plat) => cpu_plat.borrow_raw_plat().sweep_aabb(
+                (min - origin).to_array().into(),
+                (max - origin).to_array().into(),
+                velocity.to_array().into(),
+                u32::MAX,
+                |voxel_id| self.is_solid(voxel_id),
+            ),
+            #[cfg(feature = "turbo")]
+            Plat::Gpu(_) => anyhow::bail!("You cant collide with plat on gpu, yet"),
+        };
+
+        if hit.is_none() {
+            return Ok(None);
+        }
+
+        let (voxel, position) =
+            self.octree_to_world(hit.position.to_array().into(), plat_origin);
+
+        Ok(Some(WorldSweepHit {
+            voxel,
+            position,
+            normal: self.transform.rotation * IVec3::from_array(hit.normal.to_array()).as_vec3(),
+            voxel_id: hit.voxel_id,
+            time: hit.time,
+        }))
+    }
+
+    /// Plat space voxel and world space position of its (0, 0, 0) corner for voxel in octree space
+    fn octree_to_world(&self, position: UVec3, origin: IVec3) -> (IVec3, Vec3) {
+        let voxel = position.as_ivec3() + origin;
+
+        (voxel, self.transform.plat_to_world(voxel.as_vec3()))
+    }
+
+    /// Block collection, which tells what voxel ids of this plat stand for
+    pub fn smbc(&self) -> Option<&SMBC> {
+        self.smbcs.first()
+    }
+
+    /// Use given [SMBC] for voxel ids of this plat. Collisions take solidity from it, see [VenxPlat::is_solid]
+    pub fn set_smbc(&mut self, smbc: SMBC) {
+        self.smbcs = vec![smbc];
+    }
+
+    /// If voxel blocks movement, see [block_collections::smbc::BlockReflection::is_solid]
+    ///
+    /// Voxels which have no reflection in plat's [SMBC] are solid, so without [VenxPlat::set_smbc] everything is
+    fn is_solid(&self, voxel_id: u32) -> bool {
+        self.smbcs
+            .first()
+            .and_then(|smbc| smbc.block(voxel_id as usize))
+            .map_or(true, |block| block.is_solid)
+    }
+
+    /// Compute mesh of chunk and place it in world space
+    ///
+    /// Mesh is shifted by [VenxPlat::origin] and [PlatTransform] is applied to it
+    pub fn compute_world_mesh_from_chunk(&self, chunk: &Chunk) -> anyhow::Result<Mesh> {
+        let mut mesh = self.compute_mesh_from_chunk(chunk);
+        let transform = self.transform_with_origin()?;
+
+        transform.apply_to_mesh(&mut mesh);
+        Ok(mesh)
+    }
+
+    /// Transform from octree space (used by meshes) into world space
+    fn transform_with_origin(&self) -> anyhow::Result<PlatTransform> {
+        let origin = self.origin()?.as_vec3();
+
+        Ok(PlatTransform::new(
+            self.transform.plat_to_world(origin),
+            self.transform.rotation,
+        ))
+    }
     #[cfg(feature = "turbo")]
     /// Depth, chunk_level, segment_level
     pub async fn new_turbo(depth: usize, chunk_level: usize, segment_level: usize) -> VenxPlat {
@@ -116,21 +408,25 @@ impl VenxPlat {
             plat: Plat::Gpu(GpuPlat::new_plat(depth, chunk_level, segment_level).await),
             loader: todo!(),
             smbcs: todo!(),
+            transform: PlatTransform::IDENTITY,
         }
     }
     #[cfg(feature = "turbo")]
     /// Will return Normal version if there is not enough free memory on GPU.
     ///
     /// So it does not guarantee transfer on GPU
-    pub async fn transfer_to_gpu(self) -> Self {
-        VenxPlat {
+    ///
+    /// Fails if plat has custom layers, shaders bind only standard ones, see [LayerInterface::add_layer]
+    pub async fn transfer_to_gpu(self) -> anyhow::Result<Self> {
+        Ok(VenxPlat {
             plat: match self.plat {
-                Plat::Cpu(cpu_plat) => Plat::Gpu(cpu_plat.transfer_to_gpu().await),
+                Plat::Cpu(cpu_plat) => Plat::Gpu(cpu_plat.transfer_to_gpu().await?),
                 Plat::Gpu(_) => panic!("It is dumb idea to transfer data from gpu to gpu"),
             },
             loader: self.loader,
             smbcs: self.smbcs,
-        }
+            transform: self.transform,
+        })
     }
     #[cfg(feature = "turbo")]
     pub async fn transfer_from_gpu(self) -> Self {
@@ -141,6 +437,7 @@ impl VenxPlat {
             },
             loader: self.loader,
             smbcs: self.smbcs,
+            transform: self.transform,
         }
     }
 
@@ -156,7 +453,7 @@ impl VenxPlat {
         _lod: Option<usize>,
     )
     //            Vertex          Color         Normal
-     -> Vec<(Vec<[f32; 3]>, Vec<[f32; 4]>, Vec<[f32; 3]>)> {
+     -> anyhow::Result<Vec<(Vec<[f32; 3]>, Vec<[f32; 4]>, Vec<[f32; 3]>)>> {
         let chunks_amount = (chunk_range_x.end - chunk_range_x.start)
             * (chunk_range_z.end - chunk_range_z.start)
             * (chunk_range_y.end - chunk_range_y.start);
@@ -212,7 +509,7 @@ impl VenxPlat {
 
                     let chunk = plat.load_chunk(uvec3(x, y, z), 0, 5);
 
-                    let vx_mesh = plat.compute_mesh_from_chunk(&chunk);
+                    let vx_mesh = plat.compute_world_mesh_from_chunk(&chunk)?;
 
                     let mesh_idx = counter / capacity;
 
@@ -247,11 +544,18 @@ impl VenxPlat {
             }
         }
 
-        meshes
+        Ok(meshes)
     }
 }
 
 macro_rules! plat_helper {
+    ($self:ident, method $method:ident) => {
+        match &$self.plat {
+            Plat::Cpu(plat) => plat.$method(),
+            #[cfg(feature = "turbo")]
+            Plat::Gpu(plat) => plat.$method(),
+        }
+    };
     ($self:ident, method $method:ident, $($y:ident),+) => {
         match &$self.plat {
             Plat::Cpu(plat) => plat.$method($($y),+),
@@ -304,8 +608,103 @@ impl LoadInterface for VenxPlat {
 }
 #[async_trait]
 impl LayerInterface for VenxPlat {
-    async fn set_voxel(&mut self, layer: usize, position: glam::UVec3, ty: usize) {
-        plat_helper!(async, mut self, method set_voxel,  layer, position, ty);
+    fn add_layer(&mut self, name: &str) -> anyhow::Result<usize> {
+        plat_helper!(mut self, method add_layer,  name)
+    }
+
+    fn layer_id(&self, name: &str) -> Option<usize> {
+        plat_helper!(self, method layer_id,  name)
+    }
+
+    fn layer_names(&self) -> Vec<String> {
+        plat_helper!(self, method layer_names)
+    }
+
+    fn layer_order(&self) -> Vec<usize> {
+        plat_helper!(self, method layer_order)
+    }
+
+    fn set_layer_order(&mut self, order: &[usize]) -> anyhow::Result<()> {
+        plat_helper!(mut self, method set_layer_order,  order)
+    }
+
+    fn persistence(&self, layer: usize) -> Persistence {
+        plat_helper!(self, method persistence,  layer)
+    }
+
+    fn set_persistence(&mut self, layer: usize, persistence: Persistence) {
+        plat_helper!(mut self, method set_persistence,  layer, persistence)
+    }
+
+    async fn set_voxel(
+        &mut self,
+        layer: usize,
+        position: glam::UVec3,
+        ty: usize,
+    ) -> Result<(), LayerError> {
+        plat_helper!(async, mut self, method set_voxel,  layer, position, ty)
+    }
+
+    async fn set_voxel_at(
+        &mut self,
+        layer: usize,
+        position: IVec3,
+        ty: usize,
+    ) -> Result<(), LayerError> {
+        plat_helper!(async, mut self, method set_voxel_at,  layer, position, ty)
+    }
+
+    async fn set_voxel_many(
+        &mut self,
+        layer: usize,
+        voxels: &[(UVec3, u32)],
+    ) -> Result<(), LayerError> {
+        plat_helper!(async, mut self, method set_voxel_many,  layer, voxels)
+    }
+
+    async fn set_segment(
+        &mut self,
+        layer: usize,
+        segment: &Segment,
+        offset: UVec3,
+    ) -> Result<(), LayerError> {
+        plat_helper!(async, mut self, method set_segment,  layer, segment, offset)
+    }
+
+    fn extract_region(&self, layer: usize, min: UVec3, max: UVec3) -> Clipboard {
+        plat_helper!(self, method extract_region,  layer, min, max)
+    }
+
+    async fn paste(
+        &mut self,
+        layer: usize,
+        clipboard: &Clipboard,
+        offset: UVec3,
+        transform: PasteTransform,
+    ) -> Result<(), LayerError> {
+        plat_helper!(async, mut self, method paste,  layer, clipboard, offset, transform)
+    }
+
+    async fn move_region(
+        &mut self,
+        layer: usize,
+        min: UVec3,
+        max: UVec3,
+        offset: UVec3,
+    ) -> Result<(), LayerError> {
+        plat_helper!(async, mut self, method move_region,  layer, min, max, offset)
+    }
+
+    async fn compress_dag(&mut self, layer: usize) -> CompressReport {
+        plat_helper!(async, mut self, method compress_dag,  layer)
+    }
+
+    async fn merge_layers(&mut self, src: usize, dst: usize) -> Result<(), LayerError> {
+        plat_helper!(async, mut self, method merge_layers,  src, dst)
+    }
+
+    fn collect_garbage(&mut self, layer: usize) -> GarbageReport {
+        plat_helper!(mut self, method collect_garbage,  layer)
     }
 
     fn free(&self, layer: usize) -> (u32, u32) {
@@ -319,6 +718,14 @@ impl LayerInterface for VenxPlat {
     fn freeze(&mut self, layer: usize) {
         plat_helper!(mut self, method freeze,  layer);
     }
+
+    fn is_frozen(&self, layer: usize) -> bool {
+        plat_helper!(self, method is_frozen,  layer)
+    }
+
+    fn reserve(&mut self, layer: usize, amounts: (u32, u32)) {
+        plat_helper!(mut self, method reserve,  layer, amounts);
+    }
     fn compress(
         &mut self,
         layer: usize,
@@ -333,13 +740,123 @@ impl LayerInterface for VenxPlat {
     fn get_voxel(&self, position: glam::UVec3) -> Option<GetNodeResult> {
         plat_helper!(self, method get_voxel,  position)
     }
+
+    fn get_voxel_in(&self, position: UVec3, layers_mask: u32) -> Option<GetNodeResult> {
+        plat_helper!(self, method get_voxel_in,  position, layers_mask)
+    }
+
+    fn get_voxel_at(&self, position: IVec3) -> Result<Option<GetNodeResult>, LayerError> {
+        plat_helper!(self, method get_voxel_at,  position)
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
+    use glam::{ivec3, uvec3, vec3, Quat};
     use pollster::block_on;
+    use venx_core::plat::layer::layer::Layer;
+
+    use super::{
+        block_collections::{bc::BlockCollection, smbc::SMBC},
+        interfaces::layer::LayerInterface,
+        loader::{external_buffer::FakeBuffer, vertex_pool::VertexPool},
+        transform::PlatTransform,
+        VenxPlat,
+    };
 
-    use super::{interfaces::layer::LayerInterface, VenxPlat};
+    pub(crate) fn vertex_pool() -> VertexPool {
+        VertexPool::new(6, 1, vec![1], Box::new(FakeBuffer), Box::new(FakeBuffer))
+    }
+
+    pub(crate) fn new_plat(depth: usize) -> VenxPlat {
+        VenxPlat::new(depth, 5, 5, vertex_pool())
+    }
+
+    #[test]
+    fn hits_in_world_space() {
+        let mut plat = new_plat(6);
+        plat.set_origin(ivec3(-10, 0, 0)).unwrap();
+        // Quarter turn around Y: plat +X points to world -Z
+        plat.set_transform(PlatTransform::new(
+            vec3(100., 0., 0.),
+            Quat::from_rotation_y(FRAC_PI_2),
+        ));
+        block_on(plat.set_voxel_at(Layer::BASE, ivec3(3, 0, 0), 1)).unwrap();
+
+        let hit = plat
+            .raycast_world(vec3(100.5, 0.5, 0.), vec3(0., 0., -1.), 20.)
+            .unwrap()
+            .unwrap();
+        assert_eq!(hit.voxel, ivec3(3, 0, 0));
+        assert!(hit.position.abs_diff_eq(vec3(100., 0., -3.), 1e-4));
+        assert!(hit.normal.abs_diff_eq(vec3(0., 0., 1.), 1e-4));
+        assert!((hit.distance - 3.).abs() < 1e-4);
+
+        // Box and velocity are given in world space as well
+        let hit = plat
+            .sweep_aabb(
+                vec3(100.2, 0.2, -0.8),
+                vec3(100.8, 0.8, -0.2),
+                vec3(0., 0., -5.),
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(hit.voxel, ivec3(3, 0, 0));
+        assert!(hit.position.abs_diff_eq(vec3(100., 0., -3.), 1e-4));
+        assert!(hit.normal.abs_diff_eq(vec3(0., 0., 1.), 1e-4));
+        assert!((hit.time - 0.44).abs() < 1e-4);
+
+        // Moving along plat's X does not hit anything
+        assert!(plat
+            .sweep_aabb(
+                vec3(100.2, 0.2, -0.8),
+                vec3(100.8, 0.8, -0.2),
+                vec3(5., 0., 0.),
+            )
+            .unwrap()
+            .is_none());
+
+        assert_eq!(
+            plat.collide_aabb(vec3(100.2, 0.2, -3.8), vec3(100.8, 0.8, -3.2))
+                .unwrap(),
+            [(ivec3(3, 0, 0), 1)]
+        );
+        assert_eq!(
+            plat.collide_aabb(vec3(103.2, 0.2, -0.8), vec3(103.8, 0.8, -0.2))
+                .unwrap(),
+            []
+        );
+    }
+
+    #[test]
+    fn sweep_through_water() {
+        let mut smbc = SMBC::new();
+        smbc.extend(&BlockCollection::testing()).unwrap();
+        let stone = *smbc.id("Stone").unwrap();
+        let water = *smbc.id("Water").unwrap();
+
+        let mut plat = new_plat(6);
+        block_on(plat.set_voxel(Layer::BASE, uvec3(5, 1, 3), water)).unwrap();
+        block_on(plat.set_voxel(Layer::BASE, uvec3(5, 1, 6), stone)).unwrap();
+
+        let (min, max, velocity) = (vec3(5.2, 1., 1.2), vec3(5.8, 1.8, 1.8), vec3(0., 0., 6.));
+
+        // Everything is solid without block collection
+        let hit = plat.sweep_aabb(min, max, velocity).unwrap().unwrap();
+        assert_eq!(hit.voxel_id as usize, water);
+
+        plat.set_smbc(smbc);
+
+        let hit = plat.sweep_aabb(min, max, velocity).unwrap().unwrap();
+        assert_eq!(hit.voxel_id as usize, stone);
+        assert!((hit.time - 0.7).abs() < 0.0001);
+        assert_eq!(
+            plat.collide_aabb(vec3(5., 1., 3.), vec3(6., 2., 4.)).unwrap(),
+            []
+        );
+    }
 
     #[cfg(feature = "turbo")]
     #[test]
@@ -349,19 +866,19 @@ mod tests {
             // First
             let mut normal_plat_1 = VenxPlat::new(6, 5, 9);
             // Build something
-            normal_plat_1.set_voxel(0, (4, 4, 4).into(), 1).await;
-            normal_plat_1.set_voxel(0, (4, 5, 4).into(), 1).await;
-            normal_plat_1.set_voxel(0, (5, 5, 5).into(), 2).await;
+            normal_plat_1.set_voxel(0, (4, 4, 4).into(), 1).await.unwrap();
+            normal_plat_1.set_voxel(0, (4, 5, 4).into(), 1).await.unwrap();
+            normal_plat_1.set_voxel(0, (5, 5, 5).into(), 2).await.unwrap();
 
             // Second
             let mut normal_plat_2 = VenxPlat::new(6, 5, 9);
             // Build something
-            normal_plat_2.set_voxel(0, (4, 4, 4).into(), 1).await;
-            normal_plat_2.set_voxel(0, (4, 5, 4).into(), 1).await;
-            normal_plat_2.set_voxel(0, (5, 5, 5).into(), 2).await;
+            normal_plat_2.set_voxel(0, (4, 4, 4).into(), 1).await.unwrap();
+            normal_plat_2.set_voxel(0, (4, 5, 4).into(), 1).await.unwrap();
+            normal_plat_2.set_voxel(0, (5, 5, 5).into(), 2).await.unwrap();
 
             // Transfer first to gpu
-            let turbo_plat = pollster::block_on(normal_plat_1.transfer_to_gpu());
+            let turbo_plat = pollster::block_on(normal_plat_1.transfer_to_gpu()).unwrap();
 
             // Transfer back to cpu
             let transfered_from_gpu = pollster::block_on(turbo_plat.transfer_from_gpu());
@@ -1,60 +1,48 @@
 use ouroboros::*;
 
-use venx_core::{
-    plat::{
-        layer::layer::Layer,
-        node::Node,
-        node_l2::NodeL2,
-        raw_plat::{
-            LayerIndex::{Base, Canvas, Schem, Tmp},
-            RawPlat,
-        },
+use venx_core::plat::{
+    layer::{
+        error::LayerError,
+        layer::{Layer, Persistence},
     },
-    utils::l2s,
+    node::Node,
+    node_l2::NodeL2,
+    raw_plat::{RawPlat, MAX_LAYERS, STANDARD_LAYERS},
 };
 
 use crate::plat::interfaces::PlatInterface;
 #[cfg(feature = "turbo")]
 use crate::plat::turbo::gpu_plat::GpuPlat;
 
+/// Nodes and level 2 nodes of single layer
+pub(crate) type LayerComponents = (Vec<Node>, Vec<NodeL2>);
+
 // #[derive(bitcode::Encode, bitcode::Decode)]
 #[self_referencing]
 //#[derive(PartialEq, Debug)]
 pub struct CpuPlat {
-    // Base
-    pub(crate) base_nodes: Vec<Node>,
-    pub(crate) base_l2: Vec<NodeL2>,
-
-    // Tmp
-    pub(crate) tmp_nodes: Vec<Node>,
-    pub(crate) tmp_l2: Vec<NodeL2>,
-
-    // Schem
-    pub(crate) schem_nodes: Vec<Node>,
-    pub(crate) schem_l2: Vec<NodeL2>,
-
-    // Canvas
-    pub(crate) canvas_nodes: Vec<Node>,
-    pub(crate) canvas_l2: Vec<NodeL2>,
-    #[borrows(mut base_nodes, mut base_l2, mut tmp_nodes, mut tmp_l2, mut schem_nodes, mut schem_l2, mut canvas_nodes, mut canvas_l2)]
+    /// Names of layers. Index is id of layer
+    pub(crate) names: Vec<String>,
+    /// Buffers of layers. Index is id of layer
+    pub(crate) components: Vec<LayerComponents>,
+    #[borrows(mut components)]
     #[covariant]
     pub raw_plat: RawPlat<'this>,
 }
 
 impl CpuPlat {
     pub(crate) fn new_plat(depth: usize, chunk_level: usize, segment_level: usize) -> Self {
-        // let base = (
-        //     vec![Node::default(); 3 * (l2s(depth) * l2s(depth)) as usize + 590_000],
-        //     vec![NodeL2::default(); 6 * (l2s(depth) * l2s(depth)) as usize + 80_000],
-        // );
-        let base = (
-            vec![Node::default(); (l2s(depth) * l2s(depth)) as usize + 590_000],
-            vec![NodeL2::default(); (l2s(depth) * l2s(depth)) as usize + 80_000],
-        );
-        let tmp = (vec![Node::default(); 8], vec![NodeL2::default(); 2]);
+        // Layers are growing on demand, see [CpuPlat::grow]
+        let base = (vec![Node::default(); 128], vec![NodeL2::default(); 128]);
+        let tmp = Self::blank_components();
         let (schem, canvas) = (tmp.clone(), tmp.clone());
 
-        Self::new_from(depth, chunk_level, segment_level, base, tmp, schem, canvas)
+        Self::new_from(
+            depth,
+            chunk_level,
+            segment_level,
+            vec![base, tmp, schem, canvas],
+        )
     }
     // TMP
     pub(crate) fn _new_plat_with_length(
@@ -66,94 +54,223 @@ impl CpuPlat {
         let base = (vec![Node::default(); len], vec![NodeL2::default(); 10]);
         let (tmp, schem, canvas) = (base.clone(), base.clone(), base.clone());
 
-        Self::new_from(depth, chunk_level, segment_level, base, tmp, schem, canvas)
+        Self::new_from(
+            depth,
+            chunk_level,
+            segment_level,
+            vec![base, tmp, schem, canvas],
+        )
+    }
+    /// Smallest buffers of new layer. It grows on demand
+    pub(crate) fn blank_components() -> LayerComponents {
+        (vec![Node::default(); 8], vec![NodeL2::default(); 2])
     }
-    /// Create an empty CpuPlat
+    /// Create an empty CpuPlat with standard layers, see [STANDARD_LAYERS]
     pub(crate) fn new_from(
         depth: usize,
         chunk_level: usize,
         segment_level: usize,
-        mut base: (Vec<Node>, Vec<NodeL2>),
-        mut tmp: (Vec<Node>, Vec<NodeL2>),
-        mut schem: (Vec<Node>, Vec<NodeL2>),
-        mut canvas: (Vec<Node>, Vec<NodeL2>),
+        mut components: Vec<LayerComponents>,
     ) -> Self {
+        assert_eq!(components.len(), STANDARD_LAYERS.len());
+
         // Setup and drop
-        Layer::new(0, &mut base.0, &mut base.1);
-        Layer::new(0, &mut tmp.0, &mut tmp.1);
-        Layer::new(0, &mut schem.0, &mut schem.1);
-        Layer::new(0, &mut canvas.0, &mut canvas.1);
+        for (nodes, level_2) in components.iter_mut() {
+            Layer::new(0, nodes, level_2);
+        }
+
+        let names = STANDARD_LAYERS
+            .iter()
+            .map(|name| name.to_string())
+            .collect();
 
-        Self::from_existing(depth, chunk_level, segment_level, base, tmp, schem, canvas)
+        let mut plat = Self::from_existing(depth, chunk_level, segment_level, names, components);
+        plat.with_raw_plat_mut(|plat| plat[Layer::TMP].persistence = Persistence::Transient);
+        plat
     }
     /// Create CpuPlat with already filled layer components
     pub(crate) fn from_existing(
         depth: usize,
         _chunk_level: usize,
         _segment_level: usize,
-        base: (Vec<Node>, Vec<NodeL2>),
-        tmp: (Vec<Node>, Vec<NodeL2>),
-        schem: (Vec<Node>, Vec<NodeL2>),
-        canvas: (Vec<Node>, Vec<NodeL2>),
+        names: Vec<String>,
+        components: Vec<LayerComponents>,
     ) -> Self {
+        assert_eq!(names.len(), components.len());
+        assert!(components.len() <= MAX_LAYERS);
+
         CpuPlatBuilder {
-            raw_plat_builder: |// Base
-                               base_nodes: &mut Vec<Node>,
-                               base_l2: &mut Vec<NodeL2>,
-
-                               // Tmp
-                               tmp_nodes: &mut Vec<Node>,
-                               tmp_l2: &mut Vec<NodeL2>,
-
-                               // Schem
-                               schem_nodes: &mut Vec<Node>,
-                               schem_l2: &mut Vec<NodeL2>,
-
-                               // Canvas
-                               canvas_nodes: &mut Vec<Node>,
-                               canvas_l2: &mut Vec<NodeL2>| {
-                RawPlat {
-                    position: (0, 0, 0),
-                    rotation: (0, 0, 0),
-                    depth,
-                    layers: [
-                        Layer {
-                            depth,
-                            level_2: base_l2,
-                            nodes: base_nodes,
-                        },
-                        Layer {
-                            depth,
-                            level_2: tmp_l2,
-                            nodes: tmp_nodes,
-                        },
-                        Layer {
-                            depth,
-                            level_2: schem_l2,
-                            nodes: schem_nodes,
-                        },
-                        Layer {
-                            depth,
-                            level_2: canvas_l2,
-                            nodes: canvas_nodes,
-                        },
-                    ],
+            names,
+            components,
+            raw_plat_builder: |components: &mut Vec<LayerComponents>| {
+                let mut raw_plat = RawPlat::empty(depth);
+
+                for (nodes, level_2) in components.iter_mut() {
+                    raw_plat.push_layer(Layer {
+                        // Assigned by plat
+                        id: 0,
+                        depth,
+                        level_2,
+                        nodes,
+                        freezed: false,
+                        shared: false,
+                        persistence: Persistence::Persistent,
+                    });
                 }
+                raw_plat
             },
-            base_nodes: base.0,
-            base_l2: base.1,
-            tmp_nodes: tmp.0,
-            tmp_l2: tmp.1,
-            schem_nodes: schem.0,
-            schem_l2: schem.1,
-            canvas_nodes: canvas.0,
-            canvas_l2: canvas.1,
         }
         .build()
     }
 
+    /// Make sure layer has at least given amount of free nodes on upper levels and level 2
+    ///
+    /// If not, pools are doubled and new nodes are chained to the free list.
+    pub(crate) fn grow(&mut self, layer: usize, (upper, l2): (usize, usize)) {
+        let (has_upper, has_l2) = {
+            let layer = &self.borrow_raw_plat()[layer];
+            (layer.has_free::<Node>(upper), layer.has_free::<NodeL2>(l2))
+        };
+
+        if has_upper && has_l2 {
+            return;
+        }
+
+        let (upper_len, l2_len) = {
+            let layer = &self.borrow_raw_plat()[layer];
+            (layer.nodes.len(), layer.level_2.len())
+        };
+
+        let upper_len = if has_upper {
+            upper_len
+        } else {
+            usize::max(upper_len * 2, upper_len + upper)
+        };
+        let l2_len = if has_l2 {
+            l2_len
+        } else {
+            usize::max(l2_len * 2, l2_len + l2)
+        };
+
+        self.resize_layer(layer, (upper_len, l2_len));
+    }
+
+    /// Run fallible write into layer, growing it and repeating while it runs out of nodes
+    ///
+    /// `write` is repeated from the start, so it has to be idempotent
+    pub(crate) fn write_growing<F>(&mut self, layer: usize, mut write: F) -> Result<(), LayerError>
+    where
+        F: FnMut(&mut RawPlat) -> Result<(), LayerError>,
+    {
+        loop {
+            let (upper_len, l2_len) = {
+                let layer = &self.borrow_raw_plat()[layer];
+                (layer.nodes.len(), layer.level_2.len())
+            };
+
+            match self.with_raw_plat_mut(&mut write) {
+                Err(LayerError::OutOfNodes) => self.grow(layer, (upper_len, 0)),
+                Err(LayerError::OutOfL2Nodes) => self.grow(layer, (0, l2_len)),
+                res => return res,
+            }
+        }
+    }
+
+    /// Resize pools of given layer to exact lengths
+    ///
+    /// New nodes are chained to the free list. On shrinking nothing is relinked,
+    /// so make sure there are no live or free nodes behind new lengths (see [Layer::shrink_to_fit_all]).
+    pub(crate) fn resize_layer(&mut self, layer: usize, (upper_len, l2_len): (usize, usize)) {
+        let (old_upper_len, old_l2_len) = self.rebuild(|_, components| {
+            let (nodes, level_2) = &mut components[layer];
+            let (old_upper_len, old_l2_len) = (nodes.len(), level_2.len());
+
+            nodes.resize(upper_len, Node::default());
+            level_2.resize(l2_len, NodeL2::default());
+
+            if upper_len < old_upper_len {
+                nodes.shrink_to_fit();
+            }
+            if l2_len < old_l2_len {
+                level_2.shrink_to_fit();
+            }
+
+            (old_upper_len, old_l2_len)
+        });
+
+        self.with_raw_plat_mut(|plat| {
+            if upper_len > old_upper_len {
+                plat[layer].link_free::<Node>(old_upper_len, upper_len);
+            }
+            if l2_len > old_l2_len {
+                plat[layer].link_free::<NodeL2>(old_l2_len, l2_len);
+            }
+        });
+    }
+
+    /// Add new layer on top of all others. Returns its id
+    pub(crate) fn push_layer(&mut self, name: &str) -> usize {
+        let (mut nodes, mut level_2) = Self::blank_components();
+        // Setup and drop
+        Layer::new(0, &mut nodes, &mut level_2);
+
+        self.rebuild(|names, components| {
+            names.push(name.to_owned());
+            components.push((nodes, level_2));
+        });
+
+        self.borrow_raw_plat().len - 1
+    }
+
+    /// Give mutable access to buffers of all layers
+    ///
+    /// Since [RawPlat] borrows all buffers, it is rebuilt afterwards.
+    /// Position, layer flags and order are kept. Layers can only be added
+    fn rebuild<R>(
+        &mut self,
+        f: impl FnOnce(&mut Vec<String>, &mut Vec<LayerComponents>) -> R,
+    ) -> R {
+        let (depth, position, order, flags) = {
+            let plat = self.borrow_raw_plat();
+            let flags: Vec<(bool, bool, Persistence)> = plat
+                .layers()
+                .iter()
+                .map(|layer| (layer.freezed, layer.shared, layer.persistence))
+                .collect();
+
+            (plat.depth, plat.position, plat.order().to_vec(), flags)
+        };
+
+        // Move buffers out of plat, so they can be reallocated
+        let placeholder = Self::from_existing(depth, 5, 5, vec![], vec![]);
+        let mut heads = std::mem::replace(self, placeholder).into_heads();
+
+        let res = f(&mut heads.names, &mut heads.components);
+        assert!(heads.components.len() >= flags.len());
+
+        *self = Self::from_existing(depth, 5, 5, heads.names, heads.components);
+
+        self.with_raw_plat_mut(|plat| {
+            plat.position = position;
+
+            for (layer, (freezed, shared, persistence)) in plat.layers_mut().iter_mut().zip(flags) {
+                layer.freezed = freezed;
+                layer.shared = shared;
+                layer.persistence = persistence;
+            }
+
+            // New layers are on top
+            let mut order = order;
+            order.extend(order.len()..plat.len);
+            plat.set_order(&order);
+        });
+
+        res
+    }
+
+    /// Fails if there are custom layers, see [GpuPlat::new_from]
     #[cfg(feature = "turbo")]
-    pub(crate) async fn transfer_to_gpu(self) -> GpuPlat {
+    pub(crate) async fn transfer_to_gpu(self) -> anyhow::Result<GpuPlat> {
         //
 
         let plat = self.borrow_raw_plat();
@@ -163,13 +280,116 @@ impl CpuPlat {
             plat.depth,
             5,
             6,
-            (plat[Base].nodes.to_vec(), plat[Base].level_2.to_vec()),
-            (plat[Tmp].nodes.to_vec(), plat[Tmp].level_2.to_vec()),
-            (plat[Schem].nodes.to_vec(), plat[Schem].level_2.to_vec()),
-            (plat[Canvas].nodes.to_vec(), plat[Canvas].level_2.to_vec()),
+            self.borrow_names().clone(),
+            plat.layers()
+                .iter()
+                .map(|layer| (layer.nodes.to_vec(), layer.level_2.to_vec()))
+                .collect(),
+            plat.order(),
         )
         .await
     }
 }
 
 impl PlatInterface for CpuPlat {}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use glam::{uvec3, UVec3};
+    use pollster::block_on;
+
+    use venx_core::plat::layer::error::LayerError;
+
+    use crate::plat::interfaces::layer::LayerInterface;
+
+    use super::CpuPlat;
+
+    /// Plat of depth 7 with chunks and segments on level 5
+    pub(crate) fn new_plat() -> CpuPlat {
+        CpuPlat::new_plat(7, 5, 5)
+    }
+
+    /// Set voxels one by one, growing layer if needed
+    pub(crate) fn set_voxels(plat: &mut CpuPlat, layer: usize, voxels: &[(UVec3, usize)]) {
+        for &(position, voxel_id) in voxels {
+            block_on(plat.set_voxel(layer, position, voxel_id)).unwrap();
+        }
+    }
+
+    #[test]
+    fn grow_on_demand() {
+        let mut plat = new_plat();
+        let (upper_len, l2_len) = plat.length(0);
+
+        for x in 0..32 {
+            for y in 0..32 {
+                for z in 0..32 {
+                    set_voxels(&mut plat, 0, &[(uvec3(x, y, z), (x + y + z) as usize + 1)]);
+                }
+            }
+        }
+
+        let (grown_upper_len, grown_l2_len) = plat.length(0);
+        assert!(grown_upper_len > upper_len);
+        assert!(grown_l2_len > l2_len);
+
+        for x in 0..32 {
+            for y in 0..32 {
+                for z in 0..32 {
+                    let res = plat.get_voxel(uvec3(x, y, z)).unwrap();
+                    assert_eq!(res.voxel_id, (x + y + z) as usize + 1);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn freeze_and_reserve() {
+        let mut plat = new_plat();
+
+        for x in 0..16 {
+            for z in 0..16 {
+                set_voxels(
+                    &mut plat,
+                    0,
+                    &[(uvec3(x, 0, z), (x % 4) as usize + 1), (uvec3(x, 1, z), 1)],
+                );
+            }
+        }
+        // Leave some holes
+        for x in 0..16 {
+            for z in 0..16 {
+                set_voxels(&mut plat, 0, &[(uvec3(x, 1, z), 0)]);
+            }
+        }
+
+        let (upper_len, l2_len) = plat.length(0);
+        let (free_upper, free_l2) = plat.free(0);
+
+        plat.freeze(0);
+
+        assert!(plat.is_frozen(0));
+        assert_eq!(plat.free(0), (0, 0));
+        assert_eq!(plat.length(0), (upper_len - free_upper, l2_len - free_l2));
+        assert_eq!(
+            block_on(plat.set_voxel(0, uvec3(30, 30, 30), 7)),
+            Err(LayerError::Frozen)
+        );
+        assert_eq!(plat.free(0), (0, 0));
+
+        plat.reserve(0, (40, 3));
+        assert!(!plat.is_frozen(0));
+        assert_eq!(plat.free(0), (40, 3));
+
+        set_voxels(&mut plat, 0, &[(uvec3(30, 30, 30), 7)]);
+
+        for x in 0..16 {
+            for z in 0..16 {
+                let res = plat.get_voxel(uvec3(x, 0, z)).unwrap();
+                assert_eq!(res.voxel_id, (x % 4) as usize + 1);
+                assert!(plat.get_voxel(uvec3(x, 1, z)).is_none());
+            }
+        }
+        assert_eq!(plat.get_voxel(uvec3(30, 30, 30)).unwrap().voxel_id, 7);
+    }
+}
@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+
+use venx_core::plat::{layer::layer::Layer, node::Node, node_l2::NodeL2};
+
+use crate::plat::interfaces::layer::CompressReport;
+
+/// Bottom-up hash-consing of a whole layer
+///
+/// Each reachable node is visited once. After its children are replaced with canonical ones,
+/// node is looked up among already seen nodes on the same level.
+/// If identical node exists, parent gets linked to it and duplicate is deallocated
+///
+/// Sharing marks are dropped, recount them with [Layer::collect_garbage] afterwards
+#[derive(Default)]
+pub(crate) struct Dedup {
+    /// Canonical nodes on each level (2..depth), key is node with already deduplicated children
+    lookup_tables: Vec<HashMap<Node, u32>>,
+    /// Canonical fork chains, key is content of every fork in chain.
+    /// Chains are deduplicated as a whole, so forks are never shared between different chains
+    lookup_table_chains: HashMap<Vec<[u32; 8]>, u32>,
+    lookup_table_l2: HashMap<NodeL2, u32>,
+    /// Node idx -> canonical idx. First forks of chains share it, since they live in the same pool
+    visited: HashMap<u32, u32>,
+    visited_l2: HashMap<u32, u32>,
+    report: CompressReport,
+}
+
+impl Dedup {
+    pub(crate) fn run(layer: &mut Layer) -> CompressReport {
+        let mut dedup = Dedup {
+            lookup_tables: vec![HashMap::new(); layer.depth + 1],
+            ..Default::default()
+        };
+
+        // Root is always on 1 idx;
+        dedup.node(layer, 1, layer.depth);
+
+        dedup.report
+    }
+
+    /// Returns canonical idx of node
+    fn node(&mut self, layer: &mut Layer, idx: u32, level: usize) -> u32 {
+        if idx == 0 {
+            return 0;
+        }
+
+        if let Some(canonical) = self.visited.get(&idx) {
+            return *canonical;
+        }
+
+        let mut node = layer[idx as usize];
+        // Marks are not a part of content
+        node.flag = 0;
+
+        for child in node.children.iter_mut() {
+            *child = match level {
+                3 => self.l2(layer, *child),
+                // Children are forks
+                5 => self.fork(layer, *child),
+                _ => self.node(layer, *child, level - 1),
+            };
+        }
+
+        layer[idx as usize] = node;
+
+        // Root stays where it is
+        if idx == 1 {
+            return 1;
+        }
+
+        let canonical = *self.lookup_tables[level].entry(node).or_insert(idx);
+
+        self.replace(layer, idx, canonical)
+    }
+
+    fn fork(&mut self, layer: &mut Layer, idx: u32) -> u32 {
+        if idx == 0 {
+            return 0;
+        }
+
+        if let Some(canonical) = self.visited.get(&idx) {
+            return *canonical;
+        }
+
+        let mut chain = vec![];
+        let mut fork_idx = idx;
+
+        while fork_idx != 0 {
+            let mut fork = layer[fork_idx as usize];
+
+            for slot in 0..4 {
+                // Entries are packed
+                if fork[slot * 2] == 0 {
+                    break;
+                }
+                fork[slot * 2 + 1] = self.node(layer, fork[slot * 2 + 1], 4);
+            }
+
+            layer[fork_idx as usize] = fork;
+            chain.push(fork_idx);
+
+            fork_idx = if fork.flag > 0 { fork.flag as u32 } else { 0 };
+        }
+
+        // Links between forks are internal to chain
+        let key = chain
+            .iter()
+            .map(|&fork_idx| layer[fork_idx as usize].children)
+            .collect();
+        let canonical = *self.lookup_table_chains.entry(key).or_insert(idx);
+
+        if canonical != idx {
+            for fork_idx in chain {
+                layer.deallocate_node::<Node>(fork_idx as usize);
+                self.report.freed_upper += 1;
+            }
+        }
+
+        self.visited.insert(idx, canonical);
+
+        canonical
+    }
+
+    fn l2(&mut self, layer: &mut Layer, idx: u32) -> u32 {
+        if idx == 0 {
+            return 0;
+        }
+
+        if let Some(canonical) = self.visited_l2.get(&idx) {
+            return *canonical;
+        }
+
+        let node = layer.level_2[idx as usize];
+        let canonical = *self.lookup_table_l2.entry(node).or_insert(idx);
+
+        if canonical != idx {
+            layer.deallocate_node::<NodeL2>(idx as usize);
+            self.report.freed_l2 += 1;
+        }
+
+        self.visited_l2.insert(idx, canonical);
+
+        canonical
+    }
+
+    fn replace(&mut self, layer: &mut Layer, idx: u32, canonical: u32) -> u32 {
+        if canonical != idx {
+            layer.deallocate_node::<Node>(idx as usize);
+            self.report.freed_upper += 1;
+        }
+
+        self.visited.insert(idx, canonical);
+
+        canonical
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::uvec3;
+    use pollster::block_on;
+
+    use crate::plat::{
+        interfaces::layer::LayerInterface,
+        normal::cpu_plat::tests::{new_plat, set_voxels},
+    };
+
+    #[test]
+    fn compress_dag_repeated() {
+        let mut plat = new_plat();
+
+        // Same 16x16x16 pattern in every chunk of 64x64x64 area
+        let ty = |x: u32, y: u32, z: u32| ((x % 16 + y % 16 * 3 + z % 16) % 5) as usize;
+
+        for x in 0..64 {
+            for y in 0..64 {
+                for z in 0..64 {
+                    set_voxels(&mut plat, 0, &[(uvec3(x, y, z), ty(x, y, z))]);
+                }
+            }
+        }
+
+        let (upper_len, l2_len) = plat.length(0);
+        let (free_upper, free_l2) = plat.free(0);
+
+        let report = block_on(plat.compress_dag(0));
+
+        assert!(report.freed_upper > 0);
+        assert!(report.freed_l2 > 0);
+        assert_eq!(
+            plat.free(0),
+            (
+                free_upper + report.freed_upper as u32,
+                free_l2 + report.freed_l2 as u32
+            )
+        );
+
+        // Nothing left to deduplicate
+        assert_eq!(block_on(plat.compress_dag(0)), Default::default());
+
+        plat.freeze(0);
+        assert_eq!(
+            plat.length(0),
+            (
+                upper_len - free_upper - report.freed_upper as u32,
+                l2_len - free_l2 - report.freed_l2 as u32
+            )
+        );
+
+        for x in 0..64 {
+            for y in 0..64 {
+                for z in 0..64 {
+                    let res = plat.get_voxel(uvec3(x, y, z));
+                    match ty(x, y, z) {
+                        0 => assert!(res.is_none()),
+                        voxel_id => assert_eq!(res.unwrap().voxel_id, voxel_id),
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn edit_after_compress_dag() {
+        let mut plat = new_plat();
+
+        // Two identical regions
+        for x in 0..32 {
+            for z in 0..32 {
+                let ty = (x + z) as usize % 3 + 1;
+                set_voxels(&mut plat, 0, &[(uvec3(x, 0, z), ty)]);
+                set_voxels(&mut plat, 0, &[(uvec3(x + 64, 0, z), ty)]);
+            }
+        }
+
+        let report = block_on(plat.compress_dag(0));
+        assert!(report.freed_upper > 0);
+
+        // Edit only second one
+        set_voxels(&mut plat, 0, &[(uvec3(64 + 5, 0, 5), 9)]);
+        set_voxels(&mut plat, 0, &[(uvec3(64 + 6, 0, 6), 0)]);
+        set_voxels(&mut plat, 0, &[(uvec3(64 + 7, 1, 7), 1)]);
+
+        for x in 0..32 {
+            for z in 0..32 {
+                let ty = (x + z) as usize % 3 + 1;
+                assert_eq!(plat.get_voxel(uvec3(x, 0, z)).unwrap().voxel_id, ty);
+
+                let edited = plat.get_voxel(uvec3(x + 64, 0, z));
+                match (x, z) {
+                    (5, 5) => assert_eq!(edited.unwrap().voxel_id, 9),
+                    (6, 6) => assert!(edited.is_none()),
+                    _ => assert_eq!(edited.unwrap().voxel_id, ty),
+                }
+            }
+        }
+        assert!(plat.get_voxel(uvec3(7, 1, 7)).is_none());
+        assert_eq!(plat.get_voxel(uvec3(64 + 7, 1, 7)).unwrap().voxel_id, 1);
+
+        // Only nodes still linked by the first region were copied, so nothing leaked
+        assert_eq!(plat.collect_garbage(0), Default::default());
+        assert_eq!(plat.get_voxel(uvec3(64 + 5, 0, 5)).unwrap().voxel_id, 9);
+        assert_eq!(plat.get_voxel(uvec3(5, 0, 5)).unwrap().voxel_id, 2);
+    }
+}
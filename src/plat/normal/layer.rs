@@ -1,23 +1,329 @@
-use std::collections::HashMap;
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+};
 
+use anyhow::bail;
 use async_trait::async_trait;
 use log::{info, warn};
 
-use venx_core::plat::{node::Node, node_l2::NodeL2, op::get::GetNodeResult};
+use venx_core::plat::{
+    layer::{error::LayerError, layer::Persistence},
+    node::Node,
+    node_l2::NodeL2,
+    op::{gc::GarbageReport, get::GetNodeResult},
+    raw_plat::MAX_LAYERS,
+};
 
-use crate::plat::interfaces::layer::LayerInterface;
+use crate::plat::{
+    clipboard::{Clipboard, PasteTransform},
+    interfaces::layer::{CompressReport, LayerInterface},
+    segment::Segment,
+};
 
-use super::cpu_plat::CpuPlat;
+use super::{cpu_plat::CpuPlat, dag::Dedup};
 #[async_trait]
 impl LayerInterface for CpuPlat {
-    async fn set_voxel(&mut self, layer: usize, position: glam::UVec3, ty: usize) {
-        self.with_raw_plat_mut(|plat| plat[layer].set(position.to_array().into(), ty as u32));
+    fn add_layer(&mut self, name: &str) -> anyhow::Result<usize> {
+        if self.layer_id(name).is_some() {
+            bail!("Layer {name} already exists");
+        }
+        if self.borrow_raw_plat().len >= MAX_LAYERS {
+            bail!("Plat cannot have more than {MAX_LAYERS} layers");
+        }
+
+        Ok(self.push_layer(name))
+    }
+
+    fn layer_id(&self, name: &str) -> Option<usize> {
+        self.borrow_names().iter().position(|n| n == name)
+    }
+
+    fn layer_names(&self) -> Vec<String> {
+        self.borrow_names().clone()
+    }
+
+    fn layer_order(&self) -> Vec<usize> {
+        self.borrow_raw_plat().order().to_vec()
+    }
+
+    fn set_layer_order(&mut self, order: &[usize]) -> anyhow::Result<()> {
+        let mut sorted = order.to_vec();
+        sorted.sort();
+
+        if sorted != (0..self.borrow_raw_plat().len).collect::<Vec<_>>() {
+            bail!("Order {order:?} should list every layer id exactly once");
+        }
+
+        self.with_raw_plat_mut(|plat| plat.set_order(order));
+        Ok(())
     }
 
+    fn persistence(&self, layer: usize) -> Persistence {
+        self.borrow_raw_plat()[layer].persistence
+    }
+
+    fn set_persistence(&mut self, layer: usize, persistence: Persistence) {
+        self.with_raw_plat_mut(|plat| plat[layer].persistence = persistence);
+    }
+
+    async fn set_voxel(
+        &mut self,
+        layer: usize,
+        position: glam::UVec3,
+        ty: usize,
+    ) -> Result<(), LayerError> {
+        if self.persistence(layer) == Persistence::ReadOnly {
+            return Err(LayerError::ReadOnly);
+        }
+        if self.is_frozen(layer) {
+            // Growing would make frozen layer writable
+            return Err(LayerError::Frozen);
+        }
+
+        let required =
+            self.borrow_raw_plat()[layer].required_nodes(position.to_array().into(), ty as u32)?;
+        self.grow(layer, required);
+
+        self.with_raw_plat_mut(|plat| plat[layer].try_set(position.to_array().into(), ty as u32))
+    }
+
+    async fn set_voxel_at(
+        &mut self,
+        layer: usize,
+        position: glam::IVec3,
+        ty: usize,
+    ) -> Result<(), LayerError> {
+        let position = self
+            .borrow_raw_plat()
+            .to_local(position.to_array().into())?;
+
+        self.set_voxel(layer, position.to_array().into(), ty).await
+    }
+
+    async fn set_voxel_many(
+        &mut self,
+        layer: usize,
+        voxels: &[(glam::UVec3, u32)],
+    ) -> Result<(), LayerError> {
+        if self.persistence(layer) == Persistence::ReadOnly {
+            return Err(LayerError::ReadOnly);
+        }
+        if self.is_frozen(layer) {
+            return Err(LayerError::Frozen);
+        }
+
+        // Stable, so the last of duplicates still wins
+        let mut voxels = voxels.to_vec();
+        voxels.sort_by(|(a, _), (b, _)| octree_order(*a, *b));
+
+        let voxels: Vec<_> = voxels
+            .into_iter()
+            .map(|(position, voxel_id)| (position.to_array().into(), voxel_id))
+            .collect();
+
+        self.write_growing(layer, |plat| plat[layer].try_set_many(&voxels))
+    }
+
+    async fn set_segment(
+        &mut self,
+        layer: usize,
+        segment: &Segment,
+        offset: glam::UVec3,
+    ) -> Result<(), LayerError> {
+        if self.persistence(layer) == Persistence::ReadOnly {
+            return Err(LayerError::ReadOnly);
+        }
+        if self.is_frozen(layer) {
+            return Err(LayerError::Frozen);
+        }
+
+        let size = self.borrow_raw_plat().size();
+        let to = offset + segment.size();
+
+        if to.max_element() > size {
+            return Err(LayerError::OutOfBounds);
+        }
+
+        self.write_growing(layer, |plat| {
+            plat[layer].fill_with(offset.to_array().into(), to.to_array().into(), |position| {
+                match segment.get(glam::UVec3::from_array(position.to_array()) - offset) {
+                    0 => None,
+                    voxel_id => Some(voxel_id),
+                }
+            })
+        })
+    }
+
+    fn extract_region(&self, layer: usize, min: glam::UVec3, max: glam::UVec3) -> Clipboard {
+        let max = max.min(glam::UVec3::splat(self.borrow_raw_plat().size()));
+        let min = min.min(max);
+
+        let mut clipboard = Clipboard::new(max - min);
+        clipboard.source = Some((layer, min));
+
+        self.borrow_raw_plat()[layer].extract_region(
+            min.to_array().into(),
+            max.to_array().into(),
+            |position, voxel_id| {
+                clipboard.set(glam::UVec3::from_array(position.to_array()) - min, voxel_id)
+            },
+        );
+        clipboard
+    }
+
+    async fn paste(
+        &mut self,
+        layer: usize,
+        clipboard: &Clipboard,
+        offset: glam::UVec3,
+        transform: PasteTransform,
+    ) -> Result<(), LayerError> {
+        if self.persistence(layer) == Persistence::ReadOnly {
+            return Err(LayerError::ReadOnly);
+        }
+        if self.is_frozen(layer) {
+            return Err(LayerError::Frozen);
+        }
+
+        let transformed;
+        let clipboard = if transform.is_identity() {
+            clipboard
+        } else {
+            transformed = clipboard.transformed(transform);
+            &transformed
+        };
+
+        let size = self.borrow_raw_plat().size();
+        let to = offset + clipboard.size();
+
+        if to.max_element() > size {
+            return Err(LayerError::OutOfBounds);
+        }
+
+        let linked = self.link_clipboard(layer, clipboard, offset)?;
+
+        self.write_growing(layer, |plat| {
+            plat[layer].fill_with(offset.to_array().into(), to.to_array().into(), |position| {
+                let position = glam::UVec3::from_array(position.to_array());
+
+                if linked.contains(&(position >> 4)) {
+                    return None;
+                }
+
+                match clipboard.get(position - offset) {
+                    0 => None,
+                    voxel_id => Some(voxel_id),
+                }
+            })
+        })
+    }
+
+    async fn move_region(
+        &mut self,
+        layer: usize,
+        min: glam::UVec3,
+        max: glam::UVec3,
+        offset: glam::UVec3,
+    ) -> Result<(), LayerError> {
+        let mut clipboard = self.extract_region(layer, min, max);
+        let min = min.min(max);
+
+        if (offset + clipboard.size()).max_element() > self.borrow_raw_plat().size() {
+            return Err(LayerError::OutOfBounds);
+        }
+
+        let mut cleared = vec![];
+        clipboard.iter(|position, voxel_id| {
+            if voxel_id != 0 {
+                cleared.push((position + min, 0));
+            }
+        });
+        self.set_voxel_many(layer, &cleared).await?;
+
+        // Source is empty now, nothing to link
+        clipboard.source = None;
+        self.paste(layer, &clipboard, offset, PasteTransform::IDENTITY)
+            .await
+    }
+
+    async fn compress_dag(&mut self, layer: usize) -> CompressReport {
+        self.with_raw_plat_mut(|plat| {
+            let layer = &mut plat[layer];
+            let mut report = Dedup::run(layer);
+
+            // Marks nodes which got more than one parent
+            let garbage = layer.collect_garbage(
+                &mut vec![false; layer.nodes.len()],
+                &mut vec![false; layer.level_2.len()],
+            );
+            report.freed_upper += garbage.nodes + garbage.forks;
+            report.freed_l2 += garbage.level_2;
+
+            report
+        })
+    }
+
+    async fn merge_layers(&mut self, src: usize, dst: usize) -> Result<(), LayerError> {
+        if [src, dst]
+            .iter()
+            .any(|&layer| self.persistence(layer) == Persistence::ReadOnly)
+        {
+            return Err(LayerError::ReadOnly);
+        }
+        if self.is_frozen(src) {
+            return Err(LayerError::Frozen);
+        }
+
+        let refreeze = self.is_frozen(dst);
+        if refreeze {
+            self.reserve(dst, (0, 0));
+        }
+
+        // Merge is idempotent, so just grow and try again
+        let res = self.write_growing(dst, |plat| plat.merge_layers(src, dst));
+
+        if refreeze {
+            self.freeze(dst);
+        }
+
+        res
+    }
+
+    fn collect_garbage(&mut self, layer: usize) -> GarbageReport {
+        let (upper_len, l2_len) = self.length(layer);
+        let mut marks = vec![false; upper_len as usize];
+        let mut marks_l2 = vec![false; l2_len as usize];
+
+        self.with_raw_plat_mut(|plat| plat[layer].collect_garbage(&mut marks, &mut marks_l2))
+    }
     fn freeze(&mut self, layer: usize) {
-        let (len_l2, _len_upper) = self.length(layer);
-        let mut helper_l2 = vec![0; len_l2 as usize];
-        self.with_raw_plat_mut(|plat| plat.layers[layer].freeze_upper(&mut helper_l2));
+        let (upper_len, l2_len) = self.with_raw_plat_mut(|plat| {
+            plat[layer].freezed = true;
+            plat[layer].shrink_to_fit_all()
+        });
+        self.resize_layer(layer, (upper_len, l2_len));
+    }
+    fn is_frozen(&self, layer: usize) -> bool {
+        self.borrow_raw_plat()[layer].freezed
+    }
+    fn reserve(&mut self, layer: usize, (upper, l2): (u32, u32)) {
+        let (free_upper, free_l2) = self.free(layer);
+        let (upper_len, l2_len) = self.length(layer);
+
+        self.with_raw_plat_mut(|plat| plat[layer].freezed = false);
+
+        if free_upper >= upper && free_l2 >= l2 {
+            return;
+        }
+
+        self.resize_layer(
+            layer,
+            (
+                (upper_len + upper.saturating_sub(free_upper)) as usize,
+                (l2_len + l2.saturating_sub(free_l2)) as usize,
+            ),
+        );
     }
     fn length(&self, layer: usize) -> (u32, u32) {
         let layer = &self.borrow_raw_plat().layers[layer];
@@ -129,4 +435,373 @@ impl LayerInterface for CpuPlat {
             None
         }
     }
+
+    fn get_voxel_in(&self, position: glam::UVec3, layers_mask: u32) -> Option<GetNodeResult> {
+        let res = self
+            .borrow_raw_plat()
+            .get_voxel_in(position.to_array().into(), layers_mask);
+
+        if res.is_some() {
+            Some(res)
+        } else {
+            None
+        }
+    }
+
+    fn get_voxel_at(&self, position: glam::IVec3) -> Result<Option<GetNodeResult>, LayerError> {
+        let res = self
+            .borrow_raw_plat()
+            .get_voxel_at(position.to_array().into())?;
+
+        Ok(if res.is_some() { Some(res) } else { None })
+    }
+}
+
+impl CpuPlat {
+    /// Link every empty subtree of destination box to the same one of [Clipboard] source, see [LayerInterface::paste]
+    ///
+    /// Returns covered 16-blocks (positions shifted by 4), which are not to be pasted voxel by voxel
+    fn link_clipboard(
+        &mut self,
+        layer: usize,
+        clipboard: &Clipboard,
+        offset: glam::UVec3,
+    ) -> Result<HashSet<glam::UVec3>, LayerError> {
+        let mut linked = HashSet::new();
+
+        let Some((src_layer, src_min)) = clipboard.source else {
+            return Ok(linked);
+        };
+        let (dst_min, dst_max) = (offset, offset + clipboard.size());
+        let src_max = src_min + clipboard.size();
+
+        // Subtrees can only be shared within a layer.
+        // Overlapping boxes would change source while pasting
+        if src_layer != layer || (dst_min.cmplt(src_max).all() && src_min.cmplt(dst_max).all()) {
+            return Ok(linked);
+        }
+
+        let depth = self.borrow_raw_plat().depth();
+        let diff = offset ^ src_min;
+        // Highest level both boxes are aligned on the same way
+        let max_level = ((diff.x | diff.y | diff.z).trailing_zeros() as usize).min(depth - 1);
+
+        if max_level < 4 {
+            return Ok(linked);
+        }
+
+        let mut stack = vec![(glam::UVec3::ZERO, depth)];
+
+        while let Some((position, level)) = stack.pop() {
+            let cube_end = position + (1 << level);
+
+            if cube_end.cmple(dst_min).any() || position.cmpge(dst_max).any() {
+                continue;
+            }
+
+            if level <= max_level
+                && position.cmpge(dst_min).all()
+                && cube_end.cmple(dst_max).all()
+                && self.try_link(layer, clipboard, position - offset, position, level)?
+            {
+                for x in (position.x..cube_end.x).step_by(16) {
+                    for y in (position.y..cube_end.y).step_by(16) {
+                        for z in (position.z..cube_end.z).step_by(16) {
+                            linked.insert(glam::uvec3(x, y, z) >> 4);
+                        }
+                    }
+                }
+                continue;
+            }
+
+            if level > 4 {
+                for child in 0..8 {
+                    let child_position =
+                        glam::UVec3::from_array(Node::get_child_position(child).to_array());
+                    stack.push((position + child_position * (1 << (level - 1)), level - 1));
+                }
+            }
+        }
+
+        Ok(linked)
+    }
+
+    /// Link cube on `level` at `dst_position` to the source one, if it is empty
+    /// and source still holds the same voxels as clipboard at `local` position
+    fn try_link(
+        &mut self,
+        layer: usize,
+        clipboard: &Clipboard,
+        local: glam::UVec3,
+        dst_position: glam::UVec3,
+        level: usize,
+    ) -> Result<bool, LayerError> {
+        let src_min = clipboard.source.unwrap().1;
+        let src_position = src_min + local;
+        let cube_size = glam::UVec3::splat(1 << level);
+
+        let plat = self.borrow_raw_plat();
+
+        let mut dst_empty = true;
+        plat[layer].extract_region(
+            dst_position.to_array().into(),
+            (dst_position + cube_size).to_array().into(),
+            |_, _| dst_empty = false,
+        );
+        if !dst_empty {
+            return Ok(false);
+        }
+
+        let (mut same, mut count) = (true, 0);
+        plat[layer].extract_region(
+            src_position.to_array().into(),
+            (src_position + cube_size).to_array().into(),
+            |position, voxel_id| {
+                same &= clipboard.get(glam::UVec3::from_array(position.to_array()) - src_min)
+                    == voxel_id;
+                count += 1;
+            },
+        );
+        if !same {
+            return Ok(false);
+        }
+
+        let mut expected = 0;
+        for x in local.x..local.x + cube_size.x {
+            for y in local.y..local.y + cube_size.y {
+                for z in local.z..local.z + cube_size.z {
+                    if clipboard.get(glam::uvec3(x, y, z)) != 0 {
+                        expected += 1;
+                    }
+                }
+            }
+        }
+        if expected != count {
+            return Ok(false);
+        }
+
+        // Nothing to paste there
+        if count == 0 {
+            return Ok(true);
+        }
+
+        self.write_growing(layer, |plat| {
+            plat[layer].link_subtree(
+                src_position.to_array().into(),
+                dst_position.to_array().into(),
+                level,
+            )
+        })?;
+
+        Ok(true)
+    }
+}
+
+/// Order of voxels in octree, so consecutive ones share as much of path from the root as possible
+///
+/// Compares positions by the most significant differing bit, z goes first as it does in child index
+fn octree_order(a: glam::UVec3, b: glam::UVec3) -> Ordering {
+    let less_msb = |x: u32, y: u32| x < y && x < (x ^ y);
+
+    let mut axis = 2;
+    let mut highest = a.z ^ b.z;
+
+    for (i, diff) in [(1, a.y ^ b.y), (0, a.x ^ b.x)] {
+        if less_msb(highest, diff) {
+            axis = i;
+            highest = diff;
+        }
+    }
+
+    a[axis].cmp(&b[axis])
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{ivec3, uvec3};
+    use pollster::block_on;
+
+    use venx_core::plat::{
+        layer::{error::LayerError, layer::Lr},
+        raw_plat::MAX_LAYERS,
+    };
+
+    use crate::plat::{
+        interfaces::layer::LayerInterface,
+        normal::cpu_plat::tests::{new_plat, set_voxels},
+        segment::Segment,
+    };
+
+    #[test]
+    fn set_voxel_at_negative() {
+        let mut plat = new_plat();
+        plat.with_raw_plat_mut(|plat| plat.set_origin((-64, -10, -64).into()));
+
+        block_on(plat.set_voxel_at(0, ivec3(-1, -1, -1), 3)).unwrap();
+        block_on(plat.set_voxel_at(0, ivec3(63, 117, -64), 4)).unwrap();
+
+        assert_eq!(
+            plat.get_voxel_at(ivec3(-1, -1, -1))
+                .unwrap()
+                .unwrap()
+                .voxel_id,
+            3
+        );
+        assert_eq!(
+            plat.get_voxel_at(ivec3(63, 117, -64))
+                .unwrap()
+                .unwrap()
+                .voxel_id,
+            4
+        );
+        assert_eq!(plat.get_voxel(uvec3(63, 9, 63)).unwrap().voxel_id, 3);
+        assert_eq!(plat.get_voxel_at(ivec3(0, 0, 0)), Ok(None));
+
+        assert_eq!(
+            block_on(plat.set_voxel_at(0, ivec3(64, 0, 0), 1)),
+            Err(LayerError::OutOfBounds)
+        );
+        assert_eq!(
+            plat.get_voxel_at(ivec3(0, -11, 0)),
+            Err(LayerError::OutOfBounds)
+        );
+    }
+
+    #[test]
+    fn custom_layers() {
+        let mut plat = new_plat();
+
+        assert_eq!(plat.layer_names(), ["base", "tmp", "schem", "canvas"]);
+
+        let decals = plat.add_layer("decals").unwrap();
+        let player = plat.add_layer("player_1").unwrap();
+        assert_eq!((decals, player), (4, 5));
+        assert_eq!(plat.layer_id("decals"), Some(4));
+        assert!(plat.add_layer("decals").is_err());
+
+        set_voxels(&mut plat, 0, &[(uvec3(5, 5, 5), 1)]);
+        set_voxels(&mut plat, decals, &[(uvec3(5, 5, 5), 2)]);
+        assert_eq!(plat.get_voxel(uvec3(5, 5, 5)).unwrap().voxel_id, 2);
+
+        // Grow player layer a lot, it should not affect order or other layers
+        plat.set_layer_order(&[0, 1, 2, 3, 5, 4]).unwrap();
+        for x in 0..16 {
+            for z in 0..16 {
+                set_voxels(&mut plat, player, &[(uvec3(x, 5, z), 3)]);
+            }
+        }
+        assert_eq!(plat.layer_order(), [0, 1, 2, 3, 5, 4]);
+        assert_eq!(plat.get_voxel(uvec3(5, 5, 5)).unwrap().voxel_id, 2);
+        assert_eq!(plat.get_voxel(uvec3(6, 5, 5)).unwrap().voxel_id, 3);
+
+        plat.set_layer_order(&[0, 1, 2, 3, 4, 5]).unwrap();
+        assert_eq!(plat.get_voxel(uvec3(5, 5, 5)).unwrap().voxel_id, 3);
+
+        assert!(plat.set_layer_order(&[0, 1, 2, 3, 4, 4]).is_err());
+        assert!(plat.set_layer_order(&[0, 1, 2, 3]).is_err());
+
+        for i in 0..MAX_LAYERS - 6 {
+            plat.add_layer(&format!("extra_{i}")).unwrap();
+        }
+        assert!(plat.add_layer("one_too_many").is_err());
+    }
+
+    #[test]
+    fn dig_through_frozen_base() {
+        let mut plat = new_plat();
+
+        set_voxels(&mut plat, Lr::BASE, &[(uvec3(5, 5, 5), 1)]);
+        plat.freeze(Lr::BASE);
+
+        set_voxels(&mut plat, Lr::CANVAS, &[(uvec3(5, 5, 5), Lr::AIR as usize)]);
+
+        assert!(plat.get_voxel(uvec3(5, 5, 5)).is_none());
+        assert_eq!(
+            plat.get_voxel_in(uvec3(5, 5, 5), 1 << Lr::BASE)
+                .unwrap()
+                .voxel_id,
+            1
+        );
+
+        // Filling the hole back
+        set_voxels(&mut plat, Lr::CANVAS, &[(uvec3(5, 5, 5), 0)]);
+        assert_eq!(plat.get_voxel(uvec3(5, 5, 5)).unwrap().voxel_id, 1);
+    }
+
+    #[test]
+    fn merge_canvas_into_frozen_base() {
+        let mut plat = new_plat();
+
+        set_voxels(
+            &mut plat,
+            Lr::BASE,
+            &[(uvec3(5, 5, 5), 1), (uvec3(6, 5, 5), 1)],
+        );
+        plat.freeze(Lr::BASE);
+
+        set_voxels(&mut plat, Lr::CANVAS, &[(uvec3(5, 5, 5), Lr::AIR as usize)]);
+        for x in 0..32 {
+            set_voxels(&mut plat, Lr::CANVAS, &[(uvec3(x, 20, 0), 2)]);
+        }
+
+        block_on(plat.merge_layers(Lr::CANVAS, Lr::BASE)).unwrap();
+
+        assert!(plat.is_frozen(Lr::BASE));
+        assert_eq!(plat.free(Lr::BASE), (0, 0));
+        assert!(plat.get_voxel(uvec3(5, 5, 5)).is_none());
+        assert_eq!(plat.get_voxel(uvec3(6, 5, 5)).unwrap().voxel_id, 1);
+        for x in 0..32 {
+            let res = plat.get_voxel(uvec3(x, 20, 0)).unwrap();
+            assert_eq!((res.voxel_id, res.layer_id), (2, Lr::BASE));
+        }
+        assert!(plat
+            .get_voxel_in(uvec3(0, 20, 0), 1 << Lr::CANVAS)
+            .is_none());
+    }
+
+    #[test]
+    fn set_voxel_many_and_segment() {
+        let mut plat = new_plat();
+
+        let mut voxels = vec![];
+        for x in 0..40 {
+            for z in 0..40 {
+                voxels.push((uvec3(x, 3, z), x + 1));
+            }
+        }
+        // Last one wins
+        voxels.push((uvec3(0, 3, 0), 7));
+        voxels.push((uvec3(1, 3, 0), 0));
+
+        block_on(plat.set_voxel_many(Lr::BASE, &voxels)).unwrap();
+
+        assert_eq!(plat.get_voxel(uvec3(0, 3, 0)).unwrap().voxel_id, 7);
+        assert!(plat.get_voxel(uvec3(1, 3, 0)).is_none());
+        assert_eq!(plat.get_voxel(uvec3(39, 3, 20)).unwrap().voxel_id, 40);
+
+        let mut segment = Segment::new(5);
+        segment.iter_mut(|position, block| {
+            if position.y < 10 {
+                *block = 2;
+            }
+        });
+
+        block_on(plat.set_segment(Lr::CANVAS, &segment, uvec3(64, 32, 0))).unwrap();
+
+        assert_eq!(plat.get_voxel(uvec3(64, 32, 0)).unwrap().voxel_id, 2);
+        assert_eq!(plat.get_voxel(uvec3(95, 41, 31)).unwrap().voxel_id, 2);
+        assert!(plat.get_voxel(uvec3(95, 42, 31)).is_none());
+        assert!(plat.get_voxel(uvec3(96, 32, 0)).is_none());
+
+        assert_eq!(
+            block_on(plat.set_segment(Lr::CANVAS, &segment, uvec3(100, 0, 0))),
+            Err(LayerError::OutOfBounds)
+        );
+
+        plat.freeze(Lr::BASE);
+        assert_eq!(
+            block_on(plat.set_voxel_many(Lr::BASE, &voxels)),
+            Err(LayerError::Frozen)
+        );
+    }
 }
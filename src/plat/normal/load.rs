@@ -1,4 +1,4 @@
-use venx_core::plat::{chunk::chunk::Chunk, layer::layer::Lr};
+use venx_core::plat::chunk::chunk::Chunk;
 
 use crate::plat::interfaces::load::LoadInterface;
 
@@ -11,7 +11,7 @@ impl LoadInterface for CpuPlat {
         lod_level: usize,
         chunk_level: usize,
     ) -> Box<Chunk> {
-        Box::new(self.borrow_raw_plat()[Lr::BASE].load_chunk(
+        Box::new(self.borrow_raw_plat().load_chunk(
             position.to_array().into(),
             lod_level,
             chunk_level,
@@ -22,3 +22,33 @@ impl LoadInterface for CpuPlat {
         self.to_mesh_greedy(chunk)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use glam::uvec3;
+    use venx_core::plat::layer::layer::Lr;
+
+    use crate::plat::{
+        interfaces::load::LoadInterface,
+        normal::cpu_plat::tests::{new_plat, set_voxels},
+    };
+
+    #[test]
+    fn load_chunk_all_layers() {
+        let mut plat = new_plat();
+
+        set_voxels(
+            &mut plat,
+            Lr::BASE,
+            &[(uvec3(1, 1, 1), 1), (uvec3(2, 2, 2), 1)],
+        );
+        set_voxels(&mut plat, Lr::CANVAS, &[(uvec3(1, 1, 1), Lr::AIR as usize)]);
+        set_voxels(&mut plat, Lr::SCHEM, &[(uvec3(3, 3, 3), 2)]);
+
+        let chunk = plat.load_chunk(uvec3(0, 0, 0), 0, 5);
+
+        assert!(chunk.get(uvec3(1, 1, 1).to_array().into()).is_none());
+        assert_eq!(chunk.get(uvec3(2, 2, 2).to_array().into()), Some(1));
+        assert_eq!(chunk.get(uvec3(3, 3, 3).to_array().into()), Some(2));
+    }
+}
@@ -1,4 +1,5 @@
 pub mod cpu_plat;
+mod dag;
 pub mod layer;
 pub mod load;
 pub mod mesh;
@@ -1,7 +1,6 @@
 use glam::{uvec3, UVec3};
 
-use super::cpu::utils::lvl_to_size::lvl_to_size;
-
+/// Dense cube of voxels, see [super::interfaces::layer::LayerInterface::set_segment]
 pub struct Segment {
     pub level: usize,
     pub mtx: Vec<Vec<Vec<u32>>>,
@@ -12,7 +11,7 @@ pub struct SegmentStatic<const SIZE: usize> {
 
 impl Segment {
     pub fn size(&self) -> u32 {
-        lvl_to_size(self.level)
+        1 << self.level
     }
     pub fn level(&self) -> usize {
         self.level
@@ -28,6 +27,10 @@ impl Segment {
         let position = position.into();
         self.mtx[position.x as usize][position.y as usize][position.z as usize] = block;
     }
+    pub fn get(&self, position: impl Into<UVec3>) -> u32 {
+        let position = position.into();
+        self.mtx[position.x as usize][position.y as usize][position.z as usize]
+    }
     /// Iterate over local coordinates and its block types
     pub fn iter<F>(&self, mut callback: F)
     where
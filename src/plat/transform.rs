@@ -0,0 +1,160 @@
+use glam::{BVec3, EulerRot, IVec3, Mat4, Quat, Vec3};
+
+use super::normal::mesh::Mesh;
+
+/// Placement of plat in the scene
+///
+/// Maps plat space (see [crate::plat::interfaces::layer::LayerInterface::set_voxel_at]) into world space.
+/// Plat is rotated around plat space (0, 0, 0) first and translated afterwards
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlatTransform {
+    pub translation: Vec3,
+    pub rotation: Quat,
+}
+
+impl Default for PlatTransform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl PlatTransform {
+    pub const IDENTITY: Self = PlatTransform {
+        translation: Vec3::ZERO,
+        rotation: Quat::IDENTITY,
+    };
+
+    pub fn new(translation: Vec3, rotation: Quat) -> Self {
+        PlatTransform {
+            translation,
+            rotation: rotation.normalize(),
+        }
+    }
+
+    pub fn from_translation(translation: Vec3) -> Self {
+        Self::new(translation, Quat::IDENTITY)
+    }
+
+    /// Plat space -> world space
+    pub fn matrix(&self) -> Mat4 {
+        Mat4::from_rotation_translation(self.rotation, self.translation)
+    }
+
+    pub fn plat_to_world(&self, position: Vec3) -> Vec3 {
+        self.rotation * position + self.translation
+    }
+
+    pub fn world_to_plat(&self, position: Vec3) -> Vec3 {
+        self.rotation.inverse() * (position - self.translation)
+    }
+
+    /// Plat space voxel containing given world position
+    pub fn world_to_voxel(&self, position: Vec3) -> IVec3 {
+        self.world_to_plat(position).floor().as_ivec3()
+    }
+
+    /// Map ray from world space into plat space. Direction keeps its length
+    pub fn ray_to_plat(&self, origin: Vec3, direction: Vec3) -> (Vec3, Vec3) {
+        (
+            self.world_to_plat(origin),
+            self.rotation.inverse() * direction,
+        )
+    }
+
+    /// Axis aligned box in plat space covering world space box from `min` to `max`
+    ///
+    /// On rotated plat it is larger than the box itself
+    pub fn aabb_to_plat(&self, min: Vec3, max: Vec3) -> (Vec3, Vec3) {
+        let mut bounds = (Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY));
+
+        for corner in 0..8 {
+            let corner = Vec3::select(
+                BVec3::new(corner & 1 != 0, corner & 2 != 0, corner & 4 != 0),
+                max,
+                min,
+            );
+            let position = self.world_to_plat(corner);
+
+            bounds = (bounds.0.min(position), bounds.1.max(position));
+        }
+        bounds
+    }
+
+    /// Move mesh built in plat space into world space. Normals are rotated as well
+    pub fn apply_to_mesh(&self, mesh: &mut Mesh) {
+        for attr in mesh.iter_mut() {
+            let position = self.plat_to_world(Vec3::from_slice(&attr[0..3]));
+            let normal = self.rotation * Vec3::from_slice(&attr[7..10]);
+
+            attr[0..3].copy_from_slice(&position.to_array());
+            attr[7..10].copy_from_slice(&normal.to_array());
+        }
+    }
+
+    /// Rotation as euler angles in radians. Used for serialization
+    pub(crate) fn euler(&self) -> (f32, f32, f32) {
+        self.rotation.to_euler(EulerRot::XYZ)
+    }
+
+    pub(crate) fn from_euler(translation: (f32, f32, f32), (x, y, z): (f32, f32, f32)) -> Self {
+        Self::new(translation.into(), Quat::from_euler(EulerRot::XYZ, x, y, z))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
+    use glam::{ivec3, vec3, Quat};
+
+    use super::PlatTransform;
+
+    #[test]
+    fn round_trip() {
+        let transform = PlatTransform::new(
+            vec3(10., -4., 3.5),
+            Quat::from_rotation_y(FRAC_PI_2) * Quat::from_rotation_x(0.3),
+        );
+
+        for position in [vec3(0., 0., 0.), vec3(1., 2., 3.), vec3(-100., 5.5, 42.)] {
+            let world = transform.plat_to_world(position);
+            assert!(transform.world_to_plat(world).abs_diff_eq(position, 1e-4));
+            assert!(transform
+                .matrix()
+                .transform_point3(position)
+                .abs_diff_eq(world, 1e-4));
+        }
+
+        // Serialized form
+        let restored = PlatTransform::from_euler(transform.translation.into(), transform.euler());
+        assert!(restored
+            .plat_to_world(vec3(1., 2., 3.))
+            .abs_diff_eq(transform.plat_to_world(vec3(1., 2., 3.)), 1e-4));
+    }
+
+    #[test]
+    fn world_to_voxel_rotated() {
+        // Quarter turn around Y: plat +X points to world -Z
+        let transform = PlatTransform::new(vec3(100., 0., 0.), Quat::from_rotation_y(FRAC_PI_2));
+
+        assert!(transform
+            .plat_to_world(vec3(1., 0., 0.))
+            .abs_diff_eq(vec3(100., 0., -1.), 1e-5));
+        assert_eq!(
+            transform.world_to_voxel(vec3(100.5, 0.5, -3.5)),
+            ivec3(3, 0, 0)
+        );
+        assert_eq!(
+            transform.world_to_voxel(vec3(99.5, 0.5, 0.5)),
+            ivec3(-1, 0, -1)
+        );
+
+        let (origin, direction) = transform.ray_to_plat(vec3(100., 0., 0.), vec3(0., 0., -2.));
+        assert!(origin.abs_diff_eq(vec3(0., 0., 0.), 1e-5));
+        assert!(direction.abs_diff_eq(vec3(2., 0., 0.), 1e-5));
+
+        let (min, max) = transform.aabb_to_plat(vec3(100., 0., -3.), vec3(101., 1., -2.));
+        assert!(min.abs_diff_eq(vec3(2., 0., 0.), 1e-5));
+        assert!(max.abs_diff_eq(vec3(3., 1., 1.), 1e-5));
+    }
+}
@@ -1,3 +1,4 @@
+use anyhow::bail;
 use easy_compute::{
     include_spirv,
     util::{BufferInitDescriptor, DeviceExt},
@@ -7,42 +8,38 @@ use easy_compute::{
 
 use venx_core::plat::{
     chunk::chunk::{Chunk, ChunkLoadRequest},
+    layer::layer::Lr,
     node::Node,
     node_l2::NodeL2,
+    raw_plat::STANDARD_LAYERS,
 };
 
 use crate::plat::{
     interfaces::PlatInterface,
     normal::{
-        cpu_plat::CpuPlat,
+        cpu_plat::{CpuPlat, LayerComponents},
         mesh::{CHUNK_BUCKET, MESH_SIZE},
     },
 };
+/// Buffers of single layer on GPU
+#[allow(dead_code)]
+pub(crate) struct GpuLayer {
+    pub(crate) nodes: Buffer,
+    pub(crate) l2: Buffer,
+    pub(crate) bg: BindGroupVenx,
+}
+
 #[allow(dead_code)]
 pub struct GpuPlat {
-    // Meta
-    pub(crate) raw_plat_depth: Buffer,
+    // Meta. Depth and order of standard layers as u32, see [GpuPlat::new_from]
+    pub(crate) raw_plat_meta: Buffer,
     pub(crate) raw_plat_bg: BindGroupVenx,
     // raw_plat_freezed: Buffer,
-    // Base layer
-    pub(crate) base_nodes: Buffer,
-    pub(crate) base_l2: Buffer,
-    pub(crate) base_bg: BindGroupVenx,
-
-    // Tmp layer
-    pub(crate) tmp_nodes: Buffer,
-    pub(crate) tmp_l2: Buffer,
-    pub(crate) tmp_bg: BindGroupVenx,
-
-    // Schem layer
-    pub(crate) schem_nodes: Buffer,
-    pub(crate) schem_l2: Buffer,
-    pub(crate) schem_bg: BindGroupVenx,
-
-    // Canvas layer
-    pub(crate) canvas_nodes: Buffer,
-    pub(crate) canvas_l2: Buffer,
-    pub(crate) canvas_bg: BindGroupVenx,
+
+    // Layers. Index is id of layer
+    // Shaders are bound to standard layers only, see [STANDARD_LAYERS]
+    pub(crate) names: Vec<String>,
+    pub(crate) layers: Vec<GpuLayer>,
 
     // Chunks
     pub(crate) chunks_buffer: Buffer,
@@ -76,212 +73,128 @@ impl GpuPlat {
         // Prepare Staging buffers for copying
 
         // Metadata
-        let raw_plat_depth_stb = self.cs.new_staging_buffer(self.raw_plat_depth.size(), true);
-        // Base layer
-        let base_nodes_stb = self.cs.new_staging_buffer(self.base_nodes.size(), true);
-        let base_l2_stb = self.cs.new_staging_buffer(self.base_l2.size(), true);
-
-        // Tmp layer
-        let tmp_nodes_stb = self.cs.new_staging_buffer(self.tmp_nodes.size(), true);
-        let tmp_l2_stb = self.cs.new_staging_buffer(self.tmp_l2.size(), true);
-
-        // Schem layer
-        let schem_nodes_stb = self.cs.new_staging_buffer(self.schem_nodes.size(), true);
-        let schem_l2_stb = self.cs.new_staging_buffer(self.schem_l2.size(), true);
-
-        // Canvas layer
-        let canvas_nodes_stb = self.cs.new_staging_buffer(self.canvas_nodes.size(), true);
-        let canvas_l2_stb = self.cs.new_staging_buffer(self.canvas_l2.size(), true);
+        let raw_plat_meta_stb = self.cs.new_staging_buffer(self.raw_plat_meta.size(), true);
+        // Layers
+        let layers_stb: Vec<_> = self
+            .layers
+            .iter()
+            .map(|layer| {
+                (
+                    self.cs.new_staging_buffer(layer.nodes.size(), true),
+                    self.cs.new_staging_buffer(layer.l2.size(), true),
+                )
+            })
+            .collect();
 
         // Copy from buffers to staging buffers
         self.cs
             .eval(|encoder| {
                 // Metadata
                 encoder.copy_buffer_to_buffer(
-                    &self.raw_plat_depth,
+                    &self.raw_plat_meta,
                     0,
-                    &raw_plat_depth_stb,
+                    &raw_plat_meta_stb,
                     0,
-                    raw_plat_depth_stb.size(),
+                    raw_plat_meta_stb.size(),
                 );
 
-                // Base layer copy
-                // Nodes
-                encoder.copy_buffer_to_buffer(
-                    &self.base_nodes,
-                    0,
-                    &base_nodes_stb,
-                    0,
-                    self.base_nodes.size(),
-                );
-                // Entries
-                encoder.copy_buffer_to_buffer(
-                    &self.base_l2,
-                    0,
-                    &base_l2_stb,
-                    0,
-                    base_l2_stb.size(),
-                );
-
-                // Tmp layer copy
-                // Nodes
-                encoder.copy_buffer_to_buffer(
-                    &self.tmp_nodes,
-                    0,
-                    &tmp_nodes_stb,
-                    0,
-                    tmp_nodes_stb.size(),
-                );
-                // Entries
-                encoder.copy_buffer_to_buffer(&self.tmp_l2, 0, &tmp_l2_stb, 0, tmp_l2_stb.size());
-
-                // Schem layer copy
-                // Nodes
-                encoder.copy_buffer_to_buffer(
-                    &self.schem_nodes,
-                    0,
-                    &schem_nodes_stb,
-                    0,
-                    schem_nodes_stb.size(),
-                );
-                // Entries
-                encoder.copy_buffer_to_buffer(
-                    &self.schem_l2,
-                    0,
-                    &schem_l2_stb,
-                    0,
-                    schem_l2_stb.size(),
-                );
-
-                // Canvas layer copy
-                // Nodes
-                encoder.copy_buffer_to_buffer(
-                    &self.canvas_nodes,
-                    0,
-                    &canvas_nodes_stb,
-                    0,
-                    canvas_nodes_stb.size(),
-                );
-                // Entries
-                encoder.copy_buffer_to_buffer(
-                    &self.canvas_l2,
-                    0,
-                    &canvas_l2_stb,
-                    0,
-                    canvas_l2_stb.size(),
-                );
+                for (layer, (nodes_stb, l2_stb)) in self.layers.iter().zip(layers_stb.iter()) {
+                    // Nodes
+                    encoder.copy_buffer_to_buffer(&layer.nodes, 0, nodes_stb, 0, nodes_stb.size());
+                    // Entries
+                    encoder.copy_buffer_to_buffer(&layer.l2, 0, l2_stb, 0, l2_stb.size());
+                }
             })
             .await;
 
         // Map and copy
 
         // Metadata
-        let depth: Vec<usize> = raw_plat_depth_stb.read_manual().await;
-        raw_plat_depth_stb.unmap();
-
-        // Base layer
-        let base_nodes: Vec<Node> = base_nodes_stb.read_manual().await;
-        base_nodes_stb.unmap();
-        let base_l2: Vec<NodeL2> = base_l2_stb.read_manual().await;
-        base_l2_stb.unmap();
-
-        // Tmp layer
-        let tmp_nodes: Vec<Node> = tmp_nodes_stb.read_manual().await;
-        tmp_nodes_stb.unmap();
-        let tmp_l2: Vec<NodeL2> = tmp_l2_stb.read_manual().await;
-        tmp_l2_stb.unmap();
-
-        // Schem layer
-        let schem_nodes: Vec<Node> = schem_nodes_stb.read_manual().await;
-        schem_nodes_stb.unmap();
-        let schem_l2: Vec<NodeL2> = schem_l2_stb.read_manual().await;
-        schem_l2_stb.unmap();
-
-        // Canvas layer
-        let canvas_nodes: Vec<Node> = canvas_nodes_stb.read_manual().await;
-        canvas_nodes_stb.unmap();
-        let canvas_l2: Vec<NodeL2> = canvas_l2_stb.read_manual().await;
-        canvas_l2_stb.unmap();
+        let meta: Vec<u32> = raw_plat_meta_stb.read_manual().await;
+        raw_plat_meta_stb.unmap();
+
+        // Layers
+        let mut components = vec![];
+        for (nodes_stb, l2_stb) in layers_stb {
+            let nodes: Vec<Node> = nodes_stb.read_manual().await;
+            nodes_stb.unmap();
+            let l2: Vec<NodeL2> = l2_stb.read_manual().await;
+            l2_stb.unmap();
+
+            components.push((nodes, l2));
+        }
 
         // Create CpuPlat from copied
         // WARNING! Hardcoded values
-        CpuPlat::from_existing(
-            depth[0],
-            5,
-            6,
-            (base_nodes, base_l2),
-            (tmp_nodes, tmp_l2),
-            (schem_nodes, schem_l2),
-            (canvas_nodes, canvas_l2),
-        )
-        // CpuPlat::new_from(
-        //     depth[0],
-        //     5,
-        //     6,
-        //     (base_nodes, base_l2),
-        //     (tmp_nodes, tmp_l2),
-        //     (schem_nodes, schem_l2),
-        //     (canvas_nodes, canvas_l2),
-        // )
+        CpuPlat::from_existing(meta[0] as usize, 5, 6, self.names, components)
     }
     pub async fn new_plat(depth: usize, chunk_level: usize, segment_level: usize) -> Self {
         // TODO: make more flexible
         let base = (vec![Node::default(); 128], vec![NodeL2::default(); 10]);
-        let (tmp, schem, canvas) = (base.clone(), base.clone(), base.clone());
+        let names = STANDARD_LAYERS
+            .iter()
+            .map(|name| name.to_string())
+            .collect();
+        let components = vec![base; STANDARD_LAYERS.len()];
 
-        Self::new_from(depth, chunk_level, segment_level, base, tmp, schem, canvas).await
+        let order: Vec<usize> = (0..STANDARD_LAYERS.len()).collect();
+
+        Self::new_from(depth, chunk_level, segment_level, names, components, &order)
+            .await
+            .expect("Standard layers are always bound")
     }
 
+    /// Upload layers to GPU
+    ///
+    /// Shaders are bound to standard layers only (see [STANDARD_LAYERS]),
+    /// so it fails if there are custom layers, instead of dropping them silently
+
     pub(crate) async fn new_from(
         depth: usize,
         _chunk_level: usize,
         _segment_level: usize,
-        base: (Vec<Node>, Vec<NodeL2>),
-        tmp: (Vec<Node>, Vec<NodeL2>),
-        schem: (Vec<Node>, Vec<NodeL2>),
-        canvas: (Vec<Node>, Vec<NodeL2>),
-    ) -> Self {
+        names: Vec<String>,
+        components: Vec<LayerComponents>,
+        order: &[usize],
+    ) -> anyhow::Result<Self> {
+        // Shaders expect every standard layer to be bound
+        assert!(components.len() >= STANDARD_LAYERS.len());
+        assert_eq!(names.len(), components.len());
+
+        if components.len() > STANDARD_LAYERS.len() {
+            bail!(
+                "Custom layers ({}) cannot be transferred to GPU, shaders bind only standard ones",
+                names[STANDARD_LAYERS.len()..].join(", ")
+            );
+        }
+
         let mut cs = ComputeServer::new().await;
 
         // Allocate buffers
 
         // Metadata layer
-        let raw_plat_depth = cs.new_buffer(bytemuck::cast_slice(&[depth]));
+        // [depth, ids of standard layers from bottom to top]
+        let mut meta = vec![depth as u32];
+        meta.extend(order.iter().map(|&id| id as u32));
+        let raw_plat_meta = cs.new_buffer(bytemuck::cast_slice(&meta));
         let raw_plat_bg = BindGroupBuilder::new()
-            .insert(0, false, raw_plat_depth.as_entire_binding())
+            .insert(0, false, raw_plat_meta.as_entire_binding())
             .build(&cs);
 
-        // Base layer
-        let base_nodes_buffer = cs.new_buffer(bytemuck::cast_slice(&base.0));
-        let base_l2_buffer = cs.new_buffer(bytemuck::cast_slice(&base.1));
-        let base_bg = BindGroupBuilder::new()
-            .insert(0, false, base_nodes_buffer.as_entire_binding())
-            .insert(1, false, base_l2_buffer.as_entire_binding())
-            .build(&cs);
-
-        // Tmp layer
-        let tmp_nodes_buffer = cs.new_buffer(bytemuck::cast_slice(&tmp.0));
-        let tmp_l2_buffer = cs.new_buffer(bytemuck::cast_slice(&tmp.1));
-        let tmp_bg = BindGroupBuilder::new()
-            .insert(0, false, tmp_nodes_buffer.as_entire_binding())
-            .insert(1, false, tmp_l2_buffer.as_entire_binding())
-            .build(&cs);
-
-        // Schem layer
-        let schem_nodes_buffer = cs.new_buffer(bytemuck::cast_slice(&schem.0));
-        let schem_l2_buffer = cs.new_buffer(bytemuck::cast_slice(&schem.1));
-        let schem_bg = BindGroupBuilder::new()
-            .insert(0, false, schem_nodes_buffer.as_entire_binding())
-            .insert(1, false, schem_l2_buffer.as_entire_binding())
-            .build(&cs);
-
-        // Canvas layer
-        let canvas_nodes_buffer = cs.new_buffer(bytemuck::cast_slice(&canvas.0));
-        let canvas_l2_buffer = cs.new_buffer(bytemuck::cast_slice(&canvas.1));
-        let canvas_bg = BindGroupBuilder::new()
-            .insert(0, false, canvas_nodes_buffer.as_entire_binding())
-            .insert(1, false, canvas_l2_buffer.as_entire_binding())
-            .build(&cs);
+        // Layers
+        let layers: Vec<GpuLayer> = components
+            .iter()
+            .map(|(nodes, l2)| {
+                let nodes = cs.new_buffer(bytemuck::cast_slice(nodes));
+                let l2 = cs.new_buffer(bytemuck::cast_slice(l2));
+                let bg = BindGroupBuilder::new()
+                    .insert(0, false, nodes.as_entire_binding())
+                    .insert(1, false, l2.as_entire_binding())
+                    .build(&cs);
+
+                GpuLayer { nodes, l2, bg }
+            })
+            .collect();
 
         // Load shaders
         let module = cs
@@ -328,10 +241,10 @@ impl GpuPlat {
 
         // Load pipelines
         let load_chunk_pl = PipelineBuilder::new(&module, "load_chunk")
-            .for_bindgroup(&base_bg)
-            .for_bindgroup(&tmp_bg)
-            .for_bindgroup(&schem_bg)
-            .for_bindgroup(&canvas_bg)
+            .for_bindgroup(&layers[Lr::BASE].bg)
+            .for_bindgroup(&layers[Lr::TMP].bg)
+            .for_bindgroup(&layers[Lr::SCHEM].bg)
+            .for_bindgroup(&layers[Lr::CANVAS].bg)
             .for_bindgroup(&raw_plat_bg)
             .for_bindgroup(&chunk_bg)
             .build(&cs);
@@ -359,32 +272,22 @@ impl GpuPlat {
 
         // Load pipelines
         let to_mesh_greedy_pl = PipelineBuilder::new(&module, "to_mesh_greedy")
-            .for_bindgroup(&base_bg)
-            .for_bindgroup(&tmp_bg)
-            .for_bindgroup(&schem_bg)
-            .for_bindgroup(&canvas_bg)
+            .for_bindgroup(&layers[Lr::BASE].bg)
+            .for_bindgroup(&layers[Lr::TMP].bg)
+            .for_bindgroup(&layers[Lr::SCHEM].bg)
+            .for_bindgroup(&layers[Lr::CANVAS].bg)
             .for_bindgroup(&raw_plat_bg)
             .for_bindgroup(&chunk_bg)
             .for_bindgroup(&mesh_helper_bg)
             .build(&cs);
 
-        Self {
-            base_nodes: base_nodes_buffer,
-            base_l2: base_l2_buffer,
-            base_bg,
-            tmp_nodes: tmp_nodes_buffer,
-            tmp_l2: tmp_l2_buffer,
-            tmp_bg,
-            schem_nodes: schem_nodes_buffer,
-            schem_l2: schem_l2_buffer,
-            schem_bg,
-            canvas_nodes: canvas_nodes_buffer,
-            canvas_l2: canvas_l2_buffer,
-            canvas_bg,
+        Ok(Self {
+            names,
+            layers,
             cs,
             module,
             load_chunk_pl,
-            raw_plat_depth,
+            raw_plat_meta,
             raw_plat_bg,
             chunks_buffer: chunk_buffer,
             chunk_bg,
@@ -399,6 +302,6 @@ impl GpuPlat {
             mesh,
             chunks_requests_buffer: chunk_requests_buffer,
             chunks_requests_staging_buffer: chunk_requests_staging_buffer,
-        }
+        })
     }
 }
@@ -7,8 +7,9 @@ use easy_compute::{
 
 use log::info;
 use pollster::block_on;
-use venx_core::{
-    plat::chunk::chunk::{Chunk, ChunkLoadRequest},
+use venx_core::plat::{
+    chunk::chunk::{Chunk, ChunkLoadRequest},
+    raw_plat::STANDARD_LAYERS,
 };
 
 use crate::plat::{
@@ -32,10 +33,11 @@ impl LoadInterface for GpuPlat {
                             encoder.begin_compute_pass(&ComputePassDescriptor { label: None });
                         cpass.set_pipeline(&self.to_mesh_greedy_pl);
 
-                        cpass.set_bind_group(0, &self.base_bg.bindgroup, &[]);
-                        cpass.set_bind_group(1, &self.tmp_bg.bindgroup, &[]);
-                        cpass.set_bind_group(2, &self.schem_bg.bindgroup, &[]);
-                        cpass.set_bind_group(3, &self.canvas_bg.bindgroup, &[]);
+                        // Standard layers
+                        let standard_layers = &self.layers[..STANDARD_LAYERS.len()];
+                        for (i, layer) in standard_layers.iter().enumerate() {
+                            cpass.set_bind_group(i as u32, &layer.bg.bindgroup, &[]);
+                        }
                         cpass.set_bind_group(4, &self.raw_plat_bg.bindgroup, &[]);
                         cpass.set_bind_group(5, &self.chunk_bg.bindgroup, &[]);
                         cpass.set_bind_group(6, &self.mesh_helpers_bg.bindgroup, &[]);
@@ -102,10 +104,11 @@ impl LoadInterface for GpuPlat {
                             encoder.begin_compute_pass(&ComputePassDescriptor { label: None });
                         cpass.set_pipeline(&self.load_chunk_pl);
 
-                        cpass.set_bind_group(0, &self.base_bg.bindgroup, &[]);
-                        cpass.set_bind_group(1, &self.tmp_bg.bindgroup, &[]);
-                        cpass.set_bind_group(2, &self.schem_bg.bindgroup, &[]);
-                        cpass.set_bind_group(3, &self.canvas_bg.bindgroup, &[]);
+                        // Standard layers
+                        let standard_layers = &self.layers[..STANDARD_LAYERS.len()];
+                        for (i, layer) in standard_layers.iter().enumerate() {
+                            cpass.set_bind_group(i as u32, &layer.bg.bindgroup, &[]);
+                        }
                         cpass.set_bind_group(4, &self.raw_plat_bg.bindgroup, &[]);
                         cpass.set_bind_group(5, &self.chunk_bg.bindgroup, &[]);
                         cpass.dispatch_workgroups(blank_chunks.len() as u32, 1, 1);